@@ -1,10 +1,19 @@
-use std::{collections::LinkedList, path::Path};
+use std::{collections::LinkedList, path::{Path, PathBuf}};
 
 use jumprope::JumpRope;
 
 pub struct FileBuffer {
     changes: LinkedList<Change>,
     current: JumpRope,
+    path: Option<PathBuf>,
+    ///Name for buffers which aren't files, like *log*
+    name: Option<String>,
+    dirty: bool,
+    ///Char index of the start of each line, there is always at least one line
+    line_starts: Vec<usize>,
+    ///Revision each line was last changed in, so the frontend can tell which lines to redraw
+    line_revisions: Vec<u64>,
+    revision: u64,
 }
 
 impl FileBuffer {
@@ -12,11 +21,34 @@ impl FileBuffer {
         FileBuffer {
             changes: LinkedList::<Change>::new(),
             current: JumpRope::new(),
+            path: None,
+            name: None,
+            dirty: false,
+            line_starts: vec![0],
+            line_revisions: vec![0],
+            revision: 0,
         }
     }
 
     pub fn from_str(string: &str) -> Self {
-        todo!()
+        let line_starts = line_starts(string, 0);
+        FileBuffer {
+            changes: LinkedList::<Change>::new(),
+            current: JumpRope::from(string),
+            path: None,
+            name: None,
+            dirty: false,
+            line_revisions: vec![0; line_starts.len()],
+            line_starts,
+            revision: 0,
+        }
+    }
+
+    ///Creates a buffer which isn't backed by a file, shown to the user as `name`.
+    pub fn scratch(name: &str, string: &str) -> Self {
+        let mut buffer = FileBuffer::from_str(string);
+        buffer.name = Some(name.to_string());
+        buffer
     }
 
     pub fn from_file(path: &Path) -> Self {
@@ -24,6 +56,104 @@ impl FileBuffer {
     }
 
     pub fn insert() {}
+
+    ///Get the path of the file this buffer was loaded from, if it has one.
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    ///Get a short name for this buffer for showing to the user, the file name, the scratch buffer name or "untitled".
+    pub fn get_display_name(&self) -> String {
+        self.get_path()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| String::from("untitled"))
+    }
+
+    ///Get whether this buffer has changes that haven't been saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    ///Mark whether this buffer has changes that haven't been saved.
+    #[cfg(test)]
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    ///Adds text to the end of this buffer.  Used for output buffers like *log*, which is why it doesn't mark the buffer dirty.
+    pub fn append_text(&mut self, text: &str) {
+        let end = self.current.len_chars();
+        self.current.insert(end, text);
+
+        //the last line gets longer and any new lines are added after it
+        self.revision += 1;
+        *self.line_revisions.last_mut().expect("There is always at least one line!") = self.revision;
+        for start in line_starts(text, end).into_iter().skip(1) {
+            self.line_starts.push(start);
+            self.line_revisions.push(self.revision);
+        }
+    }
+
+    ///Removes the first `count` lines along with their newlines, always leaving the last line.  Every line left moves
+    ///up, so they all count as changed.
+    pub fn remove_first_lines(&mut self, count: usize) {
+        let count = count.min(self.line_starts.len() - 1);
+        if count == 0 {
+            return;
+        }
+
+        let end = self.line_starts[count];
+        self.current.remove(0..end);
+        self.line_starts.drain(..count);
+        for start in &mut self.line_starts {
+            *start -= end;
+        }
+
+        self.revision += 1;
+        self.line_revisions.truncate(self.line_starts.len());
+        self.line_revisions.fill(self.revision);
+    }
+
+    ///Get the revision of the most recent change to this buffer.
+    #[cfg(test)]
+    pub fn get_revision(&self) -> u64 {
+        self.revision
+    }
+
+    ///Get the number of lines in this buffer. Text after the last newline counts as a line, even if it's empty.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    ///Get the revision line `line` was last changed in.
+    pub fn get_line_revision(&self, line: usize) -> Option<u64> {
+        self.line_revisions.get(line).copied()
+    }
+
+    ///Get the text of line `line`, without its newline.
+    pub fn get_line(&self, line: usize) -> Option<String> {
+        let start = *self.line_starts.get(line)?;
+        let end = match self.line_starts.get(line + 1) {
+            Some(next) => next - 1,
+            None => self.current.len_chars()
+        };
+        let text: String = self.current.slice_substrings(start..end).collect();
+        Some(text.strip_suffix('\r').map(str::to_string).unwrap_or(text))
+    }
+
+    ///Get the full contents of this buffer.
+    pub fn get_text(&self) -> String {
+        self.current.to_string()
+    }
+}
+
+///Finds the char index of the start of each line in `text`, as if it was inserted at char index `offset`.
+fn line_starts(text: &str, offset: usize) -> Vec<usize> {
+    let mut starts = vec![offset];
+    starts.extend(text.chars().enumerate().filter(|(_, character)| *character == '\n').map(|(index, _)| offset + index + 1));
+    starts
 }
 
 enum Change {
@@ -43,4 +173,60 @@ mod tests{
         let x = FileBuffer::new();
         assert_eq!(x.changes.len(), 0);
     }
+
+    #[test]
+    fn from_str_has_text(){
+        let x = FileBuffer::from_str("hello");
+        assert_eq!(x.get_text(), "hello");
+    }
+
+    #[test]
+    fn append_text_adds_to_end(){
+        let mut x = FileBuffer::scratch("*log*", "a\n");
+        x.append_text("b\n");
+        assert_eq!(x.get_text(), "a\nb\n");
+        assert_eq!(x.get_display_name(), "*log*");
+    }
+
+    #[test]
+    fn lines_are_split_on_newlines(){
+        let x = FileBuffer::from_str("one\r\ntwo\n\nfour");
+        assert_eq!(x.line_count(), 4);
+        assert_eq!(x.get_line(0).as_deref(), Some("one"));
+        assert_eq!(x.get_line(2).as_deref(), Some(""));
+        assert_eq!(x.get_line(3).as_deref(), Some("four"));
+        assert_eq!(x.get_line(4), None);
+    }
+
+    #[test]
+    fn append_text_only_changes_revision_of_touched_lines(){
+        let mut x = FileBuffer::from_str("a\nb");
+        x.append_text("c\nd\n");
+        assert_eq!(x.line_count(), 4);
+        assert_eq!(x.get_line(1).as_deref(), Some("bc"));
+        assert_eq!(x.get_line(3).as_deref(), Some(""));
+        assert_eq!(x.get_line_revision(0), Some(0));
+        assert_eq!(x.get_line_revision(1), Some(x.get_revision()));
+        assert_eq!(x.get_line_revision(3), Some(x.get_revision()));
+    }
+
+    #[test]
+    fn removing_first_lines_moves_the_rest_up(){
+        let mut x = FileBuffer::from_str("a\nb\nc\n");
+        x.remove_first_lines(2);
+        assert_eq!(x.get_text(), "c\n");
+        assert_eq!(x.line_count(), 2);
+        assert_eq!(x.get_line(0).as_deref(), Some("c"));
+        assert_eq!(x.get_line_revision(0), Some(x.get_revision()));
+
+        x.remove_first_lines(5);
+        assert_eq!(x.get_text(), "");
+        assert_eq!(x.line_count(), 1);
+    }
+
+    #[test]
+    fn new_filebuffer_is_clean(){
+        let x = FileBuffer::new();
+        assert!(!x.is_dirty());
+    }
 }
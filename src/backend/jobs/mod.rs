@@ -0,0 +1,2 @@
+pub mod scheduler;
+pub mod search;
\ No newline at end of file
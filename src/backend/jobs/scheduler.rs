@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::intermediary::message_queue::{MessageQueue, BackendMessage, JobOutcome};
+use crate::intermediary::panic_hook;
+use crate::intermediary::supervisor::panic_message;
+
+pub type JobId = u64;
+
+///A piece of backend work that runs on the worker pool instead of blocking the backend loop.
+pub trait Job: Send{
+    ///Name of the job for showing to the user.
+    fn name(&self) -> String;
+
+    ///Jobs with the same key supersede each other: submitting a new one cancels the old one (a new search query, for example).
+    fn supersede_key(&self) -> Option<&'static str>{
+        None
+    }
+
+    ///Does the work.  Long running jobs should check [JobContext::is_cancelled] regularly and return early when it is set.
+    fn run(self: Box<Self>, context: &JobContext) -> Result<(), String>;
+}
+
+///Handed to a running job so it can report progress, send results and notice when it has been cancelled.
+pub struct JobContext{
+    id: JobId,
+    cancelled: Arc<AtomicBool>,
+    backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>
+}
+
+impl JobContext{
+    ///Gets whether the job has been cancelled and should stop.
+    pub fn is_cancelled(&self) -> bool{
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    ///Tells the frontend how far along the job is.  `progress` is from 0.0 to 1.0, or None if the job can't tell.
+    pub fn report_progress(&self, progress: Option<f32>, status: &str){
+        self.send(BackendMessage::JobProgress{
+            id: self.id,
+            progress,
+            status: status.to_string()
+        });
+    }
+
+    ///Sends a message, usually results, to the frontend.
+    pub fn send(&self, message: BackendMessage){
+        self.backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(message);
+    }
+}
+
+///A job waiting for a worker.
+struct QueuedJob{
+    id: JobId,
+    job: Box<dyn Job>,
+    cancelled: Arc<AtomicBool>
+}
+
+///Runs [Job]s on a pool of worker threads.  Every job reports JobStarted, any progress, and JobFinished to the frontend.
+pub struct JobScheduler{
+    sender: Option<Sender<QueuedJob>>,
+    workers: Vec<JoinHandle<()>>,
+    next_id: JobId,
+    ///Cancellation flags of jobs which are queued or running. Workers remove a job once it has finished.
+    active: Arc<Mutex<HashMap<JobId, Arc<AtomicBool>>>>,
+    ///The latest job submitted for each supersede key.
+    superseding: HashMap<&'static str, JobId>,
+    backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>
+}
+
+impl JobScheduler{
+    ///Creates a scheduler with `worker_count` worker threads, which send their messages to `backend_message_queue`.
+    pub fn new(worker_count: usize, backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>) -> JobScheduler{
+        let (sender, receiver) = mpsc::channel::<QueuedJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let active: Arc<Mutex<HashMap<JobId, Arc<AtomicBool>>>> = Arc::new(Mutex::new(HashMap::new()));
+
+        let workers = (0..worker_count.max(1)).map(|i| {
+            let receiver = receiver.clone();
+            let active = active.clone();
+            let backend_message_queue = backend_message_queue.clone();
+            thread::Builder::new()
+                .name(format!("job worker {}", i))
+                .spawn(move || worker(receiver, active, backend_message_queue))
+                .expect("Failed to spawn job worker!")
+        }).collect();
+
+        JobScheduler{
+            sender: Some(sender),
+            workers,
+            next_id: 0,
+            active,
+            superseding: HashMap::new(),
+            backend_message_queue
+        }
+    }
+
+    ///Queues a job to run on the worker pool, cancelling the job it supersedes if there is one.
+    pub fn submit(&mut self, job: Box<dyn Job>) -> JobId{
+        let id = self.next_id;
+        self.next_id += 1;
+
+        if let Some(key) = job.supersede_key(){
+            if let Some(old_id) = self.superseding.insert(key, id){
+                self.cancel(old_id);
+            }
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.active.lock().unwrap_or_else(|e| e.into_inner()).insert(id, cancelled.clone());
+
+        self.backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::JobStarted{
+            id,
+            name: job.name()
+        });
+
+        if let Some(sender) = &self.sender{
+            sender.send(QueuedJob{ id, job, cancelled }).expect("Job workers have stopped!");
+        }
+
+        id
+    }
+
+    ///Cancels a queued or running job.  Does nothing if the job has already finished.
+    pub fn cancel(&mut self, id: JobId){
+        if let Some(cancelled) = self.active.lock().unwrap_or_else(|e| e.into_inner()).get(&id){
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    ///Cancels every job and waits for the workers to stop.
+    pub fn shutdown(mut self){
+        for cancelled in self.active.lock().unwrap_or_else(|e| e.into_inner()).values(){
+            cancelled.store(true, Ordering::Relaxed);
+        }
+
+        //dropping the sender makes the workers' recv() fail once the queue is drained
+        self.sender = None;
+        for worker in self.workers.drain(..){
+            let _ = worker.join();
+        }
+    }
+}
+
+///Loop run by each worker thread: take the next job, run it unless it was cancelled while queued, and report how it went.
+fn worker(receiver: Arc<Mutex<Receiver<QueuedJob>>>, active: Arc<Mutex<HashMap<JobId, Arc<AtomicBool>>>>, backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>){
+    loop{
+        let queued = match receiver.lock().unwrap_or_else(|e| e.into_inner()).recv(){
+            Ok(queued) => queued,
+            Err(..) => break
+        };
+
+        let context = JobContext{
+            id: queued.id,
+            cancelled: queued.cancelled,
+            backend_message_queue: backend_message_queue.clone()
+        };
+
+        let outcome = if context.is_cancelled(){
+            JobOutcome::Cancelled
+        } else {
+            let job = queued.job;
+            match panic_hook::catch_panic(AssertUnwindSafe(|| job.run(&context))){
+                Ok(..) if context.is_cancelled() => JobOutcome::Cancelled,
+                Ok(Ok(())) => JobOutcome::Completed,
+                Ok(Err(error)) => JobOutcome::Failed(error),
+                Err(payload) => JobOutcome::Failed(format!("job panicked: {}", panic_message(payload.as_ref())))
+            }
+        };
+
+        active.lock().unwrap_or_else(|e| e.into_inner()).remove(&context.id);
+        context.send(BackendMessage::JobFinished{
+            id: context.id,
+            outcome
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::cell::Cell;
+    use std::fs;
+    use std::panic;
+    use std::time::{Duration, Instant};
+
+    use crate::backend::file_buffer::FileBuffer;
+
+    ///Waits until the job runs or is cancelled, then finishes.
+    struct WaitForCancel{
+        key: Option<&'static str>
+    }
+
+    impl Job for WaitForCancel{
+        fn name(&self) -> String{
+            String::from("wait")
+        }
+
+        fn supersede_key(&self) -> Option<&'static str>{
+            self.key
+        }
+
+        fn run(self: Box<Self>, context: &JobContext) -> Result<(), String>{
+            while !context.is_cancelled(){
+                thread::sleep(Duration::from_millis(1));
+            }
+            Ok(())
+        }
+    }
+
+    struct Quick;
+
+    impl Job for Quick{
+        fn name(&self) -> String{
+            String::from("quick")
+        }
+
+        fn run(self: Box<Self>, context: &JobContext) -> Result<(), String>{
+            context.report_progress(Some(0.5), "halfway");
+            Ok(())
+        }
+    }
+
+    thread_local!{
+        ///Set on the threads whose panics the test's panic hook handles, so panics in other tests don't reach it.
+        static HOOKED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    struct Panics;
+
+    impl Job for Panics{
+        fn name(&self) -> String{
+            String::from("panics")
+        }
+
+        fn run(self: Box<Self>, _context: &JobContext) -> Result<(), String>{
+            HOOKED.with(|hooked| hooked.set(true));
+            panic!("job went wrong");
+        }
+    }
+
+    ///Waits for the JobFinished message of `id`, returning its outcome and any progress reported along the way.
+    fn wait_for_outcome(queue: &Arc<Mutex<MessageQueue<BackendMessage>>>, id: JobId) -> (JobOutcome, Vec<Option<f32>>){
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut progress_reports = Vec::new();
+        while Instant::now() < deadline{
+            let mut queue = queue.lock().unwrap();
+            while !queue.is_empty(){
+                match queue.get_message(){
+                    BackendMessage::JobProgress{ id: job, progress, .. } if job == id => progress_reports.push(progress),
+                    BackendMessage::JobFinished{ id: job, outcome } if job == id => return (outcome, progress_reports),
+                    _ => {}
+                }
+            }
+            drop(queue);
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Job never finished!");
+    }
+
+    #[test]
+    fn job_completes(){
+        let queue = Arc::new(Mutex::new(MessageQueue::new()));
+        let mut scheduler = JobScheduler::new(1, queue.clone());
+
+        let id = scheduler.submit(Box::new(Quick));
+
+        assert_eq!(wait_for_outcome(&queue, id).0, JobOutcome::Completed);
+        scheduler.shutdown();
+    }
+
+    #[test]
+    fn cancelled_job_reports_cancelled(){
+        let queue = Arc::new(Mutex::new(MessageQueue::new()));
+        let mut scheduler = JobScheduler::new(1, queue.clone());
+
+        let id = scheduler.submit(Box::new(WaitForCancel{ key: None }));
+        scheduler.cancel(id);
+
+        assert_eq!(wait_for_outcome(&queue, id).0, JobOutcome::Cancelled);
+        scheduler.shutdown();
+    }
+
+    #[test]
+    fn new_job_cancels_superseded_job(){
+        let queue = Arc::new(Mutex::new(MessageQueue::new()));
+        let mut scheduler = JobScheduler::new(2, queue.clone());
+
+        let old = scheduler.submit(Box::new(WaitForCancel{ key: Some("search") }));
+        let new = scheduler.submit(Box::new(WaitForCancel{ key: Some("search") }));
+
+        assert_eq!(wait_for_outcome(&queue, old).0, JobOutcome::Cancelled);
+        scheduler.cancel(new);
+        scheduler.shutdown();
+    }
+
+    #[test]
+    fn progress_is_reported(){
+        let queue = Arc::new(Mutex::new(MessageQueue::new()));
+        let mut scheduler = JobScheduler::new(1, queue.clone());
+
+        let id = scheduler.submit(Box::new(Quick));
+
+        assert_eq!(wait_for_outcome(&queue, id).1, vec![Some(0.5)]);
+        scheduler.shutdown();
+    }
+
+    #[test]
+    fn panicking_job_writes_no_crash_report_or_recovery_files(){
+        let directory = std::env::temp_dir().join(format!("digit-job-panic-test-{}", std::process::id()));
+        let recovery_dir = directory.join("recovery");
+        let crash_dir = directory.join("crashes");
+
+        let mut dirty = FileBuffer::from_str("unsaved work");
+        dirty.set_dirty(true);
+        let crash_handler = panic_hook::crash_handler(Arc::new(Mutex::new(vec![dirty])), recovery_dir.clone(), crash_dir.clone());
+        //shared so it can be put back afterwards, other tests panicking meanwhile still go through it
+        let previous_hook: Arc<dyn Fn(&panic::PanicHookInfo) + Send + Sync> = Arc::from(panic::take_hook());
+        let hooked_previous = previous_hook.clone();
+        panic::set_hook(Box::new(move |info| {
+            if HOOKED.with(Cell::get){
+                crash_handler(info);
+            }else{
+                hooked_previous(info);
+            }
+        }));
+
+        let queue = Arc::new(Mutex::new(MessageQueue::new()));
+        let mut scheduler = JobScheduler::new(1, queue.clone());
+        let id = scheduler.submit(Box::new(Panics));
+        let outcome = wait_for_outcome(&queue, id).0;
+        scheduler.shutdown();
+        let job_left_files = recovery_dir.exists() || crash_dir.exists();
+
+        //the same panic outside of a job is reported, so the hook is actually in place
+        let _ = thread::spawn(|| {
+            HOOKED.with(|hooked| hooked.set(true));
+            panic!("thread went wrong");
+        }).join();
+        let thread_left_files = recovery_dir.exists() && crash_dir.exists();
+
+        panic::set_hook(Box::new(move |info| previous_hook(info)));
+        let _ = fs::remove_dir_all(&directory);
+
+        assert_eq!(outcome, JobOutcome::Failed(String::from("job panicked: job went wrong")));
+        assert!(!job_left_files);
+        assert!(thread_left_files);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backend::jobs::scheduler::{Job, JobContext};
+use crate::intermediary::message_queue::{BackendMessage, SearchMatch};
+
+///Progress is reported after this many files have been searched.
+const PROGRESS_INTERVAL: usize = 64;
+
+///The search stops once it has found this many matches, so a common query doesn't send every line of the project.
+pub const MAX_SEARCH_MATCHES: usize = 1000;
+
+///Searches every text file under a directory for lines containing a query.
+///A new search supersedes the previous one, so only the latest query keeps running.
+pub struct ProjectSearchJob{
+    root: PathBuf,
+    query: String
+}
+
+impl ProjectSearchJob{
+    pub fn new(root: &Path, query: &str) -> ProjectSearchJob{
+        ProjectSearchJob{
+            root: root.to_path_buf(),
+            query: query.to_string()
+        }
+    }
+}
+
+impl Job for ProjectSearchJob{
+    fn name(&self) -> String{
+        format!("Searching for \"{}\"", self.query)
+    }
+
+    fn supersede_key(&self) -> Option<&'static str>{
+        Some("project search")
+    }
+
+    fn run(self: Box<Self>, context: &JobContext) -> Result<(), String>{
+        context.report_progress(None, "Indexing files");
+
+        let mut files = Vec::new();
+        collect_files(&self.root, &mut files, context).map_err(|e| e.to_string())?;
+
+        let mut matches = Vec::new();
+        let mut truncated = false;
+        for (i, path) in files.iter().enumerate(){
+            if context.is_cancelled(){
+                return Ok(());
+            }
+
+            if i % PROGRESS_INTERVAL == 0{
+                context.report_progress(Some(i as f32 / files.len() as f32), &format!("Searched {} of {} files", i, files.len()));
+            }
+
+            //files that can't be read as text are skipped rather than failing the whole search
+            //one match past the limit is asked for to tell whether there were more
+            if let Ok(contents) = fs::read_to_string(path){
+                matches.extend(search_text(path, &contents, &self.query, MAX_SEARCH_MATCHES - matches.len() + 1));
+            }
+            if matches.len() > MAX_SEARCH_MATCHES{
+                matches.truncate(MAX_SEARCH_MATCHES);
+                truncated = true;
+                break;
+            }
+        }
+
+        context.send(BackendMessage::SearchResults{
+            query: self.query,
+            matches,
+            truncated
+        });
+
+        Ok(())
+    }
+}
+
+///Recursively collects every file under `directory`, skipping hidden entries and build output.
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>, context: &JobContext) -> std::io::Result<()>{
+    for entry in fs::read_dir(directory)?{
+        if context.is_cancelled(){
+            return Ok(());
+        }
+
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || name == "target"{
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir(){
+            collect_files(&entry.path(), files, context)?;
+        } else if file_type.is_file(){
+            files.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+///Finds the first `limit` lines of `contents` containing `query`.
+pub fn search_text(path: &Path, contents: &str, query: &str, limit: usize) -> Vec<SearchMatch>{
+    if query.is_empty(){
+        return Vec::new();
+    }
+
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains(query))
+        .take(limit)
+        .map(|(i, line)| SearchMatch{
+            path: path.to_path_buf(),
+            line: i + 1,
+            text: line.to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn matching_lines_are_found_with_line_numbers(){
+        let matches = search_text(Path::new("a.rs"), "fn main(){\n    let x = 1;\n}\nfn other(){}", "fn ", 10);
+
+        let lines: Vec<usize> = matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 4]);
+    }
+
+    #[test]
+    fn empty_query_matches_nothing(){
+        assert!(search_text(Path::new("a.rs"), "anything", "", 10).is_empty());
+    }
+
+    #[test]
+    fn search_stops_at_the_limit(){
+        let matches = search_text(Path::new("a.rs"), "x\nx\nx\nx", "x", 2);
+
+        let lines: Vec<usize> = matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 2]);
+    }
+}
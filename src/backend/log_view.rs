@@ -0,0 +1,136 @@
+use log::Level;
+
+use crate::backend::file_buffer::FileBuffer;
+use crate::intermediary::logging::{self, LogEntry};
+
+///Name of the buffer showing the log.
+pub const LOG_BUFFER_NAME: &str = "*log*";
+
+///How many entries the *log* buffer keeps before dropping the oldest, as many as the log history does.
+pub const LOG_BUFFER_LINES: usize = logging::LOG_HISTORY_CAPACITY;
+
+///Which log entries the *log* buffer shows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter{
+    ///Entries less severe than this are hidden.
+    pub level: Level,
+    ///If set, only entries logged from modules starting with this are shown, e.g. digit::frontend
+    pub module: Option<String>
+}
+
+impl LogFilter{
+    ///Gets whether `entry` should be shown.
+    pub fn matches(&self, entry: &LogEntry) -> bool{
+        if entry.level > self.level{
+            return false;
+        }
+
+        match &self.module{
+            Some(module) => entry.target.starts_with(module.as_str()),
+            None => true
+        }
+    }
+}
+
+///Keeps the *log* buffer up to date with new log entries.
+pub struct LogView{
+    filter: LogFilter,
+    ///Index of the *log* buffer in the open buffer list.
+    buffer_index: usize,
+    ///Sequence number of the next entry to look at.
+    next_sequence: u64
+}
+
+impl LogView{
+    ///Creates a view which writes to the buffer at `buffer_index`, starting from the oldest kept entry.
+    pub fn new(filter: LogFilter, buffer_index: usize) -> LogView{
+        LogView{
+            filter,
+            buffer_index,
+            next_sequence: 0
+        }
+    }
+
+    ///Get the index of the *log* buffer in the open buffer list.
+    pub fn get_buffer_index(&self) -> usize{
+        self.buffer_index
+    }
+
+    ///Appends any new entries which pass the filter to the *log* buffer, dropping the oldest once it has more than
+    ///[LOG_BUFFER_LINES].  Returns whether the buffer changed.
+    pub fn update(&mut self, buffers: &mut [FileBuffer]) -> bool{
+        let entries = logging::entries_since(self.next_sequence);
+        if let Some(last) = entries.last(){
+            self.next_sequence = last.sequence + 1;
+        }
+
+        let new_lines: String = entries.iter()
+            .filter(|entry| self.filter.matches(entry))
+            .map(|entry| entry.format() + "\n")
+            .collect();
+
+        if new_lines.is_empty(){
+            return false;
+        }
+
+        match buffers.get_mut(self.buffer_index){
+            Some(buffer) => {
+                buffer.append_text(&new_lines);
+                keep_last_lines(buffer, LOG_BUFFER_LINES);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+///Drops the oldest lines of `buffer` so at most `max_lines` are left.  Every entry ends in a newline, so the empty line
+///after the last one isn't counted.
+fn keep_last_lines(buffer: &mut FileBuffer, max_lines: usize){
+    let lines = buffer.line_count() - 1;
+    if lines > max_lines{
+        buffer.remove_first_lines(lines - max_lines);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::time::SystemTime;
+
+    fn entry(level: Level, target: &str) -> LogEntry{
+        LogEntry{
+            sequence: 0,
+            time: SystemTime::now(),
+            level,
+            target: target.to_string(),
+            message: String::from("message")
+        }
+    }
+
+    #[test]
+    fn filter_hides_less_severe_entries(){
+        let filter = LogFilter{ level: Level::Warn, module: None };
+
+        assert!(filter.matches(&entry(Level::Error, "digit")));
+        assert!(!filter.matches(&entry(Level::Info, "digit")));
+    }
+
+    #[test]
+    fn buffer_keeps_the_newest_lines(){
+        let mut buffer = FileBuffer::scratch(LOG_BUFFER_NAME, "one\ntwo\n");
+        buffer.append_text("three\n");
+
+        keep_last_lines(&mut buffer, 2);
+
+        assert_eq!(buffer.get_text(), "two\nthree\n");
+    }
+
+    #[test]
+    fn filter_by_module_prefix(){
+        let filter = LogFilter{ level: Level::Trace, module: Some(String::from("digit::frontend")) };
+
+        assert!(filter.matches(&entry(Level::Info, "digit::frontend::main")));
+        assert!(!filter.matches(&entry(Level::Info, "digit::backend::main")));
+    }
+}
@@ -0,0 +1,127 @@
+use std::thread;
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::file_buffer::FileBuffer;
+use crate::backend::jobs::scheduler::JobScheduler;
+use crate::backend::jobs::search::ProjectSearchJob;
+use crate::backend::log_view::{LogFilter, LogView, LOG_BUFFER_NAME};
+use crate::backend::recovery;
+use crate::intermediary::paths;
+use crate::intermediary::message_queue::{MessageQueue, FrontendMessage, BackendMessage, BufferLine};
+
+/// This is the "main function" for the backend thread.  This is called once from main and everything else editing related happens here.
+/// the message queues are used to communicate between threads, and open_buffers is shared so the buffers can still be saved if this thread dies.
+pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>, open_buffers: Arc<Mutex<Vec<FileBuffer>>>){
+    log::info!("Backend Thread Started");
+
+    //slow work like searching runs here so it doesn't hold up this loop
+    let worker_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(2).min(4);
+    let mut job_scheduler = JobScheduler::new(worker_count, backend_message_queue.clone());
+
+    //keeps the *log* buffer up to date once it has been opened
+    let mut log_view: Option<LogView> = None;
+
+    backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::TestMessage);
+
+    'running: loop{
+
+        //process messages from frontend
+        let mut incoming_queue = frontend_message_queue.lock().unwrap_or_else(|e| e.into_inner());
+   
+        while !incoming_queue.is_empty() {
+            let message = incoming_queue.get_message();
+
+            match message{
+                FrontendMessage::UserQuit | FrontendMessage::Shutdown => {
+                    break 'running;
+                },
+                FrontendMessage::FatalError(message) => {
+                    log::error!("Frontend stopped: {}", message);
+                    let buffers = open_buffers.lock().unwrap_or_else(|e| e.into_inner());
+                    match recovery::save_dirty_buffers(&buffers, &paths::recovery_dir()) {
+                        Ok(saved) if !saved.is_empty() => log::info!("Saved {} unsaved buffers for recovery", saved.len()),
+                        Ok(..) => {},
+                        Err(error) => log::error!("Failed to save buffers for recovery: {}", error)
+                    }
+                    break 'running;
+                },
+                FrontendMessage::DebugMessage(message) => {
+                    log::debug!(target: "digit::frontend", "{}", message)
+                },
+                FrontendMessage::ProjectSearch(query) => {
+                    let root = std::env::current_dir().unwrap_or_default();
+                    job_scheduler.submit(Box::new(ProjectSearchJob::new(&root, &query)));
+                },
+                FrontendMessage::CancelJob(id) => {
+                    job_scheduler.cancel(id);
+                },
+                FrontendMessage::OpenLogBuffer{ level, module } => {
+                    log_view = Some(open_log_buffer(&open_buffers, LogFilter{ level, module }));
+                    if let Some(log_view) = &log_view {
+                        backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::ShowBuffer(log_view.get_buffer_index()));
+                    }
+                },
+                FrontendMessage::RequestLines{ buffer, first_line, count } => {
+                    if let Some(lines) = get_lines(&open_buffers, buffer, first_line, count) {
+                        backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(lines);
+                    }
+                }
+                _ => {}
+            }
+
+        }
+
+        drop(incoming_queue);    
+
+        if let Some(log_view) = &mut log_view {
+            let changed = log_view.update(&mut open_buffers.lock().unwrap_or_else(|e| e.into_inner()));
+            if changed {
+                backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::BufferChanged(log_view.get_buffer_index()));
+            }
+        }
+        
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    job_scheduler.shutdown();
+
+    log::info!("Backend Thread Stopped");
+}
+
+///Opens the *log* buffer, or clears it if it's already open, so it can be refilled with the new filter.
+fn open_log_buffer(open_buffers: &Arc<Mutex<Vec<FileBuffer>>>, filter: LogFilter) -> LogView{
+    let mut buffers = open_buffers.lock().unwrap_or_else(|e| e.into_inner());
+
+    let existing = buffers.iter().position(|buffer| buffer.get_path().is_none() && buffer.get_display_name() == LOG_BUFFER_NAME);
+    let buffer_index = match existing {
+        Some(index) => {
+            buffers[index] = FileBuffer::scratch(LOG_BUFFER_NAME, "");
+            index
+        },
+        None => {
+            buffers.push(FileBuffer::scratch(LOG_BUFFER_NAME, ""));
+            buffers.len() - 1
+        }
+    };
+
+    LogView::new(filter, buffer_index)
+}
+
+///Gets the lines the frontend asked for, or None if there is no buffer at `buffer_index`.
+///Only the requested lines are copied, so this stays cheap however big the buffer is.
+fn get_lines(open_buffers: &Arc<Mutex<Vec<FileBuffer>>>, buffer_index: usize, first_line: usize, count: usize) -> Option<BackendMessage>{
+    let buffers = open_buffers.lock().unwrap_or_else(|e| e.into_inner());
+    let buffer = buffers.get(buffer_index)?;
+
+    let total_lines = buffer.line_count();
+    let first_line = first_line.min(total_lines);
+    let lines = (first_line..(first_line + count).min(total_lines))
+        .filter_map(|line| Some(BufferLine{
+            revision: buffer.get_line_revision(line)?,
+            text: buffer.get_line(line)?
+        }))
+        .collect();
+
+    Some(BackendMessage::Lines{ buffer: buffer_index, first_line, total_lines, lines })
+}
@@ -1 +1,6 @@
-pub mod file_buffer;
\ No newline at end of file
+pub mod file_buffer;
+pub mod main;
+pub mod jobs;
+pub mod log_view;
+pub mod swap;
+pub mod recovery;
\ No newline at end of file
@@ -0,0 +1,117 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::file_buffer::FileBuffer;
+
+///Name of the file in the recovery directory that lists what was recovered, one `original<TAB>recovered` per line.
+pub const MANIFEST_NAME: &str = "manifest.txt";
+
+///Writes the contents of every dirty buffer into `directory` along with a manifest of what was saved,
+///so the next launch can tell the user where to find them.  Saving the same buffers again overwrites the same files.
+///Returns the paths of the recovered files.
+pub fn save_dirty_buffers(buffers: &[FileBuffer], directory: &Path) -> io::Result<Vec<PathBuf>>{
+    let mut recovered = Vec::new();
+    let mut manifest = String::new();
+
+    for (index, buffer) in buffers.iter().enumerate(){
+        if !buffer.is_dirty(){
+            continue;
+        }
+
+        fs::create_dir_all(directory)?;
+
+        let path = directory.join(format!("{}-{}", index, buffer.get_display_name()));
+        fs::write(&path, buffer.get_text())?;
+
+        let original = buffer.get_path()
+            .map(|original| original.display().to_string())
+            .unwrap_or_else(|| buffer.get_display_name());
+        manifest.push_str(&format!("{}\t{}\n", original, path.display()));
+
+        recovered.push(path);
+    }
+
+    if !recovered.is_empty(){
+        fs::write(directory.join(MANIFEST_NAME), manifest)?;
+    }
+
+    Ok(recovered)
+}
+
+///Checks `directory` for buffers recovered after a crash and describes them for showing to the user.
+///The manifest is removed afterwards so the notice is only shown once; the recovered files themselves are left alone.
+pub fn take_recovery_notice(directory: &Path) -> io::Result<Option<String>>{
+    let manifest_path = directory.join(MANIFEST_NAME);
+
+    let manifest = match fs::read_to_string(&manifest_path){
+        Ok(manifest) => manifest,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error)
+    };
+
+    let lines: Vec<String> = manifest.lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(original, recovered)| format!("  {} -> {}", original, recovered))
+        .collect();
+
+    fs::remove_file(&manifest_path)?;
+
+    if lines.is_empty(){
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "Recovered {} unsaved buffer(s) from the last session:\n{}",
+        lines.len(),
+        lines.join("\n")
+    )))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn test_directory(name: &str) -> PathBuf{
+        std::env::temp_dir().join(format!("digit-recovery-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn saved_buffers_are_listed_in_notice(){
+        let directory = test_directory("notice");
+
+        let mut dirty = FileBuffer::from_str("unsaved work");
+        dirty.set_dirty(true);
+
+        let recovered = save_dirty_buffers(&[FileBuffer::new(), dirty], &directory).unwrap();
+        let notice = take_recovery_notice(&directory).unwrap().unwrap();
+
+        assert_eq!(fs::read_to_string(&recovered[0]).unwrap(), "unsaved work");
+        assert!(notice.contains("Recovered 1 unsaved buffer(s)"));
+        assert!(notice.contains(&recovered[0].display().to_string()));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn notice_is_only_shown_once(){
+        let directory = test_directory("once");
+
+        let mut dirty = FileBuffer::from_str("unsaved work");
+        dirty.set_dirty(true);
+
+        save_dirty_buffers(&[dirty], &directory).unwrap();
+
+        assert!(take_recovery_notice(&directory).unwrap().is_some());
+        assert!(take_recovery_notice(&directory).unwrap().is_none());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn no_notice_without_manifest(){
+        let directory = test_directory("missing");
+
+        assert!(take_recovery_notice(&directory).unwrap().is_none());
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::file_buffer::FileBuffer;
+
+///Gets the name of the swap file for the buffer at `index` in the open buffer list.
+///The index keeps two buffers for files with the same name from sharing a swap file.
+pub fn swap_file_name(buffer: &FileBuffer, index: usize) -> String{
+    format!("{}-{}.swp", index, buffer.get_display_name())
+}
+
+///Writes the contents of every dirty buffer to a swap file in `directory`, creating the directory if needed.
+///Returns the paths of the swap files written.
+pub fn flush_swap_files(buffers: &[FileBuffer], directory: &Path) -> io::Result<Vec<PathBuf>>{
+    let mut written = Vec::new();
+
+    for (index, buffer) in buffers.iter().enumerate(){
+        if !buffer.is_dirty(){
+            continue;
+        }
+
+        fs::create_dir_all(directory)?;
+
+        let path = directory.join(swap_file_name(buffer, index));
+        fs::write(&path, buffer.get_text())?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn untitled_buffer_swap_file_name(){
+        let buffer = FileBuffer::new();
+
+        assert_eq!(swap_file_name(&buffer, 3), "3-untitled.swp");
+    }
+
+    #[test]
+    fn only_dirty_buffers_are_flushed(){
+        let directory = std::env::temp_dir().join(format!("digit-swap-test-{}", std::process::id()));
+
+        let clean = FileBuffer::from_str("clean");
+        let mut dirty = FileBuffer::from_str("dirty");
+        dirty.set_dirty(true);
+
+        let written = flush_swap_files(&[clean, dirty], &directory).unwrap();
+
+        assert_eq!(written, vec![directory.join("1-untitled.swp")]);
+        assert_eq!(fs::read_to_string(&written[0]).unwrap(), "dirty");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glfw::{Key, Modifiers};
+
+use crate::frontend::rendering::image::RgbaImage;
+
+///What the capture commands on Print Screen capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture{
+    ///The whole window, saved as a PNG.  Print Screen.
+    Frame,
+    ///Just the editor pane, saved as a PNG.  Shift+Print Screen.
+    Pane,
+    ///The visible code as HTML, copied to the clipboard.  Ctrl+Print Screen.
+    Html,
+    ///The visible code as SVG, copied to the clipboard.  Ctrl+Shift+Print Screen.
+    Svg
+}
+
+impl Capture{
+    ///Get the capture command bound to `key` with `modifiers`, if there is one.
+    pub fn for_key(key: Key, modifiers: Modifiers) -> Option<Capture>{
+        if key != Key::PrintScreen {
+            return None;
+        }
+
+        let control = modifiers.contains(Modifiers::Control);
+        let shift = modifiers.contains(Modifiers::Shift);
+        Some(match (control, shift) {
+            (false, false) => Capture::Frame,
+            (false, true) => Capture::Pane,
+            (true, false) => Capture::Html,
+            (true, true) => Capture::Svg
+        })
+    }
+
+    ///Returns true if the capture is read back from the gpu, and so has to wait for a frame to be drawn.
+    pub fn needs_frame(&self) -> bool{
+        matches!(self, Capture::Frame | Capture::Pane)
+    }
+}
+
+///Converts `rect` (x, y, width, height in logical pixels) to the surface pixels it covers, rounding outwards.
+pub fn pixel_rect(rect: [f32; 4], content_scale: f32) -> (u32, u32, u32, u32){
+    let left = (rect[0] * content_scale).floor().max(0.0);
+    let top = (rect[1] * content_scale).floor().max(0.0);
+    let right = ((rect[0] + rect[2]) * content_scale).ceil().max(left);
+    let bottom = ((rect[1] + rect[3]) * content_scale).ceil().max(top);
+
+    (left as u32, top as u32, (right - left) as u32, (bottom - top) as u32)
+}
+
+///Writes `image` to a new PNG in `directory`, named after the time it was taken.  Returns the path written to.
+pub fn save_screenshot(image: &RgbaImage, directory: &Path) -> io::Result<PathBuf>{
+    fs::create_dir_all(directory)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let path = directory.join(format!("screenshot-{}.png", timestamp));
+
+    image.save_png(&path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn print_screen_modifiers_pick_the_capture(){
+        assert_eq!(Capture::for_key(Key::PrintScreen, Modifiers::empty()), Some(Capture::Frame));
+        assert_eq!(Capture::for_key(Key::PrintScreen, Modifiers::Control | Modifiers::Shift), Some(Capture::Svg));
+        assert_eq!(Capture::for_key(Key::P, Modifiers::Control), None);
+    }
+
+    #[test]
+    fn pixel_rect_rounds_outwards(){
+        assert_eq!(pixel_rect([0.0, 0.0, 100.0, 50.0], 1.0), (0, 0, 100, 50));
+        assert_eq!(pixel_rect([10.25, 5.0, 10.5, 10.0], 2.0), (20, 10, 22, 20));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::Write;
+
+///A run of text drawn in one colour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSpan{
+    pub text: String,
+    pub color: [f32; 4]
+}
+
+///How exported code looks, matching what the editor draws.
+#[derive(Debug, Clone)]
+pub struct ExportStyle{
+    pub background: [f32; 3],
+    ///A CSS font family list, see [css_font_family].
+    pub font_family: String,
+    ///Pixels per em.
+    pub font_size: f32,
+    pub line_height: f32
+}
+
+///Renders `lines` as a styled HTML `<pre>` block, for pasting into docs and bug reports.
+pub fn to_html(lines: &[Vec<ExportSpan>], style: &ExportStyle) -> String{
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<pre style=\"background:{};font-family:{};font-size:{}px;line-height:{}px;padding:8px\">",
+        css_color(style.background), escape_markup(&style.font_family), style.font_size, style.line_height
+    );
+
+    for (number, line) in lines.iter().enumerate() {
+        if number > 0 {
+            html.push('\n');
+        }
+        for span in line {
+            let _ = write!(html, "<span style=\"color:{}\">{}</span>", css_rgba(span.color), escape_markup(&span.text));
+        }
+    }
+
+    html.push_str("</pre>\n");
+    html
+}
+
+///Renders `lines` as a standalone SVG, `width` logical pixels wide with one row of text per line.
+pub fn to_svg(lines: &[Vec<ExportSpan>], style: &ExportStyle, width: f32) -> String{
+    let padding = 8.0;
+    let width = width + padding * 2.0;
+    let height = lines.len() as f32 * style.line_height + padding * 2.0;
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height);
+    let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", css_color(style.background));
+    let _ = writeln!(
+        svg,
+        "<g font-family=\"{}\" font-size=\"{}\" xml:space=\"preserve\">",
+        escape_markup(&style.font_family), style.font_size
+    );
+
+    for (number, line) in lines.iter().enumerate() {
+        if line.iter().all(|span| span.text.is_empty()) {
+            continue;
+        }
+
+        //text is placed by its baseline, put it where a line box of line_height would have it
+        let baseline = padding + number as f32 * style.line_height + (style.line_height + style.font_size * 0.6) / 2.0;
+        let _ = write!(svg, "<text x=\"{}\" y=\"{}\">", padding, baseline);
+        for span in line {
+            let _ = write!(svg, "<tspan fill=\"{}\">{}</tspan>", css_rgba(span.color), escape_markup(&span.text));
+        }
+        svg.push_str("</text>\n");
+    }
+
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+///Builds the CSS font family list for the editor's font, falling back to any monospace font where it isn't installed.
+pub fn css_font_family(family: Option<&str>) -> String{
+    match family {
+        Some(family) if !family.is_empty() => format!("'{}', monospace", family.replace('\\', "\\\\").replace('\'', "\\'")),
+        _ => String::from("monospace")
+    }
+}
+
+///Formats a colour with channels from 0 to 1 as #rrggbb.
+fn css_color(color: [f32; 3]) -> String{
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(color[0]), channel(color[1]), channel(color[2]))
+}
+
+///Formats a colour with alpha as #rrggbb, or #rrggbbaa if it's translucent.
+fn css_rgba(color: [f32; 4]) -> String{
+    let rgb = css_color([color[0], color[1], color[2]]);
+    if color[3] >= 1.0 {
+        rgb
+    }else{
+        format!("{}{:02x}", rgb, (color[3].clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+///Escapes text for HTML and SVG, in element content and in quoted attributes.
+fn escape_markup(text: &str) -> String{
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn style() -> ExportStyle{
+        ExportStyle{
+            background: [0.0, 0.0, 0.0],
+            font_family: String::from("monospace"),
+            font_size: 14.0,
+            line_height: 17.0
+        }
+    }
+
+    fn line(text: &str) -> Vec<ExportSpan>{
+        vec![ExportSpan{ text: text.to_string(), color: [1.0, 1.0, 1.0, 1.0] }]
+    }
+
+    #[test]
+    fn html_escapes_code(){
+        let html = to_html(&[line("if a < b && c > d {"), line("}")], &style());
+        assert!(html.starts_with("<pre style=\"background:#000000;"));
+        assert!(html.contains("<span style=\"color:#ffffff\">if a &lt; b &amp;&amp; c &gt; d {</span>\n<span"));
+    }
+
+    #[test]
+    fn svg_has_a_text_row_per_line(){
+        let svg = to_svg(&[line("let x = \"1\";"), line(""), line("x")], &style(), 100.0);
+        assert_eq!(svg.matches("<text ").count(), 2);
+        assert!(svg.contains("let x = &quot;1&quot;;"));
+        assert!(svg.contains("height=\"67\""));
+    }
+
+    #[test]
+    fn font_family_falls_back_to_monospace(){
+        assert_eq!(css_font_family(Some("Fira Code")), "'Fira Code', monospace");
+        assert_eq!(css_font_family(Some("Bob's Mono")), "'Bob\\'s Mono', monospace");
+        assert_eq!(css_font_family(None), "monospace");
+
+        let html = to_html(&[line("x")], &ExportStyle{ font_family: css_font_family(Some("Fira Code")), ..style() });
+        assert!(html.contains("font-family:&#39;Fira Code&#39;, monospace;"));
+    }
+
+    #[test]
+    fn colors_are_hex(){
+        assert_eq!(css_color([1.0, 0.5, 0.0]), "#ff8000");
+        assert_eq!(css_rgba([1.0, 0.5, 0.0, 1.0]), "#ff8000");
+        assert_eq!(css_rgba([1.0, 0.5, 0.0, 0.5]), "#ff800080");
+    }
+}
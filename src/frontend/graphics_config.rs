@@ -0,0 +1,341 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///Whether wgpu's software (fallback) adapter is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackAdapter{
+    ///Try a hardware adapter first, and the fallback adapter if that fails.
+    Auto,
+    ///Only use the fallback adapter.
+    Force,
+    ///Never use the fallback adapter, or any other software rasterizer.
+    Avoid
+}
+
+impl FallbackAdapter{
+    ///Get the values of `force_fallback_adapter` to try, in order.
+    pub fn attempts(&self) -> &'static [bool]{
+        match self{
+            FallbackAdapter::Auto => &[false, true],
+            FallbackAdapter::Force => &[true],
+            FallbackAdapter::Avoid => &[false]
+        }
+    }
+
+    ///Returns false for adapters this setting rules out.  Avoid rules out every software rasterizer, since asking
+    ///for a hardware adapter can still return one like llvmpipe when it's the only adapter wgpu finds.
+    pub fn allows(&self, device_type: wgpu::DeviceType) -> bool{
+        *self != FallbackAdapter::Avoid || device_type != wgpu::DeviceType::Cpu
+    }
+}
+
+///How glyphs are rasterized into the atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphMode{
+    ///A coverage bitmap for every size, sharp at the size it was rasterized at.
+    Bitmap,
+    ///One signed distance field per glyph, scaled to any size.  Small text still uses bitmaps.
+    Sdf
+}
+
+///The order of the coloured subpixels across each pixel of an LCD, for subpixel text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubpixelOrder{
+    Rgb,
+    Bgr
+}
+
+///How the frontend picks its graphics backend, adapter and present mode, and how it draws text.
+///Set from the config file and then from command line flags, so flags win.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphicsConfig{
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    pub present_mode: wgpu::PresentMode,
+    pub fallback_adapter: FallbackAdapter,
+    pub glyph_mode: GlyphMode,
+    ///Subpixel text for this LCD's subpixel order, or None for grayscale text.
+    pub subpixel: Option<SubpixelOrder>,
+    ///Gamma applied to subpixel text's coverage, above 1 makes it heavier.
+    pub text_gamma: f32,
+    ///Contrast added to subpixel text's coverage, from 0 to 1.
+    pub text_contrast: f32,
+    ///Font file to use as the primary font, including BDF and PCF pixel fonts, or None for the default font.
+    pub font: Option<PathBuf>
+}
+
+impl Default for GraphicsConfig{
+    fn default() -> Self{
+        GraphicsConfig{
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+            fallback_adapter: FallbackAdapter::Auto,
+            glyph_mode: GlyphMode::Bitmap,
+            subpixel: None,
+            text_gamma: 1.0,
+            text_contrast: 0.0,
+            font: None
+        }
+    }
+}
+
+impl GraphicsConfig{
+    ///Loads the config from `config_file` if it exists, then applies the command line `args` (without the program name).
+    ///Bad options are logged and skipped, so a typo never stops the editor from starting.
+    pub fn load(config_file: &Path, args: &[String]) -> GraphicsConfig{
+        let mut config = GraphicsConfig::default();
+
+        match fs::read_to_string(config_file) {
+            Ok(text) => {
+                for error in config.apply_config_file(&text) {
+                    log::warn!("{}: {}", config_file.display(), error);
+                }
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {},
+            Err(error) => log::warn!("Failed to read {}: {}", config_file.display(), error)
+        }
+
+        for error in config.apply_args(args) {
+            log::warn!("{}", error);
+        }
+
+        config
+    }
+
+    ///Applies `key = value` lines from a config file.  Blank lines, lines starting with # and keys which
+    ///aren't graphics options are skipped.  Returns an error for each line that couldn't be used.
+    pub fn apply_config_file(&mut self, text: &str) -> Vec<String>{
+        let mut errors = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((key, value)) if is_graphics_option(key.trim()) => {
+                    if let Err(error) = self.set(key.trim(), value.trim()) {
+                        errors.push(format!("line {}: {}", number + 1, error));
+                    }
+                },
+                Some(_) => {},
+                None => errors.push(format!("line {}: expected key = value", number + 1))
+            }
+        }
+
+        errors
+    }
+
+    ///Applies command line flags, either `--backend vulkan` or `--backend=vulkan`.
+    ///Returns an error for each flag that couldn't be used.
+    pub fn apply_args(&mut self, args: &[String]) -> Vec<String>{
+        let mut errors = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => {
+                    errors.push(format!("Unexpected argument '{}'", arg));
+                    continue;
+                }
+            };
+
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key, value.to_string()),
+                None if is_graphics_option(flag) => match args.next() {
+                    Some(value) => (flag, value.clone()),
+                    None => {
+                        errors.push(format!("--{} needs a value", flag));
+                        continue;
+                    }
+                },
+                None => (flag, String::new())
+            };
+
+            if let Err(error) = self.set(key, &value) {
+                errors.push(error);
+            }
+        }
+
+        errors
+    }
+
+    ///Sets one option by name, with the same names and values as the config file.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String>{
+        //paths are the only values where case matters
+        if key == "font" {
+            self.font = Some(PathBuf::from(value)).filter(|path| !path.as_os_str().is_empty());
+            return Ok(());
+        }
+        let value = value.to_lowercase();
+        match key{
+            "backend" => self.backends = parse_backends(&value)?,
+            "adapter" => self.power_preference = match value.as_str() {
+                "default" => wgpu::PowerPreference::default(),
+                "low-power" | "integrated" => wgpu::PowerPreference::LowPower,
+                "high-performance" | "discrete" => wgpu::PowerPreference::HighPerformance,
+                _ => return Err(format!("Unknown adapter preference '{}', expected default, low-power or high-performance", value))
+            },
+            "vsync" => self.present_mode = match value.as_str() {
+                "on" | "fifo" => wgpu::PresentMode::Fifo,
+                "off" => wgpu::PresentMode::AutoNoVsync,
+                "auto" => wgpu::PresentMode::AutoVsync,
+                "relaxed" => wgpu::PresentMode::FifoRelaxed,
+                "mailbox" => wgpu::PresentMode::Mailbox,
+                "immediate" => wgpu::PresentMode::Immediate,
+                _ => return Err(format!("Unknown vsync mode '{}', expected on, off, auto, relaxed, mailbox or immediate", value))
+            },
+            "fallback-adapter" => self.fallback_adapter = match value.as_str() {
+                "auto" => FallbackAdapter::Auto,
+                "force" | "always" => FallbackAdapter::Force,
+                "avoid" | "never" => FallbackAdapter::Avoid,
+                _ => return Err(format!("Unknown fallback adapter option '{}', expected auto, force or avoid", value))
+            },
+            "glyphs" => self.glyph_mode = match value.as_str() {
+                "bitmap" => GlyphMode::Bitmap,
+                "sdf" => GlyphMode::Sdf,
+                _ => return Err(format!("Unknown glyph mode '{}', expected bitmap or sdf", value))
+            },
+            "subpixel" => self.subpixel = match value.as_str() {
+                "off" | "none" => None,
+                "rgb" => Some(SubpixelOrder::Rgb),
+                "bgr" => Some(SubpixelOrder::Bgr),
+                _ => return Err(format!("Unknown subpixel order '{}', expected off, rgb or bgr", value))
+            },
+            "text-gamma" => self.text_gamma = parse_in_range(key, &value, 0.5, 4.0)?,
+            "text-contrast" => self.text_contrast = parse_in_range(key, &value, 0.0, 1.0)?,
+            _ => return Err(format!("Unknown option '{}'", key))
+        }
+        Ok(())
+    }
+
+    ///Picks the present mode to configure the surface with, falling back to Fifo (which every surface supports)
+    ///if the configured mode isn't in `supported`.  The Auto modes are always accepted, wgpu picks a supported mode for them.
+    pub fn choose_present_mode(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode{
+        match self.present_mode{
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync => self.present_mode,
+            mode if supported.contains(&mode) => mode,
+            mode => {
+                log::warn!("Present mode {:?} isn't supported by this surface, using Fifo", mode);
+                wgpu::PresentMode::Fifo
+            }
+        }
+    }
+}
+
+fn is_graphics_option(key: &str) -> bool{
+    matches!(key, "backend" | "adapter" | "vsync" | "fallback-adapter" | "glyphs" | "subpixel" | "text-gamma" | "text-contrast" | "font")
+}
+
+///Parses a number for `key` which has to be from `min` to `max`.
+fn parse_in_range(key: &str, value: &str, min: f32, max: f32) -> Result<f32, String>{
+    match value.parse::<f32>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(number),
+        _ => Err(format!("Bad {} '{}', expected a number from {} to {}", key, value, min, max))
+    }
+}
+
+///Parses a comma separated list of backends, e.g. "vulkan" or "vulkan,gl".
+fn parse_backends(value: &str) -> Result<wgpu::Backends, String>{
+    let mut backends = wgpu::Backends::empty();
+    for name in value.split(',').map(str::trim) {
+        backends |= match name {
+            "all" | "auto" => wgpu::Backends::all(),
+            "primary" => wgpu::Backends::PRIMARY,
+            "vulkan" => wgpu::Backends::VULKAN,
+            "gl" | "opengl" => wgpu::Backends::GL,
+            "metal" => wgpu::Backends::METAL,
+            "dx12" => wgpu::Backends::DX12,
+            "dx11" => wgpu::Backends::DX11,
+            _ => return Err(format!("Unknown backend '{}', expected vulkan, gl, metal, dx12, dx11, primary or all", name))
+        };
+    }
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String>{
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_accept_both_forms(){
+        let mut config = GraphicsConfig::default();
+        let errors = config.apply_args(&args(&["--backend", "vulkan", "--vsync=off", "--adapter=low-power"]));
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.backends, wgpu::Backends::VULKAN);
+        assert_eq!(config.present_mode, wgpu::PresentMode::AutoNoVsync);
+        assert_eq!(config.power_preference, wgpu::PowerPreference::LowPower);
+    }
+
+    #[test]
+    fn bad_flags_are_reported_and_skipped(){
+        let mut config = GraphicsConfig::default();
+        let errors = config.apply_args(&args(&["--backend=glide", "--fallback-adapter", "force", "--vsync"]));
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(config.backends, wgpu::Backends::all());
+        assert_eq!(config.fallback_adapter, FallbackAdapter::Force);
+    }
+
+    #[test]
+    fn config_file_skips_comments_and_other_options(){
+        let mut config = GraphicsConfig::default();
+        let errors = config.apply_config_file("# graphics\nbackend = gl, vulkan\n\ntab-width = 4\nvsync = mailbox\nglyphs = SDF\n");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.backends, wgpu::Backends::GL | wgpu::Backends::VULKAN);
+        assert_eq!(config.present_mode, wgpu::PresentMode::Mailbox);
+        assert_eq!(config.glyph_mode, GlyphMode::Sdf);
+    }
+
+    #[test]
+    fn font_path_keeps_its_case(){
+        let mut config = GraphicsConfig::default();
+        let errors = config.apply_config_file("font = /home/me/Fonts/Terminus.pcf.gz\n");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.font, Some(PathBuf::from("/home/me/Fonts/Terminus.pcf.gz")));
+
+        config.apply_args(&args(&["--font="]));
+        assert_eq!(config.font, None);
+    }
+
+    #[test]
+    fn subpixel_options_are_checked(){
+        let mut config = GraphicsConfig::default();
+        let errors = config.apply_args(&args(&["--subpixel", "BGR", "--text-gamma=1.8", "--text-contrast=2"]));
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(config.subpixel, Some(SubpixelOrder::Bgr));
+        assert_eq!(config.text_gamma, 1.8);
+        assert_eq!(config.text_contrast, 0.0);
+    }
+
+    #[test]
+    fn unsupported_present_mode_falls_back_to_fifo(){
+        let config = GraphicsConfig{ present_mode: wgpu::PresentMode::Mailbox, ..GraphicsConfig::default() };
+
+        assert_eq!(config.choose_present_mode(&[wgpu::PresentMode::Fifo]), wgpu::PresentMode::Fifo);
+        assert_eq!(config.choose_present_mode(&[wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox]), wgpu::PresentMode::Mailbox);
+    }
+
+    #[test]
+    fn auto_fallback_tries_hardware_first(){
+        assert_eq!(FallbackAdapter::Auto.attempts(), &[false, true]);
+    }
+
+    #[test]
+    fn avoiding_the_fallback_rules_out_software_adapters(){
+        assert!(!FallbackAdapter::Avoid.allows(wgpu::DeviceType::Cpu));
+        assert!(FallbackAdapter::Avoid.allows(wgpu::DeviceType::DiscreteGpu));
+        assert!(FallbackAdapter::Auto.allows(wgpu::DeviceType::Cpu));
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use crate::intermediary::message_queue::{BackendMessage, JobOutcome};
+
+///What the frontend knows about a background job, for drawing spinners and progress bars.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStatus{
+    pub name: String,
+    ///From 0.0 to 1.0, or None if the job can't tell (show a spinner).
+    pub progress: Option<f32>,
+    pub status: String
+}
+
+impl JobStatus{
+    ///Describes the job in one line for the job list, with its progress as a percentage if it has one.
+    pub fn describe(&self) -> String{
+        let mut description = self.name.clone();
+        if let Some(progress) = self.progress{
+            description.push_str(&format!(" ({:.0}%)", progress * 100.0));
+        }
+        if !self.status.is_empty(){
+            description.push_str(": ");
+            description.push_str(&self.status);
+        }
+        description
+    }
+}
+
+///Tracks the background jobs that are currently running from the Job* messages sent by the backend.
+pub struct ActiveJobs{
+    jobs: BTreeMap<u64, JobStatus>
+}
+
+impl ActiveJobs{
+    pub fn new() -> ActiveJobs{
+        ActiveJobs{
+            jobs: BTreeMap::new()
+        }
+    }
+
+    ///Updates the tracked jobs from a backend message.  Returns the outcome if the message finished a job.
+    pub fn update(&mut self, message: &BackendMessage) -> Option<JobOutcome>{
+        match message{
+            BackendMessage::JobStarted{ id, name } => {
+                self.jobs.insert(*id, JobStatus{
+                    name: name.clone(),
+                    progress: None,
+                    status: String::new()
+                });
+            },
+            BackendMessage::JobProgress{ id, progress, status } => {
+                if let Some(job) = self.jobs.get_mut(id){
+                    job.progress = *progress;
+                    job.status = status.clone();
+                }
+            },
+            BackendMessage::JobFinished{ id, outcome } => {
+                self.jobs.remove(id);
+                return Some(outcome.clone());
+            },
+            _ => {}
+        }
+        None
+    }
+
+    ///Get the running jobs, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &JobStatus)>{
+        self.jobs.iter()
+    }
+
+    ///Gets whether any jobs are running.
+    pub fn is_empty(&self) -> bool{
+        self.jobs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn progress_updates_running_job(){
+        let mut jobs = ActiveJobs::new();
+
+        jobs.update(&BackendMessage::JobStarted{ id: 1, name: String::from("search") });
+        jobs.update(&BackendMessage::JobProgress{ id: 1, progress: Some(0.25), status: String::from("1 of 4") });
+
+        assert_eq!(jobs.iter().next().unwrap().1.progress, Some(0.25));
+    }
+
+    #[test]
+    fn finished_job_is_removed(){
+        let mut jobs = ActiveJobs::new();
+
+        jobs.update(&BackendMessage::JobStarted{ id: 1, name: String::from("search") });
+        let outcome = jobs.update(&BackendMessage::JobFinished{ id: 1, outcome: JobOutcome::Cancelled });
+
+        assert_eq!(outcome, Some(JobOutcome::Cancelled));
+        assert!(jobs.is_empty());
+    }
+
+    #[test]
+    fn description_includes_progress_and_status(){
+        let mut jobs = ActiveJobs::new();
+
+        jobs.update(&BackendMessage::JobStarted{ id: 1, name: String::from("search") });
+        assert_eq!(jobs.iter().next().unwrap().1.describe(), "search");
+
+        jobs.update(&BackendMessage::JobProgress{ id: 1, progress: Some(0.25), status: String::from("1 of 4") });
+        assert_eq!(jobs.iter().next().unwrap().1.describe(), "search (25%): 1 of 4");
+    }
+}
@@ -1,25 +1,60 @@
 
 
-use crate::frontend::rendering::mesh::Vertex;
+use crate::frontend::rendering::mesh::{Vertex, Border, Shadow};
 use crate::intermediary::message_queue::{MessageQueue, FrontendMessage, BackendMessage};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex};
 
 use glfw::{Action, Context, Key};
 
 use crate::frontend::wgpu_state;
+use crate::frontend::graphics_config::GraphicsConfig;
+use crate::frontend::job_status::ActiveJobs;
+use crate::frontend::perf_hud::{PerfHud, FrameSample};
 use crate::frontend::rendering::render_state;
+use crate::frontend::rendering::layer::{Layer, ClipRect};
+use crate::frontend::rendering::pane_cache::{Pane, PaneId};
+use crate::frontend::rendering::shaping::{FontChain, Shaper};
+use crate::frontend::rendering::text;
+use crate::frontend::rendering::line_cache::LineMeshCache;
+use crate::frontend::viewport::Viewport;
+use crate::frontend::redraw::{self, RedrawScheduler, WakeOnMessage};
+use crate::frontend::capture::{self, Capture};
+use crate::frontend::code_export::{self, ExportSpan, ExportStyle};
+use crate::frontend::viewport;
+use crate::frontend::rendering::render::{self, CLEAR_COLOR};
+
+///Size of text in pixels per em.
+const TEXT_SIZE: f32 = 14.0;
+
+///Colour of the buffer's text.
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+
+///How often the performance overlay is redrawn when nothing else changes.
+const HUD_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+///Id of the editor pane in the pane cache.
+const EDITOR_PANE: PaneId = 0;
+
+///How many frames in a row the surface can be lost after reconfiguring it before the device is treated as lost.
+const MAX_SURFACE_RECONFIGURES: u32 = 3;
+
+///How many project search matches are listed in the notification, the rest are only counted.
+const SEARCH_RESULTS_SHOWN: usize = 5;
 
 /// This is the "main function" for the rendering thread.  This is called once from main and everything else rendering related happens here.
-/// the message queues are used to communicate between threads
-pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>){
-    println!("Rendering Thread Started");
+/// the message queues are used to communicate between threads, and graphics_config picks the gpu backend, adapter, present mode and font
+pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>>, graphics_config: GraphicsConfig){
+    log::info!("Rendering Thread Started");
 
     //initialize glfw window
 
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
 
+    //the size is in logical pixels: scaled up by the monitor's content scale on Windows and X11, and given a HiDPI
+    //framebuffer on macOS
+    glfw.window_hint(glfw::WindowHint::ScaleToMonitor(true));
+    glfw.window_hint(glfw::WindowHint::CocoaRetinaFramebuffer(true));
     let (mut window, events) = glfw.create_window(640, 480, "Digit", glfw::WindowMode::Windowed)
         .expect("Failed to create window!");
 
@@ -45,40 +80,154 @@ pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, b
     window.make_current();
 
 
-    let mut wgpu_state = pollster::block_on(wgpu_state::WGPUState::new(&window));
+    let mut wgpu_state = match pollster::block_on(wgpu_state::WGPUState::new(&window, &graphics_config)) {
+        Ok(wgpu_state) => wgpu_state,
+        Err(error) => {
+            log::error!("Couldn't set up the GPU: {}", error);
+            frontend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(FrontendMessage::FatalError(format!("Couldn't set up the GPU: {}", error)));
+            return;
+        }
+    };
+
+    crate::intermediary::panic_hook::set_adapter_info(format!("{:?}", wgpu_state.get_adapter_info()));
 
     let mut render_state = render_state::RenderState::new();
+    //the editor pane's content, only built when the pane has changed since it was last drawn into its texture
+    let mut pane_render_state = render_state::RenderState::new();
+
+    //fonts for all text, text is skipped if there isn't a primary font
+    let fonts = FontChain::load(graphics_config.font.as_deref());
+    let mut shaper = Shaper::new();
+    match &fonts {
+        Some(fonts) => log::info!("Using fonts {:?}", fonts.iter().map(|font| font.get_path()).collect::<Vec<_>>()),
+        None => log::warn!("No font found, text will not be drawn")
+    }
+
+    //the latest notification from the backend, shown along the bottom of the window
+    let mut notification_text: Option<String> = None;
+
+    //background jobs running in the backend, for spinners and progress bars
+    let mut active_jobs = ActiveJobs::new();
+
+    //frame timings, shown as an overlay when toggled with F12
+    let mut perf_hud = PerfHud::new();
 
-    //for debugging purposes
-    let mut rendering_total_time = Duration::from_secs(0);
-    let mut times_rendered: u32 = 0;
+    //the buffer being shown, only its visible lines are fetched and drawn
+    let line_height = fonts.as_ref().map_or(TEXT_SIZE * 1.2, |fonts| fonts.get_primary().line_metrics(TEXT_SIZE).line_height.ceil());
+    let mut viewport = Viewport::new(wgpu_state.get_logical_size().1, line_height);
+    let mut line_cache = LineMeshCache::new();
 
-    loop{
+    //why the last frame couldn't be uploaded, if it couldn't
+    let mut upload_error: Option<&str> = None;
 
-        //process messages from main thread
-        let mut incoming_queue = backend_message_queue.lock().unwrap();
+    //frames are only drawn when something changed, messages from the backend wake the wait for glfw events
+    let mut redraw = RedrawScheduler::new();
+    let _wake_on_message = WakeOnMessage::install(backend_message_queue.clone());
+
+    //true while the window is minimized or has no area, there's no surface to draw to
+    let mut paused = false;
+
+    //frames in a row which lost the surface, if reconfiguring doesn't help the device is gone
+    let mut surface_losses: u32 = 0;
+    //how many frames in a row recreating the lost device has failed, only the first failure is logged
+    let mut recreate_failures: u32 = 0;
+
+    //screenshot asked for, taken once the next frame has been drawn
+    let mut pending_capture: Option<Capture> = None;
+
+    'running: loop{
+
+        //sleep until there's input, a message, or an animation frame is due
+        match redraw.wait_timeout(Instant::now()) {
+            _ if paused => glfw.wait_events(),
+            Some(timeout) if timeout.is_zero() => glfw.poll_events(),
+            Some(timeout) => glfw.wait_events_timeout(timeout.as_secs_f64()),
+            None => glfw.wait_events()
+        }
+
+        let frame_start = Instant::now();
+
+        let mut should_quit: bool = false;
+
+        //process messages from main thread. a backend thread that panicked while holding the queue poisons it, but
+        //the messages in it (like Shutdown from the supervisor) are still whole, so keep going rather than panic too
+        let mut incoming_queue = backend_message_queue.lock().unwrap_or_else(|e| e.into_inner());
+        let queue_depth = incoming_queue.len();
    
         while !incoming_queue.is_empty() {
             let message = incoming_queue.get_message();
 
-            println!("Frontend recieved message!");
+            match message{
+                BackendMessage::Shutdown => {
+                    //the backend is gone, so there is no one to send UserQuit to
+                    break 'running;
+                },
+                BackendMessage::Notification(notification) => {
+                    log::info!("Notification: {}", notification);
+                    notification_text = Some(notification);
+                    redraw.mark_dirty();
+                },
+                BackendMessage::ShowBuffer(..) => {
+                    viewport.update(&message);
+                    line_cache.clear();
+                    redraw.mark_dirty();
+                },
+                BackendMessage::BufferChanged(..) | BackendMessage::Lines{..} => {
+                    //not logged, since that would change the *log* buffer again
+                    if viewport.update(&message) {
+                        redraw.mark_dirty();
+                    }
+                },
+                BackendMessage::JobStarted{..} | BackendMessage::JobProgress{..} | BackendMessage::JobFinished{..} => {
+                    if let Some(outcome) = active_jobs.update(&message) {
+                        log::debug!("Job finished: {:?}", outcome);
+                    }
+                    redraw.mark_dirty();
+                },
+                BackendMessage::SearchResults{ query, matches, truncated } => {
+                    let count = if truncated { format!("more than {}", matches.len()) } else { matches.len().to_string() };
+                    log::info!("Found {} matches for \"{}\"", count, query);
+                    let mut results = format!("Found {} matches for \"{}\"", count, query);
+                    for found in matches.iter().take(SEARCH_RESULTS_SHOWN) {
+                        results.push_str(&format!("\n{}:{}: {}", found.path.display(), found.line, found.text.trim()));
+                    }
+                    notification_text = Some(results);
+                    redraw.mark_dirty();
+                },
+                _ => {
+                    log::debug!("Frontend recieved message {:?}", message);
+                }
+            }
 
         }
 
         drop(incoming_queue);
 
 
-        let mut should_quit: bool = false;
-
         window.swap_buffers();
 
         let mut messages_for_backend: Vec<FrontendMessage> = Vec::new();
 
         //process events from glfw
-        glfw.poll_events();
         for (_, event) in glfw::flush_messages(&events){
+            if redraw::event_needs_redraw(&event) {
+                redraw.mark_dirty();
+            }
             match event{
-                glfw::WindowEvent::Size(x, y) => {
+                //the surface is sized in framebuffer pixels, the window's size in screen coordinates differs on HiDPI displays
+                glfw::WindowEvent::FramebufferSize(x, y) if x <= 0 || y <= 0 => {
+                    //a surface can't have no area, stop drawing until the window has one again
+                    paused = true;
+                },
+                glfw::WindowEvent::Iconify(iconified) => {
+                    paused = iconified;
+                    if !iconified {
+                        let size = window.get_framebuffer_size();
+                        paused = wgpu_state.resize(size).is_err();
+                    }
+                },
+                glfw::WindowEvent::FramebufferSize(x, y) => {
+                    paused = false;
                     let result = wgpu_state.resize((x, y));
                     match result {
                         Ok(..) => {//resize did not fail
@@ -88,9 +237,60 @@ pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, b
                         }
                     }
                 },
+                glfw::WindowEvent::Scroll(_, y) => {
+                    viewport.scroll_by(-y as f32 * line_height * 3.0);
+                },
+                glfw::WindowEvent::ContentScale(scale, _) => {
+                    //layout stays in logical pixels, glyphs are rasterized again for the new scale as lines are rebuilt
+                    log::info!("Content scale changed to {}", scale);
+                    wgpu_state.set_content_scale(scale);
+                    line_cache.clear();
+                },
+                glfw::WindowEvent::Key(Key::F2, _, Action::Press, modifiers) => {
+                    //shift shows debug entries too
+                    let level = if modifiers.contains(glfw::Modifiers::Shift) { log::Level::Debug } else { log::Level::Info };
+                    messages_for_backend.push(FrontendMessage::OpenLogBuffer{ level, module: None });
+                },
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, modifiers) if modifiers.contains(glfw::Modifiers::Control | glfw::Modifiers::Shift) => {
+                    //there's no search box yet, so this searches for whatever was last copied
+                    match window.get_clipboard_string().filter(|query| !query.trim().is_empty()) {
+                        Some(query) => messages_for_backend.push(FrontendMessage::ProjectSearch(query)),
+                        None => notification_text = Some(String::from("Copy the text to search the project for first"))
+                    }
+                },
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    for (id, _) in active_jobs.iter() {
+                        messages_for_backend.push(FrontendMessage::CancelJob(*id));
+                    }
+                },
+                glfw::WindowEvent::Key(Key::F9, _, Action::Press, modifiers) if modifiers.contains(glfw::Modifiers::Control | glfw::Modifiers::Shift) => {
+                    //debug command, exercises the same recovery as a driver reset
+                    log::warn!("Simulating GPU device loss");
+                    wgpu_state.mark_device_lost();
+                },
+                glfw::WindowEvent::Key(Key::F12, _, Action::Press, modifiers) => {
+                    if modifiers.contains(glfw::Modifiers::Shift) {
+                        match perf_hud.write_trace(&crate::intermediary::paths::trace_dir()) {
+                            Ok(path) => log::info!("Wrote trace to {}", path.display()),
+                            Err(error) => log::error!("Failed to write trace: {}", error)
+                        }
+                    } else {
+                        perf_hud.toggle();
+                    }
+                },
+                glfw::WindowEvent::Key(key, _, Action::Press, modifiers) if Capture::for_key(key, modifiers).is_some() => {
+                    let capture = Capture::for_key(key, modifiers).expect("Checked above!");
+                    if capture.needs_frame() {
+                        pending_capture = Some(capture);
+                    } else {
+                        let exported = export_visible_code(capture, &viewport, fonts.as_ref(), &mut shaper, line_height);
+                        window.set_clipboard_string(&exported);
+                        notification_text = Some(format!("Copied {} lines as {:?}", viewport.visible_lines().len(), capture));
+                    }
+                },
                 glfw::WindowEvent::Close => {
                     should_quit = true;
-                    println!("Closing!")
+                    log::info!("Closing!")
                 },
                 _ => {}
             }
@@ -100,44 +300,296 @@ pub fn main(frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>>, b
         if should_quit {
             messages_for_backend.push(FrontendMessage::UserQuit);
 
-            //print out the average time rendering took
-            println!("Rendering took an average of {:?}", rendering_total_time.checked_div(times_rendered));
+            //print out how long frames took
+            log::info!("Frame times: {}", perf_hud.summary());
 
             window.set_should_close(true);
         }
 
+        //ask for any lines scrolled into view
+        viewport.set_size(wgpu_state.get_logical_size().1, line_height);
+        if let Some(request) = viewport.take_request() {
+            messages_for_backend.push(request);
+        }
+
         //send messages to backend
         {
-            let mut outgoing_queue = frontend_message_queue.lock().unwrap();
+            let mut outgoing_queue = frontend_message_queue.lock().unwrap_or_else(|e| e.into_inner());
             for message in messages_for_backend{
                 outgoing_queue.add_message(message.clone());
             }
         }
 
-        //modify render state
-        render_state.clear();
-        render_state.add_mesh(
-            &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
-            ],
-            &[
-                0, 1, 2,
-            ] 
-        );
-
-        //set vertex and index buffers
-        wgpu_state.set_vertices_and_indices(&mut render_state);
-
-        //render the screen
-        let now = std::time::Instant::now();
-        crate::frontend::rendering::render::render(&wgpu_state, &render_state);
-        rendering_total_time += now.elapsed();
-        times_rendered += 1;
-
-
-        //sleep for a bit
-        thread::sleep(Duration::from_millis(50));
+        //stop before starting another frame, the backend has been told we're quitting
+        if should_quit {
+            break 'running;
+        }
+
+        //nothing changed, so the last frame is still on screen. the frame stays due while paused
+        if paused || !redraw.take_due(Instant::now()) {
+            continue;
+        }
+
+        //the gpu was reset or removed, rebuild everything on a new device. editor state lives in the backend and
+        //the viewport, so nothing is lost, and the last frame is uploaded again until this one is built
+        if wgpu_state.is_device_lost() {
+            if recreate_failures == 0 {
+                log::warn!("Recreating the GPU device");
+            }
+            //no device can be created while the adapter is resetting or the window is minimized, keep trying every frame
+            if let Err(error) = wgpu_state.recreate(&window, &mut render_state) {
+                if recreate_failures == 0 {
+                    log::error!("Couldn't recreate the GPU device, retrying: {}", error);
+                }
+                recreate_failures += 1;
+                redraw.mark_dirty();
+                continue;
+            }
+            if recreate_failures > 0 {
+                log::info!("Recreated the GPU device after {} failed attempts", recreate_failures);
+            }
+            recreate_failures = 0;
+            crate::intermediary::panic_hook::set_adapter_info(format!("{:?}", wgpu_state.get_adapter_info()));
+
+            //the new glyph atlas is empty, so cached meshes point at glyphs which aren't there
+            line_cache.clear();
+            surface_losses = 0;
+        }
+
+        //building and drawing the frame uses the gpu throughout, and wgpu panics when the device is lost in the middle
+        //of it.  that's treated as losing the device, which is recreated on the next frame
+        let frame = wgpu_state.catch_device_loss(|wgpu_state| {
+            //modify render state
+            render_state.clear();
+            let (width, height) = wgpu_state.get_logical_size();
+            render_state.add_mesh(
+                &[
+                    Vertex { position: [width * 0.75, height * 0.75, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                    Vertex { position: [width * 0.5, height * 0.25, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                    Vertex { position: [width * 0.25, height * 0.75, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
+                ],
+                &[
+                    0, 1, 2,
+                ] 
+            );
+
+            //overlays go in the popup layer so they're drawn over the panes whenever they're added
+            render_state.set_layer(Layer::Popup);
+            perf_hud.draw(&mut render_state);
+
+            //add text
+            if let Some(fonts) = &fonts {
+                wgpu_state.get_glyph_atlas_mut().begin_frame();
+                wgpu_state.get_pane_cache_mut().begin_frame();
+                shaper.begin_frame();
+                line_cache.begin_frame();
+
+                //the buffer's text is drawn into the editor pane's texture, which fills the window for now, and only when
+                //the viewport has changed since
+                let editor_pane = Pane{
+                    id: EDITOR_PANE,
+                    rect: editor_pane_rect(wgpu_state),
+                    revision: viewport.get_revision(),
+                    //opaque so the buffer's text keeps its subpixel antialiasing
+                    background: Some(CLEAR_COLOR)
+                };
+                if !wgpu_state.get_pane_cache_mut().is_current(&editor_pane) {
+                    pane_render_state.clear();
+                    pane_render_state.set_layer(Layer::Text);
+                    pane_render_state.set_clip(Some(editor_pane.rect));
+                    let ascent = fonts.get_primary().line_metrics(TEXT_SIZE).ascent;
+                    for number in viewport.visible_lines() {
+                        if let Some(line) = viewport.get_line(number) {
+                            let origin = [8.0, viewport.line_top(number) + ascent];
+                            line_cache.add_line(&mut pane_render_state, wgpu_state, &mut shaper, fonts, number, line, origin, TEXT_SIZE, TEXT_COLOR);
+                        }
+                    }
+
+                    if let Err(message) = render::render_pane(wgpu_state, &editor_pane, &mut pane_render_state) {
+                        log::error!("Failed to draw the editor pane: {}", message);
+                    }
+                }
+                render_state.set_layer(Layer::Text);
+                render_state.add_pane(&editor_pane);
+
+                render_state.set_layer(Layer::Popup);
+                render_state.set_clip(None);
+                let metrics = fonts.get_primary().line_metrics(TEXT_SIZE);
+                let mut y = metrics.ascent + 8.0;
+                if perf_hud.is_visible() {
+                    text::add_text(&mut render_state, wgpu_state, &mut shaper, fonts, &perf_hud.summary(), [8.0, y], TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+                    y += metrics.line_height;
+                }
+                if !active_jobs.is_empty() {
+                    let top = y;
+                    let mut right: f32 = 8.0;
+                    for (_, job) in active_jobs.iter() {
+                        right = right.max(text::add_text(&mut render_state, wgpu_state, &mut shaper, fonts, &job.describe(), [8.0, y], TEXT_SIZE, [0.8, 0.9, 1.0, 1.0]));
+                        y += metrics.line_height;
+                    }
+                    render_state.add_rect(
+                        [4.0, top - metrics.ascent - 4.0], [right + 4.0, y - metrics.line_height - metrics.descent + 4.0], 4.0, [0.15, 0.15, 0.15, 0.9],
+                        Some(Border{ width: 1.0, color: [0.3, 0.35, 0.4, 1.0] }),
+                        None
+                    );
+                }
+                if let Some(notification) = &notification_text {
+                    let top = height - 8.0 - metrics.line_height * (notification.lines().count() as f32 - 1.0) + metrics.descent;
+                    let mut y = top;
+                    let mut right: f32 = 8.0;
+                    for line in notification.lines() {
+                        right = right.max(text::add_text(&mut render_state, wgpu_state, &mut shaper, fonts, line, [8.0, y], TEXT_SIZE, [1.0, 1.0, 0.6, 1.0]));
+                        y += metrics.line_height;
+                    }
+
+                    //rects are drawn under the layer's text, so the box can be added once the text's width is known
+                    render_state.add_rect(
+                        [4.0, top - metrics.ascent - 4.0], [right + 4.0, height - 4.0], 4.0, [0.15, 0.15, 0.15, 0.9],
+                        Some(Border{ width: 1.0, color: [0.4, 0.4, 0.3, 1.0] }),
+                        Some(Shadow{ offset: [0.0, 2.0], blur: 6.0, color: [0.0, 0.0, 0.0, 0.5] })
+                    );
+                }
+            }
+
+            //set vertex and index buffers
+            let upload_start = Instant::now();
+            let upload_result = wgpu_state.set_vertices_and_indices(&mut render_state);
+            if let Err(message) = upload_result {
+                //the last frame that fit is drawn again, only log when the problem starts
+                if upload_error != Some(message) {
+                    log::error!("Failed to upload frame: {}", message);
+                }
+            }
+            upload_error = upload_result.err();
+            let upload_time = upload_start.elapsed();
+
+            //render the screen
+            let render_start = Instant::now();
+            let render_result = render::render(wgpu_state);
+            let render_time = render_start.elapsed();
+            (upload_start, upload_time, render_result, render_start, render_time)
+        });
+        let (upload_start, upload_time, render_result, render_start, render_time) = match frame {
+            Some(frame) => frame,
+            None => {
+                redraw.mark_dirty();
+                continue;
+            }
+        };
+
+        match render_result {
+            Ok(()) => {
+                surface_losses = 0;
+
+                if let Some(capture) = pending_capture.take() {
+                    match wgpu_state.catch_device_loss(|wgpu_state| take_screenshot(capture, wgpu_state, editor_pane_rect(wgpu_state))).unwrap_or_else(|| Err(String::from("the GPU device was lost"))) {
+                        Ok(path) => {
+                            log::info!("Saved screenshot to {}", path.display());
+                            notification_text = Some(format!("Saved screenshot to {}", path.display()));
+                        },
+                        Err(error) => {
+                            log::error!("Failed to take screenshot: {}", error);
+                            notification_text = Some(format!("Failed to take screenshot: {}", error));
+                        }
+                    }
+                    redraw.mark_dirty();
+                }
+            },
+            Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                //the compositor restarted or the window changed under us, set the surface up again and redraw
+                surface_losses += 1;
+                if surface_losses > MAX_SURFACE_RECONFIGURES {
+                    log::warn!("Surface still lost after reconfiguring {} times, treating the device as lost", MAX_SURFACE_RECONFIGURES);
+                    wgpu_state.mark_device_lost();
+                }else{
+                    log::warn!("Surface lost or outdated, reconfiguring");
+                    wgpu_state.reconfigure();
+                }
+                redraw.mark_dirty();
+                continue;
+            },
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::debug!("Timed out waiting for a surface texture, skipping frame");
+                redraw.mark_dirty();
+                continue;
+            },
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                //nothing more can be drawn, let the backend save what it can
+                log::error!("Out of memory getting a surface texture");
+                frontend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(FrontendMessage::FatalError(String::from("The gpu ran out of memory")));
+                break 'running;
+            }
+        }
+
+        perf_hud.record_frame(FrameSample{
+            start: Some(frame_start),
+            frame_time: frame_start.elapsed(),
+            upload_start: Some(upload_start),
+            upload_time,
+            render_start: Some(render_start),
+            render_time,
+            vertices: render_state.num_vertices(),
+            indices: render_state.num_indices(),
+            quads: render_state.num_quads(),
+            buffer_reallocations: wgpu_state.get_buffer_reallocations(),
+            queue_depth
+        });
+
+        //the overlay's numbers keep updating while it's shown
+        if perf_hud.is_visible() {
+            redraw.schedule(Instant::now() + HUD_REFRESH_INTERVAL);
+        }
+    }
+
+    log::info!("Rendering Thread Stopped");
+}
+
+///The part of the window the editor pane's texture covers, which is all of it for now.
+fn editor_pane_rect(wgpu_state: &wgpu_state::WGPUState) -> ClipRect{
+    let (width, height) = wgpu_state.get_logical_size();
+    ClipRect::new(0.0, 0.0, width, height)
+}
+
+///Reads back the frame just drawn, crops it to the editor pane (`pane`, in logical pixels) if asked to, and saves it as a PNG.
+fn take_screenshot(capture: Capture, wgpu_state: &wgpu_state::WGPUState, pane: ClipRect) -> Result<std::path::PathBuf, String>{
+    let mut image = render::render_to_image(wgpu_state)?;
+
+    if capture == Capture::Pane {
+        let (x, y, width, height) = capture::pixel_rect([pane.x, pane.y, pane.width, pane.height], wgpu_state.get_view().get_content_scale());
+        image = image.crop(x, y, width, height);
+    }
+
+    capture::save_screenshot(&image, &crate::intermediary::paths::screenshot_dir()).map_err(|error| error.to_string())
+}
+
+///Renders the visible lines of the buffer as HTML or SVG in the editor's colours and font.
+fn export_visible_code(capture: Capture, viewport: &viewport::Viewport, fonts: Option<&FontChain>, shaper: &mut Shaper, line_height: f32) -> String{
+    let lines: Vec<Vec<ExportSpan>> = viewport.visible_lines()
+        .map(|number| {
+            let text = viewport.get_line(number).map_or(String::new(), |line| line.text.clone());
+            vec![ExportSpan{ text, color: TEXT_COLOR }]
+        })
+        .collect();
+
+    let style = ExportStyle{
+        background: CLEAR_COLOR,
+        font_family: code_export::css_font_family(fonts.and_then(|fonts| fonts.get_primary().family_name()).as_deref()),
+        font_size: TEXT_SIZE,
+        line_height
+    };
+
+    match capture {
+        Capture::Svg => {
+            //size the image to the widest line as the editor shapes it
+            let width = lines.iter()
+                .flat_map(|line| line.iter())
+                .map(|span| match fonts {
+                    Some(fonts) => shaper.shape(fonts, &span.text, TEXT_SIZE).width,
+                    None => span.text.chars().count() as f32 * TEXT_SIZE * 0.6
+                })
+                .fold(0.0, f32::max);
+            code_export::to_svg(&lines, &style, width)
+        },
+        _ => code_export::to_html(&lines, &style)
     }
 }
\ No newline at end of file
@@ -1,3 +1,10 @@
 pub mod main;
 pub mod wgpu_state;
-pub mod rendering;
\ No newline at end of file
+pub mod rendering;
+pub mod job_status;
+pub mod perf_hud;
+pub mod viewport;
+pub mod redraw;
+pub mod graphics_config;
+pub mod capture;
+pub mod code_export;
\ No newline at end of file
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::frontend::rendering::render_state::RenderState;
+
+///How many frames are kept for percentiles and the histogram.
+pub const FRAME_HISTORY: usize = 240;
+
+///How many trace events are kept before the oldest are dropped.
+pub const TRACE_CAPACITY: usize = 100_000;
+
+///Frame time which fills the whole height of the histogram (two 60hz frames).
+const HISTOGRAM_MAX_FRAME_TIME: f32 = 1.0 / 30.0;
+
+///Where the histogram is drawn, in logical pixels: below the summary text.
+const HISTOGRAM_MIN: [f32; 2] = [8.0, 32.0];
+const HISTOGRAM_MAX: [f32; 2] = [368.0, 112.0];
+
+///Measurements of a single frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameSample{
+    ///When the frame started.
+    pub start: Option<Instant>,
+    ///Whole frame, from processing messages up to presenting (not counting sleeping).
+    pub frame_time: Duration,
+    ///When set_vertices_and_indices was called.
+    pub upload_start: Option<Instant>,
+    ///Time spent in set_vertices_and_indices.
+    pub upload_time: Duration,
+    ///When render was called.
+    pub render_start: Option<Instant>,
+    ///Time spent in render.
+    pub render_time: Duration,
+    pub vertices: usize,
+    pub indices: u32,
+    ///Glyph and rectangle instances.
+    pub quads: usize,
+    ///Total vertex/index buffer reallocations so far.
+    pub buffer_reallocations: u32,
+    ///Backend messages waiting at the start of the frame.
+    pub queue_depth: usize
+}
+
+///A Chrome trace-event, either a complete span ("X") or a counter ("C").
+#[derive(Debug, Clone)]
+struct TraceEvent{
+    name: &'static str,
+    phase: char,
+    ///Microseconds since the trace started.
+    timestamp: u128,
+    ///Microseconds, only used by spans.
+    duration: u128,
+    ///Counter values, only used by counters.
+    args: Vec<(&'static str, u64)>
+}
+
+///Collects frame timings for the performance overlay and Chrome trace dumps.
+pub struct PerfHud{
+    visible: bool,
+    frames: VecDeque<FrameSample>,
+    trace_start: Instant,
+    trace: VecDeque<TraceEvent>
+}
+
+impl PerfHud{
+    pub fn new() -> PerfHud{
+        PerfHud{
+            visible: false,
+            frames: VecDeque::with_capacity(FRAME_HISTORY),
+            trace_start: Instant::now(),
+            trace: VecDeque::new()
+        }
+    }
+
+    ///Shows or hides the overlay.
+    pub fn toggle(&mut self){
+        self.visible = !self.visible;
+    }
+
+    ///Gets whether the overlay is showing.
+    pub fn is_visible(&self) -> bool{
+        self.visible
+    }
+
+    ///Records a finished frame.
+    pub fn record_frame(&mut self, sample: FrameSample){
+        if self.frames.len() == FRAME_HISTORY{
+            self.frames.pop_front();
+        }
+        self.frames.push_back(sample);
+
+        if let Some(start) = sample.start{
+            let frame_start = self.trace_timestamp(start);
+
+            self.push_trace(TraceEvent{ name: "frame", phase: 'X', timestamp: frame_start, duration: sample.frame_time.as_micros(), args: Vec::new() });
+            if let Some(upload_start) = sample.upload_start{
+                let timestamp = self.trace_timestamp(upload_start);
+                self.push_trace(TraceEvent{ name: "set_vertices_and_indices", phase: 'X', timestamp, duration: sample.upload_time.as_micros(), args: Vec::new() });
+            }
+            if let Some(render_start) = sample.render_start{
+                let timestamp = self.trace_timestamp(render_start);
+                self.push_trace(TraceEvent{ name: "render", phase: 'X', timestamp, duration: sample.render_time.as_micros(), args: Vec::new() });
+            }
+            self.push_trace(TraceEvent{
+                name: "geometry",
+                phase: 'C',
+                timestamp: frame_start,
+                duration: 0,
+                args: vec![("vertices", sample.vertices as u64), ("indices", sample.indices as u64), ("quads", sample.quads as u64)]
+            });
+            self.push_trace(TraceEvent{
+                name: "backend queue",
+                phase: 'C',
+                timestamp: frame_start,
+                duration: 0,
+                args: vec![("depth", sample.queue_depth as u64)]
+            });
+        }
+    }
+
+    ///Converts `time` to microseconds since the trace started.
+    fn trace_timestamp(&self, time: Instant) -> u128{
+        time.saturating_duration_since(self.trace_start).as_micros()
+    }
+
+    fn push_trace(&mut self, event: TraceEvent){
+        if self.trace.len() == TRACE_CAPACITY{
+            self.trace.pop_front();
+        }
+        self.trace.push_back(event);
+    }
+
+    ///Gets the `percentile` (0-100) frame time of the recorded frames.
+    pub fn frame_time_percentile(&self, percentile: f32) -> Duration{
+        let mut times: Vec<Duration> = self.frames.iter().map(|frame| frame.frame_time).collect();
+        if times.is_empty(){
+            return Duration::ZERO;
+        }
+        times.sort();
+
+        let index = ((percentile / 100.0) * (times.len() - 1) as f32).round() as usize;
+        times[index.min(times.len() - 1)]
+    }
+
+    ///Gets the average of the recorded frames' frame time, upload time and render time.
+    pub fn average_times(&self) -> (Duration, Duration, Duration){
+        if self.frames.is_empty(){
+            return (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+        }
+
+        let count = self.frames.len() as u32;
+        let frame: Duration = self.frames.iter().map(|frame| frame.frame_time).sum();
+        let upload: Duration = self.frames.iter().map(|frame| frame.upload_time).sum();
+        let render: Duration = self.frames.iter().map(|frame| frame.render_time).sum();
+        (frame / count, upload / count, render / count)
+    }
+
+    ///A one line summary of the recorded frames.
+    pub fn summary(&self) -> String{
+        let (_, upload, render) = self.average_times();
+        let latest = self.frames.back().copied().unwrap_or_default();
+
+        format!(
+            "p50 {:.2?} p95 {:.2?} p99 {:.2?} | upload {:.2?} render {:.2?} | {} vertices {} indices {} quads | {} reallocations | queue {}",
+            self.frame_time_percentile(50.0),
+            self.frame_time_percentile(95.0),
+            self.frame_time_percentile(99.0),
+            upload,
+            render,
+            latest.vertices,
+            latest.indices,
+            latest.quads,
+            latest.buffer_reallocations,
+            latest.queue_depth
+        )
+    }
+
+    ///Draws the frame time histogram into `render_state` if the overlay is visible.
+    ///Each bar is a frame, split into upload (orange), render (green) and everything else (grey),
+    ///with a white line at 60fps and a red line at the 95th percentile.
+    pub fn draw(&self, render_state: &mut RenderState){
+        if !self.visible{
+            return;
+        }
+
+        let width = HISTOGRAM_MAX[0] - HISTOGRAM_MIN[0];
+        let height = HISTOGRAM_MAX[1] - HISTOGRAM_MIN[1];
+        let to_height = |time: Duration| (time.as_secs_f32() / HISTOGRAM_MAX_FRAME_TIME).min(1.0) * height;
+
+        //translucent, so the text under the histogram can still be read
+        render_state.add_rect(HISTOGRAM_MIN, HISTOGRAM_MAX, 4.0, [0.05, 0.05, 0.05, 0.8], None, None);
+
+        //bars grow up from the bottom of the histogram
+        let bottom = HISTOGRAM_MAX[1];
+        let bar_width = width / FRAME_HISTORY as f32;
+        for (i, frame) in self.frames.iter().enumerate(){
+            let x0 = HISTOGRAM_MIN[0] + i as f32 * bar_width;
+            let x1 = x0 + bar_width;
+
+            let upload_top = bottom - to_height(frame.upload_time);
+            let render_top = (upload_top - to_height(frame.render_time)).max(HISTOGRAM_MIN[1]);
+            let frame_top = (bottom - to_height(frame.frame_time)).min(render_top).max(HISTOGRAM_MIN[1]);
+
+            render_state.add_quad([x0, upload_top], [x1, bottom], [1.0, 0.6, 0.1, 1.0]);
+            render_state.add_quad([x0, render_top], [x1, upload_top], [0.2, 0.8, 0.3, 1.0]);
+            render_state.add_quad([x0, frame_top], [x1, render_top], [0.5, 0.5, 0.5, 1.0]);
+        }
+
+        let line = |render_state: &mut RenderState, y: f32, color: [f32; 4]| {
+            render_state.add_quad([HISTOGRAM_MIN[0], y - 0.5], [HISTOGRAM_MAX[0], y + 0.5], color);
+        };
+        line(render_state, bottom - to_height(Duration::from_micros(16_667)), [1.0, 1.0, 1.0, 1.0]);
+        line(render_state, bottom - to_height(self.frame_time_percentile(95.0)), [1.0, 0.1, 0.1, 1.0]);
+    }
+
+    ///Renders the recorded trace as Chrome trace-event JSON, which can be opened in chrome://tracing or Perfetto.
+    pub fn trace_json(&self) -> String{
+        let events: Vec<String> = self.trace.iter().map(|event| {
+            let args: Vec<String> = event.args.iter().map(|(name, value)| format!("\"{}\":{}", name, value)).collect();
+            let duration = if event.phase == 'X' { format!(",\"dur\":{}", event.duration) } else { String::new() };
+            format!(
+                "{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{}{},\"pid\":1,\"tid\":1,\"args\":{{{}}}}}",
+                event.name, event.phase, event.timestamp, duration, args.join(",")
+            )
+        }).collect();
+
+        format!("{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n", events.join(",\n"))
+    }
+
+    ///Writes the recorded trace to a new timestamped file in `directory`. Returns the path of the written trace.
+    pub fn write_trace(&self, directory: &Path) -> io::Result<PathBuf>{
+        fs::create_dir_all(directory)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let path = directory.join(format!("trace-{}.json", timestamp));
+
+        fs::write(&path, self.trace_json())?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn frame(milliseconds: u64) -> FrameSample{
+        let start = Instant::now();
+        FrameSample{
+            start: Some(start),
+            frame_time: Duration::from_millis(milliseconds),
+            upload_start: Some(start),
+            upload_time: Duration::from_millis(milliseconds / 4),
+            render_start: Some(start + Duration::from_millis(milliseconds / 4)),
+            render_time: Duration::from_millis(milliseconds / 2),
+            ..FrameSample::default()
+        }
+    }
+
+    #[test]
+    fn percentiles_of_recorded_frames(){
+        let mut hud = PerfHud::new();
+        for milliseconds in 1..=100{
+            hud.record_frame(frame(milliseconds));
+        }
+
+        assert_eq!(hud.frame_time_percentile(0.0), Duration::from_millis(1));
+        assert_eq!(hud.frame_time_percentile(50.0), Duration::from_millis(51));
+        assert_eq!(hud.frame_time_percentile(100.0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn percentile_without_frames_is_zero(){
+        assert_eq!(PerfHud::new().frame_time_percentile(99.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn hidden_hud_draws_nothing(){
+        let mut hud = PerfHud::new();
+        hud.record_frame(frame(10));
+        let mut render_state = RenderState::new();
+
+        hud.draw(&mut render_state);
+
+        assert_eq!(render_state.num_indices(), 0);
+        assert_eq!(render_state.num_rects(), 0);
+        assert_eq!(render_state.num_quads(), 0);
+    }
+
+    #[test]
+    fn trace_has_spans_and_counters(){
+        let mut hud = PerfHud::new();
+        hud.record_frame(frame(10));
+
+        let json = hud.trace_json();
+
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(json.contains("\"name\":\"render\",\"ph\":\"X\""));
+        assert!(json.contains("\"name\":\"geometry\",\"ph\":\"C\""));
+    }
+
+    #[test]
+    fn spans_start_when_they_were_measured(){
+        let mut hud = PerfHud::new();
+        let start = hud.trace_start + Duration::from_millis(1);
+        hud.record_frame(FrameSample{
+            start: Some(start),
+            frame_time: Duration::from_millis(20),
+            upload_start: Some(start + Duration::from_millis(4)),
+            upload_time: Duration::from_millis(2),
+            render_start: Some(start + Duration::from_millis(9)),
+            render_time: Duration::from_millis(3),
+            ..FrameSample::default()
+        });
+
+        let timestamp = |name: &str| hud.trace.iter().find(|event| event.name == name).map(|event| event.timestamp);
+        assert_eq!(timestamp("frame"), Some(1000));
+        assert_eq!(timestamp("set_vertices_and_indices"), Some(5000));
+        assert_eq!(timestamp("render"), Some(10000));
+    }
+}
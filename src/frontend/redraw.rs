@@ -0,0 +1,143 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::intermediary::message_queue::{MessageQueue, Waker};
+
+///Decides when the frontend needs to draw a frame.  Frames are only drawn when something marked the window dirty,
+///or when an animation asked for a frame by a deadline, so an idle editor sleeps in glfw instead of redrawing.
+pub struct RedrawScheduler{
+    dirty: bool,
+    next_frame: Option<Instant>
+}
+
+impl RedrawScheduler{
+    ///Creates a scheduler which is already dirty, so the first frame is drawn straight away.
+    pub fn new() -> RedrawScheduler{
+        RedrawScheduler{
+            dirty: true,
+            next_frame: None
+        }
+    }
+
+    ///Something visible changed, draw a frame as soon as possible.
+    pub fn mark_dirty(&mut self){
+        self.dirty = true;
+    }
+
+    ///Draw a frame no later than `at`, for animations.  The earliest request wins.
+    pub fn schedule(&mut self, at: Instant){
+        self.next_frame = Some(self.next_frame.map_or(at, |next_frame| next_frame.min(at)));
+    }
+
+    ///Returns true if a frame should be drawn at `now`.
+    pub fn is_due(&self, now: Instant) -> bool{
+        self.dirty || self.next_frame.is_some_and(|next_frame| next_frame <= now)
+    }
+
+    ///Get how long to wait for events before a frame is due, or None to wait until an event arrives.
+    pub fn wait_timeout(&self, now: Instant) -> Option<Duration>{
+        if self.is_due(now) {
+            return Some(Duration::ZERO);
+        }
+        self.next_frame.map(|next_frame| next_frame.saturating_duration_since(now))
+    }
+
+    ///Returns true if a frame should be drawn at `now`, and clears the dirty flag and deadline if so.
+    pub fn take_due(&mut self, now: Instant) -> bool{
+        let due = self.is_due(now);
+        if due {
+            self.dirty = false;
+            self.next_frame = None;
+        }
+        due
+    }
+}
+
+impl Default for RedrawScheduler{
+    fn default() -> Self{
+        RedrawScheduler::new()
+    }
+}
+
+///Returns true if `event` can change what's on screen.  Mouse movement and window moves don't, so they don't cause frames.
+pub fn event_needs_redraw(event: &glfw::WindowEvent) -> bool{
+    !matches!(event,
+        glfw::WindowEvent::CursorPos(..) |
+        glfw::WindowEvent::CursorEnter(..) |
+        glfw::WindowEvent::Pos(..)
+    )
+}
+
+///Wakes the frontend from waiting on glfw events whenever the backend sends it a message.  The waker is removed
+///when this is dropped, including while unwinding, so nothing posts events after glfw has terminated.
+pub struct WakeOnMessage<T: Debug>{
+    queue: Arc<Mutex<MessageQueue<T>>>
+}
+
+impl<T: Debug> WakeOnMessage<T>{
+    pub fn install(queue: Arc<Mutex<MessageQueue<T>>>) -> WakeOnMessage<T>{
+        //glfwPostEmptyEvent may be called from any thread
+        let waker: Waker = Arc::new(|| unsafe { glfw::ffi::glfwPostEmptyEvent() });
+        queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).set_waker(Some(waker));
+
+        WakeOnMessage{
+            queue
+        }
+    }
+}
+
+impl<T: Debug> Drop for WakeOnMessage<T>{
+    fn drop(&mut self){
+        let mut queue = self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        queue.set_waker(None);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn first_frame_is_due_immediately(){
+        let now = Instant::now();
+        let mut scheduler = RedrawScheduler::new();
+
+        assert_eq!(scheduler.wait_timeout(now), Some(Duration::ZERO));
+        assert!(scheduler.take_due(now));
+        assert!(!scheduler.take_due(now));
+    }
+
+    #[test]
+    fn idle_waits_for_events(){
+        let now = Instant::now();
+        let mut scheduler = RedrawScheduler::new();
+        scheduler.take_due(now);
+
+        assert_eq!(scheduler.wait_timeout(now), None);
+        scheduler.mark_dirty();
+        assert_eq!(scheduler.wait_timeout(now), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn earliest_animation_deadline_wins(){
+        let now = Instant::now();
+        let mut scheduler = RedrawScheduler::new();
+        scheduler.take_due(now);
+
+        scheduler.schedule(now + Duration::from_millis(500));
+        scheduler.schedule(now + Duration::from_millis(100));
+        scheduler.schedule(now + Duration::from_millis(300));
+
+        assert_eq!(scheduler.wait_timeout(now), Some(Duration::from_millis(100)));
+        assert!(!scheduler.take_due(now));
+        assert!(scheduler.take_due(now + Duration::from_millis(100)));
+        assert_eq!(scheduler.wait_timeout(now), None);
+    }
+
+    #[test]
+    fn mouse_movement_doesnt_redraw(){
+        assert!(!event_needs_redraw(&glfw::WindowEvent::CursorPos(1.0, 2.0)));
+        assert!(event_needs_redraw(&glfw::WindowEvent::Size(640, 480)));
+    }
+}
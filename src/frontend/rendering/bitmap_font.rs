@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+
+///A glyph from a bitmap font, at the font's own pixel size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapGlyph{
+    pub width: u32,
+    pub height: u32,
+    ///Offset from the pen position on the baseline to the top left of the bitmap, in pixels (y down).
+    pub left: i32,
+    pub top: i32,
+    ///How far the pen moves after this glyph, in pixels.
+    pub advance: i32,
+    ///255 for set pixels and 0 for the rest, rows top to bottom.
+    pub coverage: Vec<u8>
+}
+
+///A font of fixed size bitmaps, loaded from a BDF or PCF file.  Character codes are taken as Unicode code points, which
+///is right for ISO10646 and ISO8859-1 fonts.
+#[derive(Debug, Clone)]
+pub struct BitmapFont{
+    ///The size the glyphs were drawn for, in pixels per em.
+    pub pixel_size: u32,
+    ///Distance from the baseline to the top and bottom of a line, in pixels.  Both are positive.
+    pub ascent: i32,
+    pub descent: i32,
+    pub glyphs: Vec<BitmapGlyph>,
+    characters: HashMap<char, u16>
+}
+
+impl BitmapFont{
+    ///Parses a BDF or PCF font, telling them apart by their first bytes.
+    pub fn parse(data: &[u8]) -> Result<BitmapFont, String>{
+        if data.starts_with(PCF_MAGIC) {
+            parse_pcf(data)
+        }else if data.starts_with(b"STARTFONT") {
+            let text = std::str::from_utf8(data).map_err(|_| String::from("BDF font isn't valid text"))?;
+            parse_bdf(text)
+        }else{
+            Err(String::from("Not a BDF or PCF font"))
+        }
+    }
+
+    ///Returns true if `data` looks like a BDF or PCF font.
+    pub fn is_bitmap_font(data: &[u8]) -> bool{
+        data.starts_with(PCF_MAGIC) || data.starts_with(b"STARTFONT")
+    }
+
+    ///Gets the glyph used for `character`, or None if this font doesn't have one.
+    pub fn glyph_id(&self, character: char) -> Option<u16>{
+        self.characters.get(&character).copied()
+    }
+
+    ///Gets glyph `glyph_id`.
+    pub fn get_glyph(&self, glyph_id: u16) -> Option<&BitmapGlyph>{
+        self.glyphs.get(glyph_id as usize)
+    }
+
+    ///Gets how much glyphs are enlarged to draw them at `size` pixels per em.  Pixel fonts only look right at whole
+    ///multiples of their size, so it's the nearest one.
+    pub fn scale_for(&self, size: f32) -> u32{
+        (size / self.pixel_size.max(1) as f32).round().max(1.0) as u32
+    }
+
+    ///Adds `glyph`, mapped from `character` if it has one.
+    fn push(&mut self, character: Option<char>, glyph: BitmapGlyph) -> Result<(), String>{
+        let id = u16::try_from(self.glyphs.len()).map_err(|_| String::from("Bitmap font has too many glyphs"))?;
+        if let Some(character) = character {
+            self.characters.insert(character, id);
+        }
+        self.glyphs.push(glyph);
+        Ok(())
+    }
+}
+
+///Parses a font in the Glyph Bitmap Distribution Format, the text format X11 pixel fonts are written in.
+pub fn parse_bdf(text: &str) -> Result<BitmapFont, String>{
+    let mut font = BitmapFont{
+        pixel_size: 0,
+        ascent: 0,
+        descent: 0,
+        glyphs: Vec::new(),
+        characters: HashMap::new()
+    };
+    //the font's bounding box, used for ascent and descent if there aren't properties for them
+    let mut bounds: Option<(i32, i32)> = None;
+    let mut point_size = 0;
+
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let mut words = line.split_whitespace();
+        let bad_line = || format!("BDF line {}: couldn't read '{}'", number + 1, line);
+
+        match words.next() {
+            Some("SIZE") => point_size = number_at(&mut words).ok_or_else(bad_line)?,
+            Some("FONTBOUNDINGBOX") => {
+                let values: Vec<i32> = words.filter_map(|word| word.parse().ok()).collect();
+                match values[..] {
+                    [_, height, _, y] => bounds = Some((height + y, -y)),
+                    _ => return Err(bad_line())
+                }
+            },
+            Some("PIXEL_SIZE") => font.pixel_size = number_at(&mut words).ok_or_else(bad_line)?,
+            Some("FONT_ASCENT") => font.ascent = number_at(&mut words).ok_or_else(bad_line)?,
+            Some("FONT_DESCENT") => font.descent = number_at(&mut words).ok_or_else(bad_line)?,
+            Some("STARTCHAR") => {
+                let (character, glyph) = parse_bdf_glyph(&mut lines)?;
+                font.push(character, glyph)?;
+            },
+            _ => {}
+        }
+    }
+
+    if font.glyphs.is_empty() {
+        return Err(String::from("BDF font has no glyphs"));
+    }
+    if font.ascent == 0 && font.descent == 0 {
+        (font.ascent, font.descent) = bounds.unwrap_or((font.glyphs[0].height as i32, 0));
+    }
+    if font.pixel_size == 0 {
+        font.pixel_size = if point_size > 0 { point_size } else { (font.ascent + font.descent).max(1) as u32 };
+    }
+    Ok(font)
+}
+
+///Parses the lines of one BDF glyph after its STARTCHAR, up to and including ENDCHAR.
+fn parse_bdf_glyph<'a>(lines: &mut impl Iterator<Item = (usize, &'a str)>) -> Result<(Option<char>, BitmapGlyph), String>{
+    let mut character = None;
+    let mut advance = 0;
+    let mut bounds = (0, 0, 0, 0);
+    let mut coverage = Vec::new();
+    let mut in_bitmap = false;
+
+    for (number, line) in lines {
+        let mut words = line.split_whitespace();
+        let bad_line = || format!("BDF line {}: couldn't read '{}'", number + 1, line);
+
+        match words.next() {
+            Some("ENCODING") => {
+                let code: i64 = number_at(&mut words).ok_or_else(bad_line)?;
+                character = u32::try_from(code).ok().and_then(char::from_u32);
+            },
+            Some("DWIDTH") => advance = number_at(&mut words).ok_or_else(bad_line)?,
+            Some("BBX") => {
+                let values: Vec<i32> = words.filter_map(|word| word.parse().ok()).collect();
+                bounds = match values[..] {
+                    [width, height, x, y] if width >= 0 && height >= 0 => (width, height, x, y),
+                    _ => return Err(bad_line())
+                };
+            },
+            Some("BITMAP") => in_bitmap = true,
+            Some("ENDCHAR") => {
+                let (width, height, x, y) = bounds;
+                let (width, height) = (width as u32, height as u32);
+                coverage.resize((width * height) as usize, 0);
+                return Ok((character, BitmapGlyph{
+                    width,
+                    height,
+                    left: x,
+                    top: -(y + height as i32),
+                    advance,
+                    coverage
+                }));
+            },
+            Some(row) if in_bitmap => {
+                //each row is hex, most significant bit first, padded to a whole byte
+                let width = bounds.0 as usize;
+                let bytes: Vec<u8> = (0..row.len() / 2)
+                    .map(|i| u8::from_str_radix(&row[i * 2..i * 2 + 2], 16))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| bad_line())?;
+                coverage.extend((0..width).map(|x| {
+                    let set = bytes.get(x / 8).is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0);
+                    if set { 255 } else { 0 }
+                }));
+            },
+            _ => {}
+        }
+    }
+
+    Err(String::from("BDF glyph is missing its ENDCHAR"))
+}
+
+///Parses the next word as a number.
+fn number_at<'a, T: std::str::FromStr>(words: &mut impl Iterator<Item = &'a str>) -> Option<T>{
+    words.next()?.parse().ok()
+}
+
+///The first bytes of a PCF file.
+const PCF_MAGIC: &[u8] = b"\x01fcp";
+
+//PCF table types
+const PCF_PROPERTIES: u32 = 1 << 0;
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+//PCF table format bits
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+const PCF_BYTE_MSB_FIRST: u32 = 1 << 2;
+const PCF_BIT_MSB_FIRST: u32 = 1 << 3;
+
+///Reads numbers from a PCF table, whose format says whether they're big or little endian.
+struct PcfReader<'a>{
+    data: &'a [u8],
+    position: usize,
+    big_endian: bool
+}
+
+impl<'a> PcfReader<'a>{
+    ///Starts reading the table at `offset`, after its format.  Returns the reader and the format.
+    fn table(data: &'a [u8], offset: usize) -> Option<(PcfReader<'a>, u32)>{
+        let mut reader = PcfReader{ data, position: offset, big_endian: false };
+        let format = reader.u32()?;
+        reader.big_endian = format & PCF_BYTE_MSB_FIRST != 0;
+        Some((reader, format))
+    }
+
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]>{
+        let bytes = self.data.get(self.position..self.position.checked_add(count)?)?;
+        self.position += count;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8>{
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16>{
+        let bytes: [u8; 2] = self.bytes(2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&mut self) -> Option<u32>{
+        let bytes: [u8; 4] = self.bytes(4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+}
+
+///One glyph's metrics from a PCF metrics table.
+struct PcfMetrics{
+    left: i32,
+    right: i32,
+    advance: i32,
+    ascent: i32,
+    descent: i32
+}
+
+///Parses a font in the Portable Compiled Format, the binary form X11 pixel fonts are usually installed in.
+pub fn parse_pcf(data: &[u8]) -> Result<BitmapFont, String>{
+    let bad = |what: &str| format!("PCF font has a bad {} table", what);
+
+    //the table of contents: type, format, size and offset of each table, always little endian
+    let mut reader = PcfReader{ data, position: PCF_MAGIC.len(), big_endian: false };
+    let count = reader.u32().ok_or_else(|| bad("contents"))?;
+    let mut tables = HashMap::new();
+    for _ in 0..count {
+        let kind = reader.u32().ok_or_else(|| bad("contents"))?;
+        let _format = reader.u32();
+        let _size = reader.u32();
+        let offset = reader.u32().ok_or_else(|| bad("contents"))?;
+        tables.insert(kind, offset as usize);
+    }
+    let table = |kind: u32, what: &str| {
+        tables.get(&kind)
+            .and_then(|&offset| PcfReader::table(data, offset))
+            .ok_or_else(|| bad(what))
+    };
+
+    let metrics = parse_pcf_metrics(table(PCF_METRICS, "metrics")?).ok_or_else(|| bad("metrics"))?;
+    let bitmaps = parse_pcf_bitmaps(table(PCF_BITMAPS, "bitmaps")?, &metrics).ok_or_else(|| bad("bitmaps"))?;
+    let characters = parse_pcf_encodings(table(PCF_BDF_ENCODINGS, "encodings")?, metrics.len()).ok_or_else(|| bad("encodings"))?;
+
+    //the BDF accelerators are more accurate, but either has the line's ascent and descent
+    let (mut reader, _) = table(PCF_BDF_ACCELERATORS, "accelerators").or_else(|_| table(PCF_ACCELERATORS, "accelerators"))?;
+    reader.bytes(8).ok_or_else(|| bad("accelerators"))?;
+    let ascent = reader.u32().ok_or_else(|| bad("accelerators"))? as i32;
+    let descent = reader.u32().ok_or_else(|| bad("accelerators"))? as i32;
+
+    let pixel_size = table(PCF_PROPERTIES, "properties").ok()
+        .and_then(|reader| pcf_property(reader.0, "PIXEL_SIZE"))
+        .filter(|&size| size > 0)
+        .unwrap_or((ascent + descent).max(1) as u32);
+
+    let mut font = BitmapFont{
+        pixel_size,
+        ascent,
+        descent,
+        glyphs: Vec::new(),
+        characters: HashMap::new()
+    };
+    for (metrics, coverage) in metrics.iter().zip(bitmaps) {
+        font.push(None, BitmapGlyph{
+            width: (metrics.right - metrics.left).max(0) as u32,
+            height: (metrics.ascent + metrics.descent).max(0) as u32,
+            left: metrics.left,
+            top: -metrics.ascent,
+            advance: metrics.advance,
+            coverage
+        })?;
+    }
+    font.characters = characters;
+
+    if font.glyphs.is_empty() {
+        return Err(String::from("PCF font has no glyphs"));
+    }
+    Ok(font)
+}
+
+fn parse_pcf_metrics((mut reader, format): (PcfReader, u32)) -> Option<Vec<PcfMetrics>>{
+    let compressed = format & PCF_COMPRESSED_METRICS != 0;
+    let count = if compressed { reader.u16()? as u32 } else { reader.u32()? };
+
+    (0..count).map(|_| {
+        if compressed {
+            //each value is a byte, offset by 0x80
+            let mut value = || Some(reader.u8()? as i32 - 0x80);
+            Some(PcfMetrics{ left: value()?, right: value()?, advance: value()?, ascent: value()?, descent: value()? })
+        }else{
+            let mut value = || Some(reader.u16()? as i16 as i32);
+            let metrics = PcfMetrics{ left: value()?, right: value()?, advance: value()?, ascent: value()?, descent: value()? };
+            //attributes
+            reader.u16()?;
+            Some(metrics)
+        }
+    }).collect()
+}
+
+fn parse_pcf_bitmaps((mut reader, format): (PcfReader, u32), metrics: &[PcfMetrics]) -> Option<Vec<Vec<u8>>>{
+    let count = reader.u32()? as usize;
+    let offsets: Vec<usize> = (0..count).map(|_| Some(reader.u32()? as usize)).collect::<Option<_>>()?;
+    let sizes: Vec<usize> = (0..4).map(|_| Some(reader.u32()? as usize)).collect::<Option<_>>()?;
+    let row_padding = 1 << (format & 3);
+    let scan_unit = 1 << ((format >> 4) & 3);
+    let mut bits = reader.bytes(sizes[(format & 3) as usize])?.to_vec();
+
+    //put the bits in most significant first order, whatever order the file has them in
+    if (format & PCF_BYTE_MSB_FIRST != 0) != (format & PCF_BIT_MSB_FIRST != 0) && scan_unit > 1 {
+        for unit in bits.chunks_mut(scan_unit) {
+            unit.reverse();
+        }
+    }
+    if format & PCF_BIT_MSB_FIRST == 0 {
+        for byte in bits.iter_mut() {
+            *byte = byte.reverse_bits();
+        }
+    }
+
+    metrics.iter().zip(offsets).map(|(metrics, offset)| {
+        let width = (metrics.right - metrics.left).max(0) as usize;
+        let height = (metrics.ascent + metrics.descent).max(0) as usize;
+        let row_bytes = width.div_ceil(8);
+        let stride = row_bytes.div_ceil(row_padding) * row_padding;
+
+        let mut coverage = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let start = offset + row * stride;
+            for x in 0..width {
+                let byte = *bits.get(start + x / 8)?;
+                coverage.push(if byte & (0x80 >> (x % 8)) != 0 { 255 } else { 0 });
+            }
+        }
+        Some(coverage)
+    }).collect()
+}
+
+fn parse_pcf_encodings((mut reader, _): (PcfReader, u32), glyph_count: usize) -> Option<HashMap<char, u16>>{
+    let first_column = reader.u16()? as u32;
+    let last_column = reader.u16()? as u32;
+    let first_row = reader.u16()? as u32;
+    let last_row = reader.u16()? as u32;
+    let _default_char = reader.u16()?;
+
+    let mut characters = HashMap::new();
+    for row in first_row..=last_row {
+        for column in first_column..=last_column {
+            let glyph = reader.u16()?;
+            if glyph == 0xFFFF || glyph as usize >= glyph_count {
+                continue;
+            }
+            if let Some(character) = char::from_u32(row << 8 | column) {
+                characters.insert(character, glyph);
+            }
+        }
+    }
+    Some(characters)
+}
+
+///Finds the number property `name` in a PCF properties table.
+fn pcf_property(mut reader: PcfReader, name: &str) -> Option<u32>{
+    let count = reader.u32()? as usize;
+    let mut properties = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_offset = reader.u32()? as usize;
+        let is_string = reader.u8()? != 0;
+        let value = reader.u32()?;
+        properties.push((name_offset, is_string, value));
+    }
+    //the property records are padded to four bytes
+    if !count.is_multiple_of(4) {
+        reader.bytes(4 - count % 4)?;
+    }
+    let strings_size = reader.u32()? as usize;
+    let strings = reader.bytes(strings_size)?;
+
+    properties.iter()
+        .find(|(offset, is_string, _)| !is_string && strings.get(*offset..).is_some_and(|rest| rest.starts_with(name.as_bytes()) && rest.get(name.len()) == Some(&0)))
+        .map(|&(_, _, value)| value)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const BDF: &str = "STARTFONT 2.1
+FONT -misc-tiny-medium-r-normal--4-40-75-75-c-40-iso10646-1
+SIZE 4 75 75
+FONTBOUNDINGBOX 3 4 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 3
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR A
+ENCODING 65
+DWIDTH 4 0
+BBX 3 3 0 0
+BITMAP
+40
+A0
+E0
+ENDCHAR
+STARTCHAR unmapped
+ENCODING -1
+DWIDTH 4 0
+BBX 1 1 0 0
+BITMAP
+80
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn bdf_glyphs_are_read(){
+        let font = BitmapFont::parse(BDF.as_bytes()).unwrap();
+
+        assert_eq!((font.pixel_size, font.ascent, font.descent), (4, 3, 1));
+        assert_eq!(font.glyphs.len(), 2);
+        assert_eq!(font.glyph_id('B'), None);
+
+        let glyph = font.get_glyph(font.glyph_id('A').unwrap()).unwrap();
+        assert_eq!((glyph.width, glyph.height, glyph.left, glyph.top, glyph.advance), (3, 3, 0, -3, 4));
+        assert_eq!(glyph.coverage, vec![0, 255, 0, 255, 0, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn pixel_fonts_scale_by_whole_multiples(){
+        let font = BitmapFont::parse(BDF.as_bytes()).unwrap();
+
+        assert_eq!(font.scale_for(2.0), 1);
+        assert_eq!(font.scale_for(9.0), 2);
+        assert_eq!(font.scale_for(14.0), 4);
+    }
+
+    ///Builds a little endian PCF font with one 3x2 glyph for 'A', with its rows padded to `padding` bytes.
+    fn pcf(padding: u32) -> Vec<u8>{
+        fn u32s(values: &[u32]) -> Vec<u8>{
+            values.iter().flat_map(|value| value.to_le_bytes()).collect()
+        }
+        let pad_format = padding.trailing_zeros();
+
+        let mut metrics = u32s(&[PCF_COMPRESSED_METRICS]);
+        metrics.extend([1, 0]);
+        metrics.extend([0x80, 0x83, 0x84, 0x82, 0x80]);
+
+        let mut bitmaps = u32s(&[PCF_BIT_MSB_FIRST | pad_format, 1, 0]);
+        let mut rows = vec![0b1010_0000, 0b0100_0000];
+        rows = rows.iter().flat_map(|&row| std::iter::once(row).chain(std::iter::repeat_n(0, padding as usize - 1))).collect();
+        let size = rows.len() as u32;
+        bitmaps.extend(u32s(&[size, size, size, size]));
+        bitmaps.extend(rows);
+
+        let mut encodings = u32s(&[0]);
+        for value in [65u16, 65, 0, 0, 0, 0] {
+            encodings.extend(value.to_le_bytes());
+        }
+
+        let mut accelerators = u32s(&[0]);
+        accelerators.extend([0; 8]);
+        accelerators.extend(u32s(&[2, 1]));
+
+        let tables = [(PCF_METRICS, metrics), (PCF_BITMAPS, bitmaps), (PCF_BDF_ENCODINGS, encodings), (PCF_ACCELERATORS, accelerators)];
+        let mut data = PCF_MAGIC.to_vec();
+        data.extend(u32s(&[tables.len() as u32]));
+        let mut offset = data.len() + tables.len() * 16;
+        for (kind, table) in &tables {
+            data.extend(u32s(&[*kind, 0, table.len() as u32, offset as u32]));
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            data.extend(table);
+        }
+        data
+    }
+
+    #[test]
+    fn pcf_glyphs_are_read(){
+        for padding in [1, 4] {
+            let font = BitmapFont::parse(&pcf(padding)).unwrap();
+
+            assert_eq!((font.pixel_size, font.ascent, font.descent), (3, 2, 1));
+            let glyph = font.get_glyph(font.glyph_id('A').unwrap()).unwrap();
+            assert_eq!((glyph.width, glyph.height, glyph.top, glyph.advance), (3, 2, -2, 4));
+            assert_eq!(glyph.coverage, vec![255, 0, 255, 0, 255, 0]);
+        }
+    }
+
+    #[test]
+    fn other_files_are_not_bitmap_fonts(){
+        assert!(BitmapFont::parse(b"\x00\x01\x00\x00").is_err());
+        assert!(!BitmapFont::is_bitmap_font(b"\x00\x01\x00\x00"));
+    }
+}
@@ -0,0 +1,72 @@
+//Colours everywhere in digit (themes, meshes, quads, text) are sRGB, not premultiplied, as they'd be written in CSS.
+//Frames are always drawn into an sRGB texture, so the shaders convert colours to linear light, blending happens in
+//linear light and the gpu encodes the result to sRGB again.  When the surface the adapter gave us isn't sRGB the frame
+//is drawn into a texture first and copied to it, see frame.rs.  That way a theme colour, a translucent fill or an
+//antialiased edge ends up as the same bytes on screen whichever kind of surface we're drawing to.
+
+///Converts an sRGB encoded channel to linear light.
+pub fn srgb_to_linear(value: f32) -> f32{
+    if value <= 0.04045 {
+        value / 12.92
+    }else{
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+///Converts a channel in linear light to sRGB encoding.
+#[cfg(test)]
+pub fn linear_to_srgb(value: f32) -> f32{
+    if value <= 0.0031308 {
+        value * 12.92
+    }else{
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+///Returns true if writes to `format` are encoded to sRGB by the gpu.
+pub fn is_srgb(format: wgpu::TextureFormat) -> bool{
+    format.describe().srgb
+}
+
+///Returns the sRGB format frames for a `target_format` target are drawn in, which is the target's own format if it's
+///already sRGB.
+pub fn drawing_format(target_format: wgpu::TextureFormat) -> wgpu::TextureFormat{
+    match target_format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+        _ => wgpu::TextureFormat::Rgba8UnormSrgb
+    }
+}
+
+///Picks the format to configure a surface with from the formats it supports.  sRGB formats are preferred so frames are
+///drawn straight to the surface, but any format works since frames are copied to it, see [drawing_format].
+pub fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat>{
+    formats.iter().copied().find(|&format| is_srgb(format)).or_else(|| formats.first().copied())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips(){
+        for value in [0.0, 0.02, 0.1, 0.5, 0.9, 1.0] {
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn frames_are_drawn_in_srgb(){
+        assert_eq!(drawing_format(wgpu::TextureFormat::Bgra8Unorm), wgpu::TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(drawing_format(wgpu::TextureFormat::Rgba8UnormSrgb), wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert!(is_srgb(drawing_format(wgpu::TextureFormat::Rgba16Float)));
+    }
+
+    #[test]
+    fn srgb_surface_formats_are_preferred(){
+        let formats = [wgpu::TextureFormat::Bgra8Unorm, wgpu::TextureFormat::Bgra8UnormSrgb];
+        assert_eq!(choose_surface_format(&formats), Some(wgpu::TextureFormat::Bgra8UnormSrgb));
+        assert_eq!(choose_surface_format(&formats[..1]), Some(wgpu::TextureFormat::Bgra8Unorm));
+        assert_eq!(choose_surface_format(&[]), None);
+    }
+}
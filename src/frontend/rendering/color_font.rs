@@ -0,0 +1,152 @@
+use ttf_parser::{Face, GlyphId, RasterImageFormat, RgbaColor};
+
+use crate::frontend::rendering::font::{ColorGlyph, OutlineRasterizer};
+use crate::frontend::rendering::image::RgbaImage;
+
+///Renders a color glyph at `size` pixels per em, from the font's embedded images (CBDT and sbix tables) or its
+///colored layers (COLR).  Returns None for glyphs which are only an outline.
+pub fn rasterize(face: &Face, glyph_id: u16, size: f32) -> Option<ColorGlyph>{
+    rasterize_image(face, glyph_id, size).or_else(|| rasterize_layers(face, glyph_id, size))
+}
+
+///Scales the embedded image closest to `size` to fit.  Emoji fonts usually only have one size of image, so this is
+///mostly shrinking a large one down.
+fn rasterize_image(face: &Face, glyph_id: u16, size: f32) -> Option<ColorGlyph>{
+    let raster = face.glyph_raster_image(GlyphId(glyph_id), size.round().clamp(1.0, u16::MAX as f32) as u16)?;
+    let mut image = match raster.format {
+        RasterImageFormat::PNG => RgbaImage::decode_png(raster.data).ok()?,
+        RasterImageFormat::BitmapPremulBgra32 => RgbaImage{
+            width: raster.width as u32,
+            height: raster.height as u32,
+            pixels: raster.data.chunks_exact(4).flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]]).collect()
+        },
+        //monochrome and grayscale bitmaps are left to the outline
+        _ => return None
+    };
+    if raster.format == RasterImageFormat::PNG {
+        image.premultiply();
+    }
+    if image.width == 0 || image.height == 0 || image.pixels.len() != (image.width * image.height * 4) as usize {
+        return None;
+    }
+
+    //raster positions are from the origin to the bottom left of the image, y up
+    let scale = size / raster.pixels_per_em.max(1) as f32;
+    let width = (image.width as f32 * scale).round().max(1.0) as u32;
+    let height = (image.height as f32 * scale).round().max(1.0) as u32;
+    Some(ColorGlyph{
+        left: (raster.x as f32 * scale).round() as i32,
+        top: -((raster.y as f32 + image.height as f32) * scale).round() as i32,
+        image: image.resized(width, height),
+        foreground: None
+    })
+}
+
+///Draws each of the glyph's layers over the last in its palette color.  Layers in the text's own color are drawn
+///black, and how much they cover is kept separately so the text's color can be added when the glyph is drawn.
+fn rasterize_layers(face: &Face, glyph_id: u16, size: f32) -> Option<ColorGlyph>{
+    if !face.is_color_glyph(GlyphId(glyph_id)) {
+        return None;
+    }
+    let mut layers = Layers{ current: None, layers: Vec::new() };
+    face.paint_color_glyph(GlyphId(glyph_id), 0, &mut layers)?;
+
+    //bitmap bounds in pixels, y down, covering every layer
+    let scale = size / face.units_per_em() as f32;
+    let bounds = layers.layers.iter().filter_map(|(layer, _)| face.glyph_bounding_box(*layer));
+    let (left, right, top, bottom) = bounds.fold(None, |union: Option<(i32, i32, i32, i32)>, bounds| {
+        let pixels = (
+            (bounds.x_min as f32 * scale).floor() as i32,
+            (bounds.x_max as f32 * scale).ceil() as i32,
+            (-bounds.y_max as f32 * scale).floor() as i32,
+            (-bounds.y_min as f32 * scale).ceil() as i32
+        );
+        Some(union.map_or(pixels, |union| (union.0.min(pixels.0), union.1.max(pixels.1), union.2.min(pixels.2), union.3.max(pixels.3))))
+    })?;
+    let width = (right - left).max(1) as u32;
+    let height = (bottom - top).max(1) as u32;
+
+    let mut image = RgbaImage::new(width, height);
+    let mut foreground = vec![0; (width * height) as usize];
+    let mut has_foreground = false;
+    for (layer, color) in layers.layers {
+        let mut builder = OutlineRasterizer::new(width, height, scale, left as f32, top as f32);
+        if face.outline_glyph(layer, &mut builder).is_none() {
+            continue;
+        }
+        has_foreground |= color.is_none();
+        composite_layer(&mut image, &mut foreground, &builder.finish(), color);
+    }
+
+    Some(ColorGlyph{
+        left,
+        top,
+        image,
+        foreground: Some(foreground).filter(|_| has_foreground)
+    })
+}
+
+///Draws a layer covering `coverage` over `image` in `color`.  A layer in the text's color (None) is drawn black, and
+///`foreground` adds up how much of each pixel it leaves in the text's color.  Compositing is linear in the layers'
+///colors, so adding the text's color times `foreground` to the image gives the same as drawing the layer in it.
+fn composite_layer(image: &mut RgbaImage, foreground: &mut [u8], coverage: &[u8], color: Option<RgbaColor>){
+    let is_foreground = color.is_none();
+    let color = color.unwrap_or(RgbaColor::new(0, 0, 0, 255));
+    let pixels = image.pixels.chunks_exact_mut(4).zip(foreground.iter_mut()).zip(coverage);
+    for ((pixel, foreground), &coverage) in pixels {
+        let alpha = coverage as u32 * color.alpha as u32 / 255;
+        let source = [color.red, color.green, color.blue].map(|channel| channel as u32 * alpha / 255);
+        for (channel, source) in pixel.iter_mut().zip(source.into_iter().chain([alpha])){
+            *channel = (source + *channel as u32 * (255 - alpha) / 255) as u8;
+        }
+
+        let added = if is_foreground { alpha } else { 0 };
+        *foreground = (added + *foreground as u32 * (255 - alpha) / 255) as u8;
+    }
+}
+
+///Collects a COLR glyph's layers and their colors, None for the text's color.
+struct Layers{
+    current: Option<GlyphId>,
+    layers: Vec<(GlyphId, Option<RgbaColor>)>
+}
+
+impl ttf_parser::colr::Painter for Layers{
+    fn outline(&mut self, glyph_id: GlyphId){
+        self.current = Some(glyph_id);
+    }
+
+    fn paint_foreground(&mut self){
+        if let Some(glyph_id) = self.current.take() {
+            self.layers.push((glyph_id, None));
+        }
+    }
+
+    fn paint_color(&mut self, color: RgbaColor){
+        if let Some(glyph_id) = self.current.take() {
+            self.layers.push((glyph_id, Some(color)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn foreground_layers_are_kept_apart_for_the_text_color(){
+        let mut image = RgbaImage::new(2, 1);
+        let mut foreground = vec![0; 2];
+
+        //a red layer over both pixels, then a layer in the text's color over the right half of the second
+        composite_layer(&mut image, &mut foreground, &[255, 255], Some(RgbaColor::new(255, 0, 0, 255)));
+        composite_layer(&mut image, &mut foreground, &[0, 128], None);
+
+        assert_eq!(image.pixels, vec![255, 0, 0, 255, 127, 0, 0, 255]);
+        assert_eq!(foreground, vec![0, 128]);
+
+        //a red layer over the foreground hides some of it again
+        composite_layer(&mut image, &mut foreground, &[0, 128], Some(RgbaColor::new(255, 0, 0, 255)));
+        assert_eq!(foreground, vec![0, 63]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,452 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use ab_glyph_rasterizer::{point, Point, Rasterizer};
+
+use crate::frontend::rendering::bitmap_font::BitmapFont;
+use crate::frontend::rendering::color_font;
+use crate::frontend::rendering::image::RgbaImage;
+
+///Fonts tried, in order, when no font has been configured.
+const DEFAULT_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "/System/Library/Fonts/Monaco.ttf",
+    "C:\\Windows\\Fonts\\consola.ttf",
+    "C:\\Windows\\Fonts\\cour.ttf"
+];
+
+///The first bytes of a gzipped file.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+///A glyph rendered to an 8 bit coverage bitmap.
+#[derive(Debug, Clone)]
+pub struct RasterizedGlyph{
+    pub width: u32,
+    pub height: u32,
+    ///Offset from the pen position on the baseline to the left edge of the bitmap, in pixels.
+    pub left: i32,
+    ///Offset from the baseline to the top edge of the bitmap, in pixels. Negative is above the baseline.
+    pub top: i32,
+    ///One byte of coverage per pixel, rows top to bottom.
+    pub coverage: Vec<u8>
+}
+
+///A glyph drawn in its own colors, like an emoji.
+#[derive(Debug, Clone)]
+pub struct ColorGlyph{
+    ///Offset from the pen position on the baseline to the top left of the image, in pixels (y down).
+    pub left: i32,
+    pub top: i32,
+    ///sRGB, premultiplied by alpha.
+    pub image: RgbaImage,
+    ///For glyphs with parts in the text's color: how much of each pixel is in it, which the text's color is added
+    ///by.  Those parts are black in `image`.
+    pub foreground: Option<Vec<u8>>
+}
+
+///Vertical metrics of a font at a particular size, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics{
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_height: f32
+}
+
+///A TrueType/OpenType font, or a BDF/PCF bitmap font, loaded into memory.
+pub struct Font{
+    source: FontSource,
+    path: Option<PathBuf>
+}
+
+enum FontSource{
+    ///TrueType/OpenType font file data, parsed as it's used.
+    Outline(Vec<u8>),
+    Bitmap(BitmapFont)
+}
+
+impl Font{
+    ///Loads a font from font file data, checking that it can be parsed.  Gzipped files are unpacked first, since pixel
+    ///fonts are usually installed that way.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, String>{
+        let data = if data.starts_with(GZIP_MAGIC) {
+            let mut unpacked = Vec::new();
+            flate2::read::GzDecoder::new(&data[..]).read_to_end(&mut unpacked)
+                .map_err(|error| format!("Failed to unpack font: {}", error))?;
+            unpacked
+        }else{
+            data
+        };
+
+        let source = if BitmapFont::is_bitmap_font(&data) {
+            FontSource::Bitmap(BitmapFont::parse(&data)?)
+        }else{
+            ttf_parser::Face::parse(&data, 0).map_err(|error| format!("Failed to parse font: {}", error))?;
+            FontSource::Outline(data)
+        };
+
+        Ok(Font{
+            source,
+            path: None
+        })
+    }
+
+    ///Loads a font from a .ttf/.otf/.ttc, .bdf or .pcf file, which may be gzipped.
+    pub fn from_file(path: &Path) -> io::Result<Font>{
+        let mut font = Font::from_bytes(fs::read(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        font.path = Some(path.to_path_buf());
+        Ok(font)
+    }
+
+    ///Loads the first font from [DEFAULT_FONT_PATHS] which exists.
+    pub fn find_default() -> Option<Font>{
+        DEFAULT_FONT_PATHS.iter()
+            .map(Path::new)
+            .filter(|path| path.exists())
+            .find_map(|path| Font::from_file(path).ok())
+    }
+
+    ///Get the path this font was loaded from, if it came from a file.
+    pub fn get_path(&self) -> Option<&Path>{
+        self.path.as_deref()
+    }
+
+    ///Get the raw font file data of an outline font, for libraries which parse the font themselves.
+    ///None for bitmap fonts.
+    pub fn data(&self) -> Option<&[u8]>{
+        match &self.source {
+            FontSource::Outline(data) => Some(data),
+            FontSource::Bitmap(_) => None
+        }
+    }
+
+    ///Parses an outline font, None for bitmap fonts. Parsing only reads the table directory so it is cheap enough to do per call.
+    pub fn face(&self) -> Option<ttf_parser::Face<'_>>{
+        let data = self.data()?;
+        Some(ttf_parser::Face::parse(data, 0).expect("Font was checked when it was loaded!"))
+    }
+
+    ///Get the font's family name from its name table, preferring the typographic family over the legacy one which
+    ///includes styles like "Bold".  None for bitmap fonts and fonts without a readable name.
+    pub fn family_name(&self) -> Option<String>{
+        let face = self.face()?;
+        [ttf_parser::name_id::TYPOGRAPHIC_FAMILY, ttf_parser::name_id::FAMILY].iter()
+            .find_map(|&id| face.names().into_iter().filter(|name| name.name_id == id).find_map(|name| name.to_string()))
+    }
+
+    ///Returns true for fonts with glyphs in their own colors (COLR, CBDT or sbix tables), like emoji fonts.
+    pub fn has_color_glyphs(&self) -> bool{
+        self.face().is_some_and(|face| {
+            let tables = face.tables();
+            tables.colr.is_some() || tables.cbdt.is_some() || tables.sbix.is_some()
+        })
+    }
+
+    ///Gets the glyph used for `character`, or None if this font doesn't have one.
+    pub fn glyph_id(&self, character: char) -> Option<u16>{
+        match &self.source {
+            FontSource::Outline(_) => self.face()?.glyph_index(character).map(|id| id.0),
+            FontSource::Bitmap(font) => font.glyph_id(character)
+        }
+    }
+
+    ///Gets how far the pen moves after drawing `glyph_id` at `size` pixels per em.
+    pub fn advance(&self, glyph_id: u16, size: f32) -> f32{
+        match (&self.source, self.face()) {
+            (FontSource::Bitmap(font), _) => {
+                let advance = font.get_glyph(glyph_id).map_or(0, |glyph| glyph.advance);
+                (advance * font.scale_for(size) as i32) as f32
+            },
+            (_, Some(face)) => {
+                let advance = face.glyph_hor_advance(ttf_parser::GlyphId(glyph_id)).unwrap_or(0);
+                advance as f32 * size / face.units_per_em() as f32
+            },
+            (_, None) => 0.0
+        }
+    }
+
+    ///Gets the vertical metrics at `size` pixels per em.
+    pub fn line_metrics(&self, size: f32) -> LineMetrics{
+        let (ascent, descent, line_gap) = match (&self.source, self.face()) {
+            (FontSource::Bitmap(font), _) => {
+                let scale = font.scale_for(size) as f32;
+                (font.ascent as f32 * scale, -font.descent as f32 * scale, 0.0)
+            },
+            (_, Some(face)) => {
+                let scale = size / face.units_per_em() as f32;
+                (face.ascender() as f32 * scale, face.descender() as f32 * scale, face.line_gap() as f32 * scale)
+            },
+            (_, None) => (size, 0.0, 0.0)
+        };
+
+        LineMetrics{
+            ascent,
+            descent,
+            line_height: ascent - descent + line_gap
+        }
+    }
+
+    ///Renders `glyph_id` at `size` pixels per em.  Returns None for glyphs with no outline, like spaces.
+    pub fn rasterize(&self, glyph_id: u16, size: f32) -> Option<RasterizedGlyph>{
+        self.rasterize_columns(glyph_id, size, 1, 0)
+    }
+
+    ///Renders `glyph_id` like [Font::rasterize] at three times the horizontal resolution, one column per LCD subpixel.
+    ///There's a spare pixel (three columns) either side for the subpixel filter to spread into.  `width` counts
+    ///columns, `left` is still in pixels.
+    pub fn rasterize_subpixel(&self, glyph_id: u16, size: f32) -> Option<RasterizedGlyph>{
+        self.rasterize_columns(glyph_id, size, 3, 1)
+    }
+
+    ///Renders `glyph_id` in its own colors at `size` pixels per em, if it's a color glyph like an emoji.
+    ///Returns None for every other glyph, which are drawn with [Font::rasterize] in the text's color.
+    pub fn rasterize_color(&self, glyph_id: u16, size: f32) -> Option<ColorGlyph>{
+        color_font::rasterize(&self.face()?, glyph_id, size)
+    }
+
+    ///Renders `glyph_id` with `columns` columns per pixel and `margin` empty pixels around the outline's bounds.
+    fn rasterize_columns(&self, glyph_id: u16, size: f32, columns: u32, margin: i32) -> Option<RasterizedGlyph>{
+        let face = match &self.source {
+            FontSource::Bitmap(font) => return rasterize_bitmap(font, glyph_id, size, columns, margin),
+            FontSource::Outline(_) => self.face()?
+        };
+        let scale = size / face.units_per_em() as f32;
+        let bounds = face.glyph_bounding_box(ttf_parser::GlyphId(glyph_id))?;
+
+        //bitmap bounds in pixels, y down
+        let left = (bounds.x_min as f32 * scale).floor() as i32 - margin;
+        let right = (bounds.x_max as f32 * scale).ceil() as i32 + margin;
+        let top = (-bounds.y_max as f32 * scale).floor() as i32 - margin;
+        let bottom = (-bounds.y_min as f32 * scale).ceil() as i32 + margin;
+        let width = (right - left).max(1) as u32 * columns;
+        let height = (bottom - top).max(1) as u32;
+
+        let columns = columns as f32;
+        let mut builder = OutlineRasterizer::stretched(width, height, [scale * columns, scale], left as f32 * columns, top as f32);
+        face.outline_glyph(ttf_parser::GlyphId(glyph_id), &mut builder)?;
+
+        Some(RasterizedGlyph{
+            width,
+            height,
+            left,
+            top,
+            coverage: builder.finish()
+        })
+    }
+}
+
+///Enlarges a bitmap font's glyph to the whole multiple of its size nearest `size`, with `columns` columns per pixel and
+///`margin` empty pixels around it, like [Font::rasterize_columns] does for outlines.
+fn rasterize_bitmap(font: &BitmapFont, glyph_id: u16, size: f32, columns: u32, margin: i32) -> Option<RasterizedGlyph>{
+    let glyph = font.get_glyph(glyph_id).filter(|glyph| glyph.width > 0 && glyph.height > 0)?;
+    let scale = font.scale_for(size);
+    let margin = margin as u32;
+    let width = (glyph.width * scale + margin * 2) * columns;
+    let height = glyph.height * scale + margin * 2;
+
+    let mut coverage = vec![0; (width * height) as usize];
+    for y in 0..glyph.height * scale {
+        for x in 0..glyph.width * scale * columns {
+            let value = glyph.coverage[((y / scale) * glyph.width + x / columns / scale) as usize];
+            coverage[((y + margin) * width + x + margin * columns) as usize] = value;
+        }
+    }
+
+    Some(RasterizedGlyph{
+        width,
+        height,
+        left: glyph.left * scale as i32 - margin as i32,
+        top: glyph.top * scale as i32 - margin as i32,
+        coverage
+    })
+}
+
+///Feeds a glyph outline from ttf-parser into ab_glyph_rasterizer, scaling from font units to pixels and flipping y.
+pub struct OutlineRasterizer{
+    rasterizer: Rasterizer,
+    width: u32,
+    height: u32,
+    ///Pixels per font unit, horizontally and vertically.
+    scale: [f32; 2],
+    offset_x: f32,
+    offset_y: f32,
+    start: Point,
+    last: Point
+}
+
+impl OutlineRasterizer{
+    ///Creates a rasterizer for a `width` x `height` bitmap whose top left corner is at (offset_x, offset_y) pixels from the glyph origin.
+    pub fn new(width: u32, height: u32, scale: f32, offset_x: f32, offset_y: f32) -> OutlineRasterizer{
+        OutlineRasterizer::stretched(width, height, [scale, scale], offset_x, offset_y)
+    }
+
+    ///Like [OutlineRasterizer::new], with different horizontal and vertical scales.
+    pub fn stretched(width: u32, height: u32, scale: [f32; 2], offset_x: f32, offset_y: f32) -> OutlineRasterizer{
+        OutlineRasterizer{
+            rasterizer: Rasterizer::new(width as usize, height as usize),
+            width,
+            height,
+            scale,
+            offset_x,
+            offset_y,
+            start: point(0.0, 0.0),
+            last: point(0.0, 0.0)
+        }
+    }
+
+    ///Converts a point in font units to bitmap pixels.
+    fn to_pixels(&self, x: f32, y: f32) -> Point{
+        point(x * self.scale[0] - self.offset_x, -y * self.scale[1] - self.offset_y)
+    }
+
+    ///Gets the coverage bitmap, one byte per pixel.
+    pub fn finish(self) -> Vec<u8>{
+        let mut coverage = vec![0u8; (self.width * self.height) as usize];
+        let width = self.width as usize;
+        self.rasterizer.for_each_pixel_2d(|x, y, alpha| {
+            coverage[y as usize * width + x as usize] = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+        });
+        coverage
+    }
+}
+
+impl ttf_parser::OutlineBuilder for OutlineRasterizer{
+    fn move_to(&mut self, x: f32, y: f32){
+        self.start = self.to_pixels(x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32){
+        let to = self.to_pixels(x, y);
+        self.rasterizer.draw_line(self.last, to);
+        self.last = to;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32){
+        let control = self.to_pixels(x1, y1);
+        let to = self.to_pixels(x, y);
+        self.rasterizer.draw_quad(self.last, control, to);
+        self.last = to;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32){
+        let control1 = self.to_pixels(x1, y1);
+        let control2 = self.to_pixels(x2, y2);
+        let to = self.to_pixels(x, y);
+        self.rasterizer.draw_cubic(self.last, control1, control2, to);
+        self.last = to;
+    }
+
+    fn close(&mut self){
+        if self.last != self.start{
+            self.rasterizer.draw_line(self.last, self.start);
+        }
+        self.last = self.start;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ttf_parser::OutlineBuilder;
+
+    #[test]
+    fn square_outline_is_fully_covered(){
+        //a 4x4 unit square drawn into a 4x4 bitmap, font units going up
+        let mut rasterizer = OutlineRasterizer::new(4, 4, 1.0, 0.0, -4.0);
+        rasterizer.move_to(0.0, 0.0);
+        rasterizer.line_to(4.0, 0.0);
+        rasterizer.line_to(4.0, 4.0);
+        rasterizer.line_to(0.0, 4.0);
+        rasterizer.close();
+
+        assert!(rasterizer.finish().iter().all(|&coverage| coverage == 255));
+    }
+
+    #[test]
+    fn half_square_is_half_covered(){
+        let mut rasterizer = OutlineRasterizer::new(4, 4, 1.0, 0.0, -4.0);
+        rasterizer.move_to(0.0, 0.0);
+        rasterizer.line_to(2.0, 0.0);
+        rasterizer.line_to(2.0, 4.0);
+        rasterizer.line_to(0.0, 4.0);
+        rasterizer.close();
+
+        let coverage = rasterizer.finish();
+        assert_eq!(coverage.iter().filter(|&&coverage| coverage == 255).count(), 8);
+        assert_eq!(coverage.iter().filter(|&&coverage| coverage == 0).count(), 8);
+    }
+
+    #[test]
+    fn stretched_square_covers_three_columns_per_pixel(){
+        let mut rasterizer = OutlineRasterizer::stretched(12, 4, [3.0, 1.0], 0.0, -4.0);
+        rasterizer.move_to(0.0, 0.0);
+        rasterizer.line_to(2.0, 0.0);
+        rasterizer.line_to(2.0, 4.0);
+        rasterizer.line_to(0.0, 4.0);
+        rasterizer.close();
+
+        let coverage = rasterizer.finish();
+        assert_eq!(&coverage[..12], &[255, 255, 255, 255, 255, 255, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_font_data_is_rejected(){
+        assert!(Font::from_bytes(vec![0; 16]).is_err());
+    }
+
+    //a 4 pixel font with a 2x2 block for 'x'
+    const BDF: &str = "STARTFONT 2.1
+SIZE 4 75 75
+FONTBOUNDINGBOX 2 2 0 0
+STARTPROPERTIES 2
+FONT_ASCENT 3
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 1
+STARTCHAR x
+ENCODING 120
+DWIDTH 3 0
+BBX 2 2 0 0
+BITMAP
+C0
+C0
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn bitmap_fonts_are_enlarged_by_whole_pixels(){
+        let font = Font::from_bytes(BDF.as_bytes().to_vec()).unwrap();
+        let glyph = font.glyph_id('x').unwrap();
+
+        assert!(font.data().is_none());
+        assert_eq!(font.advance(glyph, 9.0), 6.0);
+        assert_eq!(font.line_metrics(9.0).line_height, 8.0);
+
+        let rasterized = font.rasterize(glyph, 9.0).unwrap();
+        assert_eq!((rasterized.width, rasterized.height, rasterized.left, rasterized.top), (4, 4, 0, -4));
+        assert!(rasterized.coverage.iter().all(|&coverage| coverage == 255));
+
+        //three columns a pixel, with a pixel of margin
+        let subpixel = font.rasterize_subpixel(glyph, 4.0).unwrap();
+        assert_eq!((subpixel.width, subpixel.height, subpixel.left), (12, 4, -1));
+        assert_eq!(&subpixel.coverage[12..24], &[0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0]);
+        assert!(font.rasterize_color(glyph, 4.0).is_none());
+    }
+
+    #[test]
+    fn gzipped_fonts_are_unpacked(){
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, BDF.as_bytes()).unwrap();
+        let font = Font::from_bytes(encoder.finish().unwrap()).unwrap();
+
+        assert!(font.glyph_id('x').is_some());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::frontend::rendering::color;
+
+///A texture the frame is drawn into when the target isn't sRGB, and the pipeline which copies it to the target.
+///Frames are always drawn to an sRGB texture, so blending happens in linear light on every target, see color.rs.
+pub struct Frame{
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    target_format: wgpu::TextureFormat
+}
+
+impl Frame{
+    ///Creates a frame texture of `size` pixels for a target of `target_format`.
+    pub fn new(device: &wgpu::Device, size: (u32, u32), target_format: wgpu::TextureFormat) -> Frame{
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
+            label: Some("Frame Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry{
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture{
+                        sample_type: wgpu::TextureSampleType::Float{ filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                }
+            ]
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
+            label: Some("Frame Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/frame.wgsl").into())
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+            label: Some("Frame Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+            label: Some("Frame Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState{
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState{
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState{
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None
+        });
+
+        let (texture, view, bind_group) = create_texture(device, &bind_group_layout, size, target_format);
+
+        Frame{
+            texture,
+            view,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            target_format
+        }
+    }
+
+    ///Replaces the frame texture with one of `size` pixels, after the target was resized.
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)){
+        (self.texture, self.view, self.bind_group) = create_texture(device, &self.bind_group_layout, size, self.target_format);
+    }
+
+    ///Get the view the frame is drawn into
+    pub fn get_view(&self) -> &wgpu::TextureView{
+        &self.view
+    }
+
+    ///Copies the last frame drawn to `target`, which has to be the size and format the frame was created for.
+    pub fn copy_to(&self, device: &wgpu::Device, queue: &wgpu::Queue, target: &wgpu::TextureView){
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
+            label: Some("Frame Copy Encoder")
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
+                label: Some("Frame Copy Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment{
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations{
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true
+                    }
+                })],
+                depth_stencil_attachment: None
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+///Creates the sRGB texture frames for a `target_format` target are drawn into, and the bind group it's copied from.
+fn create_texture(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, size: (u32, u32), target_format: wgpu::TextureFormat) -> (wgpu::Texture, wgpu::TextureView, wgpu::BindGroup){
+    let texture = device.create_texture(&wgpu::TextureDescriptor{
+        label: Some("Frame Texture"),
+        size: wgpu::Extent3d{
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: color::drawing_format(target_format),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+        label: Some("Frame Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry{
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view)
+            }
+        ]
+    });
+
+    (texture, view, bind_group)
+}
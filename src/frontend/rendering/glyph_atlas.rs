@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+
+use crate::frontend::graphics_config::SubpixelOrder;
+use crate::frontend::rendering::font::Font;
+use crate::frontend::rendering::image::RgbaImage;
+use crate::frontend::rendering::lcd::{self, CoverageCurve};
+use crate::frontend::rendering::sdf;
+
+///Width and height of the glyph atlas texture in pixels.
+pub const ATLAS_SIZE: u32 = 1024;
+
+///Empty pixels left around each glyph so linear filtering doesn't bleed neighbours in.
+const GLYPH_PADDING: u32 = 1;
+
+///Size in pixels per em that signed distance field glyphs are rasterized at, whatever size they're drawn at.
+pub const SDF_SIZE: u32 = 48;
+
+///How far, in pixels at [SDF_SIZE], a signed distance field reaches either side of the outline.
+const SDF_SPREAD: u32 = 6;
+
+///A rectangle in the atlas, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRect{
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+///A horizontal strip of the atlas that glyphs of similar height are packed into, left to right.
+struct Shelf<K>{
+    y: u32,
+    height: u32,
+    cursor_x: u32,
+    ///Frame this shelf last had a glyph used, for least recently used eviction.
+    last_used: u64,
+    keys: Vec<K>
+}
+
+///Packs rectangles into a fixed size area using shelves.  When it fills up, the least recently used shelf
+///that is tall enough is emptied and reused, and the keys that were in it are handed back so they can be forgotten.
+pub struct ShelfAllocator<K>{
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf<K>>
+}
+
+impl<K: Clone> ShelfAllocator<K>{
+    pub fn new(width: u32, height: u32) -> ShelfAllocator<K>{
+        ShelfAllocator{
+            width,
+            height,
+            shelves: Vec::new()
+        }
+    }
+
+    ///Finds room for a `width` x `height` rectangle for `key`, used in frame `frame`.
+    ///Returns the shelf index and rectangle, and any keys evicted to make room.  Returns None if it doesn't fit
+    ///even after eviction, which happens when everything in the atlas is in use this frame.
+    pub fn allocate(&mut self, key: K, width: u32, height: u32, frame: u64) -> Option<(usize, AtlasRect, Vec<K>)>{
+        if width > self.width || height > self.height{
+            return None;
+        }
+
+        //best fit: the shortest existing shelf this fits on
+        let existing = self.shelves.iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= height && shelf.cursor_x + width <= self.width)
+            .min_by_key(|(_, shelf)| shelf.height)
+            .map(|(i, _)| i);
+
+        //don't put small glyphs on much taller shelves if a new shelf could be opened instead
+        let next_y = self.shelves.last().map(|shelf| shelf.y + shelf.height).unwrap_or(0);
+        let can_open = next_y + height <= self.height;
+        let existing = existing.filter(|&i| !can_open || self.shelves[i].height <= height + height / 2);
+
+        let mut evicted = Vec::new();
+        let index = match existing{
+            Some(i) => i,
+            None if can_open => {
+                self.shelves.push(Shelf{ y: next_y, height, cursor_x: 0, last_used: frame, keys: Vec::new() });
+                self.shelves.len() - 1
+            },
+            None => {
+                let lru = self.shelves.iter()
+                    .enumerate()
+                    .filter(|(_, shelf)| shelf.height >= height && shelf.last_used < frame)
+                    .min_by_key(|(_, shelf)| (shelf.last_used, shelf.height))
+                    .map(|(i, _)| i)?;
+
+                let shelf = &mut self.shelves[lru];
+                evicted.append(&mut shelf.keys);
+                shelf.cursor_x = 0;
+                lru
+            }
+        };
+
+        let shelf = &mut self.shelves[index];
+        let rect = AtlasRect{ x: shelf.cursor_x, y: shelf.y, width, height };
+        shelf.cursor_x += width;
+        shelf.last_used = frame;
+        shelf.keys.push(key);
+
+        Some((index, rect, evicted))
+    }
+
+    ///Marks a shelf as used in `frame` so it isn't evicted.
+    pub fn touch(&mut self, shelf: usize, frame: u64){
+        if let Some(shelf) = self.shelves.get_mut(shelf){
+            shelf.last_used = frame;
+        }
+    }
+}
+
+///What a glyph in the atlas holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlyphFormat{
+    ///Coverage, for drawing at the size it was rasterized at.
+    Bitmap,
+    ///A signed distance field at [SDF_SIZE], for drawing at any size.
+    Sdf,
+    ///Coverage for each subpixel, three texels per pixel from left to right, for drawing at the size it was
+    ///rasterized at on an LCD with this subpixel order.
+    Lcd(SubpixelOrder)
+}
+
+impl GlyphFormat{
+    ///Get how many texels across the atlas each pixel of a glyph takes.
+    pub fn texels_per_pixel(&self) -> u32{
+        match self{
+            GlyphFormat::Lcd(_) => 3,
+            _ => 1
+        }
+    }
+}
+
+///Identifies a rasterized glyph: which font in the font chain, which glyph, at what size and in what format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey{
+    pub font: usize,
+    pub glyph: u16,
+    ///Size in pixels per em, always [SDF_SIZE] for signed distance fields.
+    pub size: u32,
+    pub format: GlyphFormat
+}
+
+///Where a glyph is in the atlas and how to place it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph{
+    ///Texture coordinates of the top left and bottom right corners.
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    ///Size in pixels, subpixel glyphs are three times as wide in the atlas.
+    pub width: u32,
+    pub height: u32,
+    ///Offset from the pen position on the baseline to the top left of the bitmap, in pixels (y down).
+    pub left: i32,
+    pub top: i32,
+    ///Whether this is on the color page, drawn in its own colors like an emoji.
+    pub color: bool,
+    ///Whether parts of this color glyph are in the text's color.  How much of each pixel is in it is to the right of
+    ///the glyph, past [GLYPH_PADDING] texels of padding.
+    pub tinted: bool
+}
+
+///Which of the atlas's textures a glyph is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AtlasPage{
+    Coverage,
+    Color
+}
+
+///A cached glyph, or a glyph with nothing to draw (like a space).
+#[derive(Debug, Clone, Copy)]
+enum CachedGlyph{
+    Drawn{ glyph: AtlasGlyph, page: AtlasPage, shelf: usize },
+    Empty
+}
+
+///A single channel texture holding rasterized glyphs, packed as they're first needed.  Bitmaps and signed distance
+///fields share it, the quad's flags say which one it samples.  Color glyphs go on a second, RGBA page.
+pub struct GlyphAtlas{
+    texture: wgpu::Texture,
+    color_texture: wgpu::Texture,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    allocator: ShelfAllocator<GlyphKey>,
+    color_allocator: ShelfAllocator<GlyphKey>,
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
+    ///Applied to subpixel glyphs as they're rasterized.
+    subpixel_curve: CoverageCurve,
+    frame: u64,
+    ///Increased whenever glyphs are evicted, so meshes holding their texture coordinates know to rebuild.
+    generation: u64
+}
+
+impl GlyphAtlas{
+    ///Creates an empty atlas texture along with the bind group the quad pipeline samples it through.
+    ///`subpixel_curve` adjusts the coverage of subpixel glyphs.
+    pub fn new(device: &wgpu::Device, subpixel_curve: CoverageCurve) -> GlyphAtlas{
+        let create_texture = |label, format| device.create_texture(&wgpu::TextureDescriptor{
+            label: Some(label),
+            size: wgpu::Extent3d{
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        });
+        let texture = create_texture("Glyph Atlas", wgpu::TextureFormat::R8Unorm);
+        //not an sRGB format, the shader converts colors itself after unpremultiplying
+        let color_texture = create_texture("Glyph Atlas Color Page", wgpu::TextureFormat::Rgba8Unorm);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor{
+            label: Some("Glyph Atlas Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
+            label: Some("Glyph Atlas Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry{
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture{
+                        sample_type: wgpu::TextureSampleType::Float{ filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry{
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry{
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture{
+                        sample_type: wgpu::TextureSampleType::Float{ filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                }
+            ]
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            label: Some("Glyph Atlas Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry{
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view)
+                },
+                wgpu::BindGroupEntry{
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler)
+                },
+                wgpu::BindGroupEntry{
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&color_view)
+                }
+            ]
+        });
+
+        GlyphAtlas{
+            texture,
+            color_texture,
+            bind_group_layout,
+            bind_group,
+            allocator: ShelfAllocator::new(ATLAS_SIZE, ATLAS_SIZE),
+            color_allocator: ShelfAllocator::new(ATLAS_SIZE, ATLAS_SIZE),
+            glyphs: HashMap::new(),
+            subpixel_curve,
+            frame: 0,
+            generation: 0
+        }
+    }
+
+    ///Get the layout of the bind group the atlas is sampled through
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout{
+        &self.bind_group_layout
+    }
+
+    ///Get the bind group the atlas is sampled through
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup{
+        &self.bind_group
+    }
+
+    ///Starts a new frame. Glyphs used in the current frame are never evicted.
+    pub fn begin_frame(&mut self){
+        self.frame += 1;
+    }
+
+    ///Get the eviction generation. Texture coordinates from an older generation may point at other glyphs.
+    pub fn get_generation(&self) -> u64{
+        self.generation
+    }
+
+    ///Marks a glyph as used this frame without looking it up, for meshes which were built earlier.
+    pub fn touch_glyph(&mut self, key: GlyphKey){
+        if let Some(CachedGlyph::Drawn{ page, shelf, .. }) = self.glyphs.get(&key).copied(){
+            self.touch(page, shelf);
+        }
+    }
+
+    ///Gets a glyph from the atlas, rasterizing and uploading it first if it isn't there yet.
+    ///Returns None for glyphs with nothing to draw, or if the atlas is too full this frame.
+    pub fn get_glyph(&mut self, queue: &wgpu::Queue, font: &Font, key: GlyphKey) -> Option<AtlasGlyph>{
+        match self.glyphs.get(&key).copied(){
+            Some(CachedGlyph::Drawn{ glyph, page, shelf }) => {
+                self.touch(page, shelf);
+                return Some(glyph);
+            },
+            Some(CachedGlyph::Empty) => return None,
+            None => {}
+        }
+
+        //emoji are drawn in their own colors whatever format the text is in
+        if let Some(color) = font.rasterize_color(key.glyph, key.size as f32){
+            let image = color.image;
+            let tinted = color.foreground.is_some();
+            let (shelf, uv_min, mut uv_max) = match &color.foreground{
+                Some(foreground) => {
+                    let texels = with_foreground(&image, foreground);
+                    self.place(queue, key, AtlasPage::Color, image.width * 2 + GLYPH_PADDING, image.height, &texels)?
+                },
+                None => self.place(queue, key, AtlasPage::Color, image.width, image.height, &image.pixels)?
+            };
+            uv_max[0] = uv_min[0] + image.width as f32 / ATLAS_SIZE as f32;
+            let glyph = AtlasGlyph{
+                uv_min,
+                uv_max,
+                width: image.width,
+                height: image.height,
+                left: color.left,
+                top: color.top,
+                color: true,
+                tinted
+            };
+            self.glyphs.insert(key, CachedGlyph::Drawn{ glyph, page: AtlasPage::Color, shelf });
+            return Some(glyph);
+        }
+
+        let rasterized = match key.format{
+            GlyphFormat::Bitmap => font.rasterize(key.glyph, key.size as f32),
+            GlyphFormat::Sdf => font.rasterize(key.glyph, key.size as f32).map(|rasterized| sdf::to_sdf(&rasterized, SDF_SPREAD)),
+            GlyphFormat::Lcd(order) => font.rasterize_subpixel(key.glyph, key.size as f32)
+                .map(|rasterized| lcd::filter(&rasterized, order, &self.subpixel_curve))
+        };
+        let rasterized = match rasterized{
+            Some(rasterized) => rasterized,
+            None => {
+                self.glyphs.insert(key, CachedGlyph::Empty);
+                return None;
+            }
+        };
+
+        let (shelf, uv_min, uv_max) = self.place(queue, key, AtlasPage::Coverage, rasterized.width, rasterized.height, &rasterized.coverage)?;
+        let glyph = AtlasGlyph{
+            uv_min,
+            uv_max,
+            width: rasterized.width / key.format.texels_per_pixel(),
+            height: rasterized.height,
+            left: rasterized.left,
+            top: rasterized.top,
+            color: false,
+            tinted: false
+        };
+        self.glyphs.insert(key, CachedGlyph::Drawn{ glyph, page: AtlasPage::Coverage, shelf });
+
+        Some(glyph)
+    }
+
+    fn get_allocator(&mut self, page: AtlasPage) -> &mut ShelfAllocator<GlyphKey>{
+        match page{
+            AtlasPage::Coverage => &mut self.allocator,
+            AtlasPage::Color => &mut self.color_allocator
+        }
+    }
+
+    fn touch(&mut self, page: AtlasPage, shelf: usize){
+        let frame = self.frame;
+        self.get_allocator(page).touch(shelf, frame);
+    }
+
+    ///Finds room on `page` for a `width` x `height` texel image and uploads it, evicting other glyphs if needed.
+    ///Returns the shelf it's on and its texture coordinates.
+    fn place(&mut self, queue: &wgpu::Queue, key: GlyphKey, page: AtlasPage, width: u32, height: u32, texels: &[u8]) -> Option<(usize, [f32; 2], [f32; 2])>{
+        let frame = self.frame;
+        let (shelf, rect, evicted) = self.get_allocator(page).allocate(
+            key,
+            width + GLYPH_PADDING * 2,
+            height + GLYPH_PADDING * 2,
+            frame
+        )?;
+        if !evicted.is_empty(){
+            self.generation += 1;
+        }
+        for evicted in evicted{
+            self.glyphs.remove(&evicted);
+        }
+
+        let x = rect.x + GLYPH_PADDING;
+        let y = rect.y + GLYPH_PADDING;
+        let (texture, bytes_per_texel) = match page{
+            AtlasPage::Coverage => (&self.texture, 1),
+            AtlasPage::Color => (&self.color_texture, 4)
+        };
+
+        //clear the padding too, an evicted glyph may have been there
+        let row_bytes = (width * bytes_per_texel) as usize;
+        let padded_row_bytes = rect.width * bytes_per_texel;
+        let mut padded = vec![0u8; (padded_row_bytes * rect.height) as usize];
+        for row in 0..height{
+            let source = row as usize * row_bytes;
+            let destination = ((row + GLYPH_PADDING) * padded_row_bytes + GLYPH_PADDING * bytes_per_texel) as usize;
+            padded[destination..destination + row_bytes].copy_from_slice(&texels[source..source + row_bytes]);
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture{
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d{ x: rect.x, y: rect.y, z: 0 },
+                aspect: wgpu::TextureAspect::All
+            },
+            &padded,
+            wgpu::ImageDataLayout{
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_row_bytes),
+                rows_per_image: std::num::NonZeroU32::new(rect.height)
+            },
+            wgpu::Extent3d{
+                width: rect.width,
+                height: rect.height,
+                depth_or_array_layers: 1
+            }
+        );
+
+        Some((
+            shelf,
+            [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32],
+            [(x + width) as f32 / ATLAS_SIZE as f32, (y + height) as f32 / ATLAS_SIZE as f32]
+        ))
+    }
+}
+
+///Puts how much of each pixel of a color glyph is in the text's color to the right of its image, [GLYPH_PADDING]
+///texels apart so filtering doesn't mix them.
+fn with_foreground(image: &RgbaImage, foreground: &[u8]) -> Vec<u8>{
+    let row_bytes = (image.width * 4) as usize;
+    let mut texels = Vec::with_capacity(((image.width * 2 + GLYPH_PADDING) * image.height * 4) as usize);
+    for (row, weights) in image.pixels.chunks_exact(row_bytes).zip(foreground.chunks_exact(image.width as usize)){
+        texels.extend_from_slice(row);
+        texels.extend(std::iter::repeat_n(0, (GLYPH_PADDING * 4) as usize));
+        texels.extend(weights.iter().flat_map(|&weight| [weight; 4]));
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn foreground_is_placed_right_of_the_glyph(){
+        let image = RgbaImage{ width: 1, height: 2, pixels: vec![1, 2, 3, 4, 5, 6, 7, 8] };
+
+        let texels = with_foreground(&image, &[10, 20]);
+        assert_eq!(texels, vec![1, 2, 3, 4, 0, 0, 0, 0, 10, 10, 10, 10, 5, 6, 7, 8, 0, 0, 0, 0, 20, 20, 20, 20]);
+    }
+
+    #[test]
+    fn rectangles_do_not_overlap(){
+        let mut allocator: ShelfAllocator<u32> = ShelfAllocator::new(64, 64);
+
+        let (_, a, _) = allocator.allocate(0, 10, 10, 0).unwrap();
+        let (_, b, _) = allocator.allocate(1, 10, 10, 0).unwrap();
+
+        assert!(a.x + a.width <= b.x || b.x + b.width <= a.x || a.y + a.height <= b.y || b.y + b.height <= a.y);
+    }
+
+    #[test]
+    fn full_row_opens_new_shelf(){
+        let mut allocator: ShelfAllocator<u32> = ShelfAllocator::new(20, 64);
+
+        allocator.allocate(0, 10, 10, 0).unwrap();
+        allocator.allocate(1, 10, 10, 0).unwrap();
+        let (shelf, rect, _) = allocator.allocate(2, 10, 10, 0).unwrap();
+
+        assert_eq!(shelf, 1);
+        assert_eq!(rect.y, 10);
+    }
+
+    #[test]
+    fn full_atlas_evicts_least_recently_used_shelf(){
+        let mut allocator: ShelfAllocator<u32> = ShelfAllocator::new(10, 20);
+
+        allocator.allocate(0, 10, 10, 0).unwrap();
+        allocator.allocate(1, 10, 10, 1).unwrap();
+        let (_, rect, evicted) = allocator.allocate(2, 10, 10, 2).unwrap();
+
+        assert_eq!(evicted, vec![0]);
+        assert_eq!(rect.y, 0);
+    }
+
+    #[test]
+    fn touched_shelf_is_not_evicted(){
+        let mut allocator: ShelfAllocator<u32> = ShelfAllocator::new(10, 20);
+
+        allocator.allocate(0, 10, 10, 0).unwrap();
+        allocator.allocate(1, 10, 10, 1).unwrap();
+        allocator.touch(0, 2);
+        let (_, _, evicted) = allocator.allocate(2, 10, 10, 3).unwrap();
+
+        assert_eq!(evicted, vec![1]);
+    }
+
+    #[test]
+    fn nothing_is_evicted_if_everything_is_in_use_this_frame(){
+        let mut allocator: ShelfAllocator<u32> = ShelfAllocator::new(10, 10);
+
+        allocator.allocate(0, 10, 10, 5).unwrap();
+
+        assert!(allocator.allocate(1, 10, 10, 5).is_none());
+    }
+}
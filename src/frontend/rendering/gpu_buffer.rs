@@ -0,0 +1,83 @@
+use wgpu::util::DeviceExt;
+
+///A gpu buffer which is recreated bigger whenever more data is written to it than fits.
+pub struct GrowableBuffer{
+    buffer: wgpu::Buffer,
+    size: u64,
+    label: &'static str,
+    usage: wgpu::BufferUsages
+}
+
+impl GrowableBuffer{
+    ///Creates a buffer holding `contents`.  COPY_DST is added to `usage` so the buffer can be written to later.
+    pub fn new(device: &wgpu::Device, label: &'static str, usage: wgpu::BufferUsages, contents: &[u8]) -> GrowableBuffer{
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor{
+                label: Some(label),
+                contents,
+                usage
+            }
+        );
+
+        GrowableBuffer{
+            buffer,
+            size: contents.len() as u64,
+            label,
+            usage
+        }
+    }
+
+    ///Writes `data` to the start of the buffer, recreating it if it isn't big enough.
+    ///Returns whether the buffer had to be recreated.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> bool{
+        if data.len() as u64 > self.size{
+            log::debug!("Resizing {} to {} bytes", self.label, data.len());
+            *self = GrowableBuffer::new(device, self.label, self.usage, data);
+            true
+        } else {
+            queue.write_buffer(&self.buffer, 0, data);
+            false
+        }
+    }
+
+    ///Get the underlying gpu buffer
+    pub fn get_buffer(&self) -> &wgpu::Buffer{
+        &self.buffer
+    }
+}
+
+///Packs `indices` for upload, as 16 bit indices if every one of `vertex_count` vertices can be reached with them
+///(halving the upload for most frames), otherwise as 32 bit indices.
+pub fn pack_indices(indices: &[u32], vertex_count: usize) -> (Vec<u8>, wgpu::IndexFormat){
+    if vertex_count <= u16::MAX as usize + 1{
+        let mut narrow: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+        //buffer writes have to be a multiple of 4 bytes
+        if !narrow.len().is_multiple_of(2){
+            narrow.push(0);
+        }
+        (bytemuck::cast_slice(&narrow).to_vec(), wgpu::IndexFormat::Uint16)
+    } else {
+        (bytemuck::cast_slice(indices).to_vec(), wgpu::IndexFormat::Uint32)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn small_meshes_use_16_bit_indices(){
+        let (data, format) = pack_indices(&[0, 1, 65_535], 65_536);
+        assert_eq!(format, wgpu::IndexFormat::Uint16);
+        assert_eq!(data.len(), 8);
+        assert_eq!(&data[4..6], &[0xff, 0xff]);
+    }
+
+    #[test]
+    fn large_meshes_use_32_bit_indices(){
+        let (data, format) = pack_indices(&[0, 65_536], 65_537);
+        assert_eq!(format, wgpu::IndexFormat::Uint32);
+        assert_eq!(data, bytemuck::cast_slice::<u32, u8>(&[0, 65_536]));
+    }
+}
@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
+use std::path::Path;
+
+///An 8 bit per channel RGBA image, rows top to bottom with no padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage{
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl RgbaImage{
+    ///Creates a transparent black image.
+    pub fn new(width: u32, height: u32) -> RgbaImage{
+        RgbaImage{
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4]
+        }
+    }
+
+    ///Get the pixel at (x, y) from the top left.
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4]{
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[start], self.pixels[start + 1], self.pixels[start + 2], self.pixels[start + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]){
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels[start..start + 4].copy_from_slice(&pixel);
+    }
+
+    ///Copies the part of the image inside the rectangle at (x, y) of size `width` by `height`, clipped to the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> RgbaImage{
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+
+        let mut cropped = RgbaImage::new(width, height);
+        for row in 0..height {
+            let start = ((y + row) as usize * self.width as usize + x as usize) * 4;
+            let cropped_start = row as usize * width as usize * 4;
+            cropped.pixels[cropped_start..cropped_start + width as usize * 4].copy_from_slice(&self.pixels[start..start + width as usize * 4]);
+        }
+        cropped
+    }
+
+    ///Writes the image to `path` as a PNG.
+    pub fn save_png(&self, path: &Path) -> io::Result<()>{
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    ///Reads a PNG from `path`.
+    #[cfg(test)]
+    pub fn load_png(path: &Path) -> io::Result<RgbaImage>{
+        RgbaImage::read_png(io::BufReader::new(File::open(path)?))
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))
+    }
+
+    ///Decodes PNG data, like the images embedded in color fonts.
+    pub fn decode_png(data: &[u8]) -> io::Result<RgbaImage>{
+        RgbaImage::read_png(data)
+    }
+
+    ///Decodes a PNG of any color type, expanding palettes, grayscale and 16 bit channels to 8 bit RGBA.
+    fn read_png<R: Read>(source: R) -> io::Result<RgbaImage>{
+        let mut decoder = png::Decoder::new(source);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&gray| [gray, gray, gray, 255]).collect(),
+            color_type => return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} PNGs aren't supported", color_type)
+            ))
+        };
+
+        Ok(RgbaImage{
+            width: info.width,
+            height: info.height,
+            pixels
+        })
+    }
+
+    ///Multiplies each pixel's color by its alpha.
+    pub fn premultiply(&mut self){
+        for pixel in self.pixels.chunks_exact_mut(4){
+            let alpha = pixel[3] as u32;
+            for channel in &mut pixel[..3]{
+                *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+    }
+
+    ///Scales the image to `width` by `height`, averaging the pixels each one covers when shrinking and repeating the
+    ///nearest one when enlarging.  Only meant for premultiplied images, since the average of straight colors bleeds
+    ///transparent pixels' colors into their neighbours.
+    pub fn resized(&self, width: u32, height: u32) -> RgbaImage{
+        let mut resized = RgbaImage::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return resized;
+        }
+
+        //source rows or columns covered by destination row or column `index` of `length`
+        let span = |index: u32, length: u32, source: u32| {
+            let start = (index as u64 * source as u64 / length as u64) as u32;
+            let end = ((index + 1) as u64 * source as u64).div_ceil(length as u64) as u32;
+            start..end.max(start + 1).min(source)
+        };
+
+        for y in 0..height {
+            let rows = span(y, height, self.height);
+            for x in 0..width {
+                let columns = span(x, width, self.width);
+                let mut sum = [0u32; 4];
+                for row in rows.clone() {
+                    for column in columns.clone() {
+                        for (total, channel) in sum.iter_mut().zip(self.get_pixel(column, row)){
+                            *total += channel as u32;
+                        }
+                    }
+                }
+                let count = rows.len() as u32 * columns.len() as u32;
+                resized.set_pixel(x, y, sum.map(|total| ((total + count / 2) / count) as u8));
+            }
+        }
+        resized
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn png_round_trips(){
+        let mut image = RgbaImage::new(3, 2);
+        image.set_pixel(2, 1, [10, 20, 30, 40]);
+
+        let path = std::env::temp_dir().join(format!("digit-image-test-{}.png", std::process::id()));
+        image.save_png(&path).unwrap();
+        let loaded = RgbaImage::load_png(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.unwrap(), image);
+    }
+
+    #[test]
+    fn crop_is_clipped_to_image(){
+        let mut image = RgbaImage::new(4, 4);
+        image.set_pixel(3, 3, [1, 2, 3, 4]);
+
+        let cropped = image.crop(2, 2, 10, 10);
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.get_pixel(1, 1), [1, 2, 3, 4]);
+        assert_eq!(image.crop(5, 0, 1, 1).pixels.len(), 0);
+    }
+
+    #[test]
+    fn png_data_is_decoded(){
+        let mut image = RgbaImage::new(2, 2);
+        image.set_pixel(1, 0, [200, 100, 50, 255]);
+
+        let path = std::env::temp_dir().join(format!("digit-image-decode-test-{}.png", std::process::id()));
+        image.save_png(&path).unwrap();
+        let data = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(RgbaImage::decode_png(&data.unwrap()).unwrap(), image);
+        assert!(RgbaImage::decode_png(b"not a png").is_err());
+    }
+
+    #[test]
+    fn premultiply_scales_color_by_alpha(){
+        let mut image = RgbaImage::new(1, 1);
+        image.set_pixel(0, 0, [255, 100, 0, 128]);
+        image.premultiply();
+
+        assert_eq!(image.get_pixel(0, 0), [128, 50, 0, 128]);
+    }
+
+    #[test]
+    fn resizing_averages_when_shrinking(){
+        let mut image = RgbaImage::new(2, 2);
+        image.set_pixel(0, 0, [255, 255, 255, 255]);
+        image.set_pixel(1, 1, [255, 255, 255, 255]);
+
+        assert_eq!(image.resized(1, 1).get_pixel(0, 0), [128, 128, 128, 128]);
+
+        let enlarged = image.resized(4, 4);
+        assert_eq!(enlarged.get_pixel(1, 1), [255, 255, 255, 255]);
+        assert_eq!(enlarged.get_pixel(2, 1), [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,382 @@
+use std::ops::Range;
+
+use crate::frontend::rendering::mesh::{Vertex, QuadInstance, RectInstance, VERTICES, INDICES, QUAD_LCD};
+use crate::frontend::rendering::gpu_buffer::{GrowableBuffer, pack_indices};
+use crate::frontend::rendering::pane_cache::PaneId;
+
+///The layers a frame is drawn in, back to front.  Each layer has its own buffers and is drawn completely
+///before the next one, so anything added to a later layer ends up on top whatever order it was added in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer{
+    ///Pane and line backgrounds.
+    Background,
+    ///Selections and search highlights, over the backgrounds but under the text.
+    Selection,
+    ///The buffer's text.
+    Text,
+    ///Cursors, on top of the text they sit in.
+    Cursor,
+    ///Overlays which aren't part of a pane, like the performance HUD and notifications.
+    Popup
+}
+
+impl Layer{
+    ///Every layer, in the order they're drawn.
+    pub const ALL: [Layer; 5] = [Layer::Background, Layer::Selection, Layer::Text, Layer::Cursor, Layer::Popup];
+
+    ///Get the position of this layer in [Layer::ALL].
+    pub fn index(self) -> usize{
+        self as usize
+    }
+}
+
+///A rectangle drawing is clipped to, in logical pixels from the top left of the surface.  Clip rectangles aren't scrolled,
+///they're where a pane is on screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRect{
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+impl ClipRect{
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> ClipRect{
+        ClipRect{ x, y, width, height }
+    }
+
+    ///Converts this rectangle to a scissor rectangle (x, y, width, height) in surface pixels, rounding outwards and clipped
+    ///to a surface of `surface_size`.  Returns None if none of the rectangle is on the surface.
+    pub fn to_scissor(self, content_scale: f32, surface_size: (u32, u32)) -> Option<(u32, u32, u32, u32)>{
+        let left = (self.x * content_scale).floor().max(0.0);
+        let top = (self.y * content_scale).floor().max(0.0);
+        let right = ((self.x + self.width) * content_scale).ceil().min(surface_size.0 as f32);
+        let bottom = ((self.y + self.height) * content_scale).ceil().min(surface_size.1 as f32);
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some((left as u32, top as u32, (right - left) as u32, (bottom - top) as u32))
+    }
+}
+
+///A run of a layer's indices, rects and quads drawn with the same clip rectangle.  The run's meshes are drawn first,
+///then its rects, then its quads in order, so text goes on top of the shapes it sits in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawBatch{
+    ///Where the batch is clipped to, or None to draw anywhere on the surface.
+    pub clip: Option<ClipRect>,
+    ///A cached pane drawn from its texture, see [crate::frontend::rendering::pane_cache].  Batches drawing a pane have
+    ///no indices or quads of their own.
+    pub pane: Option<PaneId>,
+    pub indices: Range<u32>,
+    pub rects: Range<u32>,
+    pub quads: Range<u32>,
+    ///Runs of the quads which are subpixel glyphs, in order.  They're drawn with the LCD pipelines between the quads
+    ///either side of them.
+    pub lcd: Vec<Range<u32>>
+}
+
+///Vertices, indices and quads added to one layer of a frame, see [crate::frontend::rendering::render_state::RenderState].
+#[derive(Default)]
+pub struct LayerContent{
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub num_indices: u32,
+    ///Rounded rectangles, drawn in order after the meshes of their batch.
+    pub rects: Vec<RectInstance>,
+    ///Glyphs and rectangles, drawn in order after the meshes and rects of their batch.
+    pub quads: Vec<QuadInstance>,
+    pub batches: Vec<DrawBatch>
+}
+
+impl LayerContent{
+    pub fn new() -> LayerContent{
+        LayerContent::default()
+    }
+
+    ///Add vertices and indices clipped to `clip`.  `indices` count from the first of `vertices`.
+    ///Returns an error without adding anything if the mesh doesn't fit.
+    pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[u32], clip: Option<ClipRect>) -> Result<(), &'static str>{
+        //indices are for the current mesh and we probably already have some so they need to be adjusted
+        let index_offset = mesh_index_offset(self.vertices.len(), vertices.len(), indices)?;
+
+        //the batch has to be started before the indices are added, so it starts at the first of them
+        self.batch_for(clip);
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend(indices.iter().map(|&index| index + index_offset));
+        self.num_indices += indices.len() as u32;
+
+        let num_indices = self.num_indices;
+        self.batches.last_mut().unwrap().indices.end = num_indices;
+        Ok(())
+    }
+
+    ///Add rounded rectangles clipped to `clip`.
+    pub fn add_rects(&mut self, rects: impl IntoIterator<Item = RectInstance>, clip: Option<ClipRect>){
+        self.batch_for(clip);
+        self.rects.extend(rects);
+
+        let num_rects = self.rects.len() as u32;
+        self.batches.last_mut().unwrap().rects.end = num_rects;
+    }
+
+    ///Add glyphs or rectangles clipped to `clip`.
+    pub fn add_quads(&mut self, quads: impl IntoIterator<Item = QuadInstance>, clip: Option<ClipRect>){
+        self.batch_for(clip);
+        let start = self.quads.len();
+        self.quads.extend(quads);
+
+        let num_quads = self.quads.len() as u32;
+        let batch = self.batches.last_mut().unwrap();
+        batch.quads.end = num_quads;
+        for (index, quad) in (start as u32..).zip(&self.quads[start..]) {
+            if quad.flags & QUAD_LCD == 0 {
+                continue;
+            }
+            match batch.lcd.last_mut() {
+                Some(run) if run.end == index => run.end += 1,
+                _ => batch.lcd.push(index..index + 1)
+            }
+        }
+    }
+
+    ///Add the cached texture of the pane `id`, clipped to `clip`.  Its content is drawn in its own batch.
+    pub fn add_pane(&mut self, id: PaneId, clip: Option<ClipRect>){
+        self.push_batch(clip, Some(id));
+    }
+
+    ///Makes sure the last batch is clipped to `clip`, starting a new one at the end of the layer if the clip rectangle
+    ///changed or the last batch draws a pane.
+    fn batch_for(&mut self, clip: Option<ClipRect>){
+        if self.batches.last().is_none_or(|batch| batch.clip != clip || batch.pane.is_some()) {
+            self.push_batch(clip, None);
+        }
+    }
+
+    ///Starts an empty batch at the end of the layer.
+    fn push_batch(&mut self, clip: Option<ClipRect>, pane: Option<PaneId>){
+        let num_indices = self.num_indices;
+        let num_rects = self.rects.len() as u32;
+        let num_quads = self.quads.len() as u32;
+        self.batches.push(DrawBatch{
+            clip,
+            pane,
+            indices: num_indices..num_indices,
+            rects: num_rects..num_rects,
+            quads: num_quads..num_quads,
+            lcd: Vec::new()
+        });
+    }
+
+    ///Returns true if nothing has been added to this layer.
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool{
+        self.batches.is_empty()
+    }
+
+    ///Get the size in bytes of the largest buffer this layer needs on the gpu.
+    pub fn largest_buffer_size(&self) -> usize{
+        let vertex_size = self.vertices.len() * std::mem::size_of::<Vertex>();
+        let index_size = self.indices.len() * std::mem::size_of::<u32>();
+        let rect_size = self.rects.len() * std::mem::size_of::<RectInstance>();
+        let quad_size = self.quads.len() * std::mem::size_of::<QuadInstance>();
+        vertex_size.max(index_size).max(rect_size).max(quad_size)
+    }
+
+    ///Remove everything from this layer.
+    pub fn clear(&mut self){
+        self.vertices.clear();
+        self.indices.clear();
+        self.num_indices = 0;
+        self.rects.clear();
+        self.quads.clear();
+        self.batches.clear();
+    }
+
+    ///Pads the index buffer so that it is a multiple of 4, but does NOT increase the num_indices count.
+    ///May cause unexpected behaviour if called multiple times before clearing.
+    pub fn pad_index_buffer(&mut self){
+        while !self.indices.len().is_multiple_of(4){
+            self.indices.push(0);
+        }
+    }
+}
+
+///Checks a mesh can be added after `existing` vertices, returning the offset for its indices.
+fn mesh_index_offset(existing: usize, added: usize, indices: &[u32]) -> Result<u32, &'static str>{
+    if indices.iter().any(|&index| index as usize >= added){
+        return Err("Mesh has an index past the end of its vertices!");
+    }
+
+    //every vertex has to be reachable with a u32 index
+    match existing.checked_add(added){
+        Some(total) if total <= u32::MAX as usize + 1 => Ok(existing as u32),
+        _ => Err("Too many vertices for a 32 bit index buffer!")
+    }
+}
+
+///The gpu buffers holding one layer of the last uploaded frame, and the batches to draw them in.
+pub struct LayerBuffers{
+    vertex_buffer: GrowableBuffer,
+    index_buffer: GrowableBuffer,
+    index_format: wgpu::IndexFormat,
+    rect_buffer: GrowableBuffer,
+    quad_buffer: GrowableBuffer,
+    batches: Vec<DrawBatch>
+}
+
+impl LayerBuffers{
+    ///Creates buffers for a layer with nothing to draw.
+    pub fn new(device: &wgpu::Device) -> LayerBuffers{
+        LayerBuffers{
+            vertex_buffer: GrowableBuffer::new(device, "Vertex Buffer", wgpu::BufferUsages::VERTEX, bytemuck::cast_slice(VERTICES)),
+            index_buffer: GrowableBuffer::new(device, "Index Buffer", wgpu::BufferUsages::INDEX, bytemuck::cast_slice(INDICES)),
+            index_format: wgpu::IndexFormat::Uint32,
+            //big enough for one rect and one quad to start with
+            rect_buffer: GrowableBuffer::new(device, "Rect Instance Buffer", wgpu::BufferUsages::VERTEX, &[0; std::mem::size_of::<RectInstance>()]),
+            quad_buffer: GrowableBuffer::new(device, "Quad Instance Buffer", wgpu::BufferUsages::VERTEX, &[0; std::mem::size_of::<QuadInstance>()]),
+            batches: Vec::new()
+        }
+    }
+
+    ///Uploads `content`, recreating buffers which aren't big enough.  Returns how many buffers were recreated.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, content: &LayerContent) -> u32{
+        let (index_data, index_format) = pack_indices(&content.indices, content.vertices.len());
+
+        let reallocations = [
+            self.vertex_buffer.write(device, queue, bytemuck::cast_slice(&content.vertices)),
+            self.index_buffer.write(device, queue, &index_data),
+            self.rect_buffer.write(device, queue, bytemuck::cast_slice(&content.rects)),
+            self.quad_buffer.write(device, queue, bytemuck::cast_slice(&content.quads))
+        ];
+
+        self.index_format = index_format;
+        self.batches.clone_from(&content.batches);
+        reallocations.iter().filter(|&&reallocated| reallocated).count() as u32
+    }
+
+    pub fn get_vertex_buffer(&self) -> &wgpu::Buffer{
+        self.vertex_buffer.get_buffer()
+    }
+
+    pub fn get_index_buffer(&self) -> &wgpu::Buffer{
+        self.index_buffer.get_buffer()
+    }
+
+    ///Get whether the index buffer holds 16 or 32 bit indices
+    pub fn get_index_format(&self) -> wgpu::IndexFormat{
+        self.index_format
+    }
+
+    pub fn get_rect_buffer(&self) -> &wgpu::Buffer{
+        self.rect_buffer.get_buffer()
+    }
+
+    pub fn get_quad_buffer(&self) -> &wgpu::Buffer{
+        self.quad_buffer.get_buffer()
+    }
+
+    ///Get the batches of the last uploaded frame, in the order they're drawn.
+    pub fn get_batches(&self) -> &[DrawBatch]{
+        &self.batches
+    }
+}
+
+///The pipelines every layer is drawn with.  Layers are shared pipelines over separate buffers rather than a pipeline
+///each: every layer draws the same primitives (meshes, rounded rectangles and quads), so a pipeline per layer would
+///be identical copies, and switching between them per layer would only add state changes.  What sets layers apart is
+///their buffers and that each is drawn completely before the next.  New visual elements are added to a layer through
+///[RenderState](super::render_state::RenderState) without touching render.rs; only a new kind of primitive needs a
+///pipeline here and a step in the draw loop.
+pub struct Pipelines{
+    pub mesh: wgpu::RenderPipeline,
+    pub rect: wgpu::RenderPipeline,
+    pub quad: wgpu::RenderPipeline,
+    ///Darkens what's under each subpixel of subpixel glyphs by that subpixel's coverage.
+    pub lcd_mask: wgpu::RenderPipeline,
+    ///Adds the color of subpixel glyphs, by each subpixel's coverage.
+    pub lcd_color: wgpu::RenderPipeline,
+    ///Draws subpixel glyphs with grayscale coverage, for targets with nothing opaque under the text to blend with.
+    pub lcd_grayscale: wgpu::RenderPipeline
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn layers_are_drawn_in_order(){
+        for (index, layer) in Layer::ALL.iter().enumerate() {
+            assert_eq!(layer.index(), index);
+        }
+        assert!(Layer::Background < Layer::Text && Layer::Text < Layer::Popup);
+    }
+
+    #[test]
+    fn changing_clip_starts_a_batch(){
+        let pane = Some(ClipRect::new(0.0, 0.0, 10.0, 10.0));
+        let quad = QuadInstance::solid([0.0, 0.0], [1.0, 1.0], [1.0, 1.0, 1.0, 1.0]);
+        let mut content = LayerContent::new();
+
+        content.add_quads([quad, quad], pane);
+        content.add_quads([quad], pane);
+        content.add_quads([quad], None);
+
+        assert_eq!(content.batches.len(), 2);
+        assert_eq!(content.batches[0].quads, 0..3);
+        assert_eq!(content.batches[1], DrawBatch{ clip: None, pane: None, indices: 0..0, rects: 0..0, quads: 3..4, lcd: Vec::new() });
+    }
+
+    #[test]
+    fn batches_with_subpixel_glyphs_are_marked(){
+        let solid = QuadInstance::solid([0.0, 0.0], [1.0, 1.0], [1.0, 1.0, 1.0, 1.0]);
+        let glyph = QuadInstance::lcd_glyph([0.0, 0.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 1.0, 1.0, 1.0]);
+        let mut content = LayerContent::new();
+
+        content.add_quads([solid], None);
+        assert!(content.batches[0].lcd.is_empty());
+        content.add_quads([glyph, glyph], None);
+        content.add_quads([glyph, solid, glyph], None);
+        assert_eq!(content.batches[0].lcd, vec![1..4, 5..6]);
+    }
+
+    #[test]
+    fn panes_get_their_own_batch(){
+        let quad = QuadInstance::solid([0.0, 0.0], [1.0, 1.0], [1.0, 1.0, 1.0, 1.0]);
+        let mut content = LayerContent::new();
+
+        content.add_quads([quad], None);
+        content.add_pane(7, None);
+        content.add_quads([quad], None);
+
+        let panes: Vec<_> = content.batches.iter().map(|batch| batch.pane).collect();
+        assert_eq!(panes, vec![None, Some(7), None]);
+        assert_eq!(content.batches[1].quads, 1..1);
+        assert!(!content.is_empty());
+    }
+
+    #[test]
+    fn bad_mesh_is_not_added(){
+        let vertex = Vertex { position: [0.0, 0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0]};
+        let mut content = LayerContent::new();
+
+        assert!(content.add_mesh(&[vertex], &[0, 1], None).is_err());
+        assert!(content.is_empty());
+        assert!(content.batches.is_empty());
+    }
+
+    #[test]
+    fn vertex_count_past_u32_is_an_error(){
+        assert_eq!(mesh_index_offset(u32::MAX as usize, 1, &[0]), Ok(u32::MAX));
+        assert!(mesh_index_offset(u32::MAX as usize, 2, &[0]).is_err());
+    }
+
+    #[test]
+    fn scissor_is_clipped_to_surface(){
+        assert_eq!(ClipRect::new(10.25, 5.0, 10.5, 10.0).to_scissor(2.0, (100, 100)), Some((20, 10, 22, 20)));
+        assert_eq!(ClipRect::new(-10.0, 90.0, 50.0, 50.0).to_scissor(1.0, (100, 100)), Some((0, 90, 40, 10)));
+        assert_eq!(ClipRect::new(200.0, 0.0, 10.0, 10.0).to_scissor(1.0, (100, 100)), None);
+        assert_eq!(ClipRect::new(0.0, 0.0, 0.0, 10.0).to_scissor(1.0, (100, 100)), None);
+    }
+}
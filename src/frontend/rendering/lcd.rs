@@ -0,0 +1,131 @@
+use crate::frontend::graphics_config::SubpixelOrder;
+use crate::frontend::rendering::font::RasterizedGlyph;
+
+///Weights, out of 256, that spread each column's coverage over its neighbours.  Lighting a subpixel on its own
+///leaves a coloured fringe, so like FreeType's default LCD filter some of it goes to two subpixels either side.
+const FILTER: [u32; 5] = [8, 77, 86, 77, 8];
+
+///Blending for the LCD mask pipeline: each channel of what's already drawn is darkened by the coverage of its
+///subpixel, which the shader outputs as its color.
+pub const MASK_BLEND: wgpu::BlendState = wgpu::BlendState{
+    color: wgpu::BlendComponent{
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add
+    },
+    alpha: wgpu::BlendComponent{
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add
+    }
+};
+
+///Blending for the LCD color pipeline, drawn after the mask: the glyph's color times each subpixel's coverage is added.
+pub const COLOR_BLEND: wgpu::BlendState = wgpu::BlendState{
+    color: wgpu::BlendComponent{
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add
+    },
+    alpha: wgpu::BlendComponent{
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add
+    }
+};
+
+///Adjusts the coverage of subpixel glyphs before they go in the atlas, with the gamma and contrast from the config.
+#[derive(Debug, Clone)]
+pub struct CoverageCurve{
+    table: [u8; 256]
+}
+
+impl CoverageCurve{
+    ///Raises coverage to 1 / `gamma`, then adds `contrast` (0 to 1) of coverage times its complement, which steepens
+    ///partly covered edges without moving fully covered or empty subpixels.
+    pub fn new(gamma: f32, contrast: f32) -> CoverageCurve{
+        let mut table = [0; 256];
+        for (value, entry) in table.iter_mut().enumerate(){
+            let coverage = (value as f32 / 255.0).powf(1.0 / gamma);
+            let coverage = coverage + contrast * coverage * (1.0 - coverage);
+            *entry = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        CoverageCurve{ table }
+    }
+
+    pub fn apply(&self, coverage: u8) -> u8{
+        self.table[coverage as usize]
+    }
+}
+
+impl Default for CoverageCurve{
+    fn default() -> Self{
+        CoverageCurve::new(1.0, 0.0)
+    }
+}
+
+///Filters a glyph from [crate::frontend::rendering::font::Font::rasterize_subpixel] and puts each pixel's three
+///columns in `order`, so the first column of every pixel is the subpixel on its left.
+pub fn filter(glyph: &RasterizedGlyph, order: SubpixelOrder, curve: &CoverageCurve) -> RasterizedGlyph{
+    let width = glyph.width as usize;
+    let mut coverage = vec![0; glyph.coverage.len()];
+
+    for (source, destination) in glyph.coverage.chunks(width).zip(coverage.chunks_mut(width)){
+        for (column, value) in destination.iter_mut().enumerate(){
+            let sum: u32 = FILTER.iter()
+                .enumerate()
+                .filter_map(|(tap, weight)| {
+                    let column = (column + tap).checked_sub(2)?;
+                    source.get(column).map(|&value| value as u32 * weight)
+                })
+                .sum();
+            *value = curve.apply((sum / 256) as u8);
+        }
+
+        //the rasterizer's columns go left to right, which is red to blue on an RGB panel
+        if order == SubpixelOrder::Bgr {
+            for pixel in destination.chunks_mut(3){
+                pixel.reverse();
+            }
+        }
+    }
+
+    RasterizedGlyph{
+        coverage,
+        ..*glyph
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn column(width: u32, lit: usize) -> RasterizedGlyph{
+        let mut coverage = vec![0; width as usize];
+        coverage[lit] = 255;
+        RasterizedGlyph{ width, height: 1, left: 0, top: 0, coverage }
+    }
+
+    #[test]
+    fn filter_spreads_a_column_over_its_neighbours(){
+        let filtered = filter(&column(9, 4), SubpixelOrder::Rgb, &CoverageCurve::default());
+
+        assert_eq!(filtered.coverage, vec![0, 0, 7, 76, 85, 76, 7, 0, 0]);
+    }
+
+    #[test]
+    fn bgr_swaps_the_outer_subpixels(){
+        let filtered = filter(&column(6, 0), SubpixelOrder::Bgr, &CoverageCurve::default());
+
+        assert_eq!(filtered.coverage, vec![7, 76, 85, 0, 0, 0]);
+    }
+
+    #[test]
+    fn curve_keeps_the_ends(){
+        let curve = CoverageCurve::new(1.8, 0.5);
+
+        assert_eq!(curve.apply(0), 0);
+        assert_eq!(curve.apply(255), 255);
+        assert!(curve.apply(128) > 128);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use crate::frontend::rendering::render_state::RenderState;
+use crate::frontend::rendering::shaping::{FontChain, Shaper};
+use crate::frontend::rendering::text::{self, TextMesh};
+use crate::frontend::wgpu_state::WGPUState;
+use crate::intermediary::message_queue::BufferLine;
+
+///How many frames a line's mesh is kept after it was last drawn, so scrolling back doesn't rebuild it.
+const LINE_CACHE_FRAMES: u64 = 120;
+
+struct CachedLineMesh{
+    revision: u64,
+    text: String,
+    size: f32,
+    color: [f32; 4],
+    ///Atlas generation the mesh's texture coordinates are from.
+    atlas_generation: u64,
+    content_scale: f32,
+    mesh: TextMesh,
+    last_used: u64
+}
+
+///Text meshes for the lines of a buffer, keyed by line number and rebuilt only when the line's revision changes.
+pub struct LineMeshCache{
+    lines: HashMap<usize, CachedLineMesh>,
+    frame: u64
+}
+
+impl LineMeshCache{
+    pub fn new() -> LineMeshCache{
+        LineMeshCache{
+            lines: HashMap::new(),
+            frame: 0
+        }
+    }
+
+    ///Starts a new frame, dropping meshes for lines which haven't been drawn for [LINE_CACHE_FRAMES] frames.
+    pub fn begin_frame(&mut self){
+        self.frame += 1;
+        let frame = self.frame;
+        self.lines.retain(|_, cached| cached.last_used + LINE_CACHE_FRAMES >= frame);
+    }
+
+    ///Drops every mesh, for when a different buffer is shown.
+    pub fn clear(&mut self){
+        self.lines.clear();
+    }
+
+    ///Adds line `number` to `render_state` with its pen at `origin`, reusing the mesh from an earlier frame if the line hasn't changed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_line(&mut self, render_state: &mut RenderState, wgpu_state: &mut WGPUState, shaper: &mut Shaper, fonts: &FontChain, number: usize, line: &BufferLine, origin: [f32; 2], size: f32, color: [f32; 4]){
+        let atlas_generation = wgpu_state.get_glyph_atlas().get_generation();
+        let content_scale = wgpu_state.get_view().get_content_scale();
+
+        let reusable = self.lines.get(&number).is_some_and(|cached| {
+            cached.revision == line.revision
+                && cached.atlas_generation == atlas_generation
+                && cached.content_scale == content_scale
+                && cached.size == size
+                && cached.color == color
+                //a line can move without changing, so check it's still the same text
+                && cached.text == line.text
+        });
+
+        if reusable{
+            let cached = self.lines.get_mut(&number).expect("Checked above!");
+            for key in &cached.mesh.glyphs{
+                wgpu_state.get_glyph_atlas_mut().touch_glyph(*key);
+            }
+            cached.last_used = self.frame;
+        }else{
+            let mesh = text::build_text_mesh(wgpu_state, shaper, fonts, &line.text, size, color);
+            self.lines.insert(number, CachedLineMesh{
+                revision: line.revision,
+                text: line.text.clone(),
+                size,
+                color,
+                //building may have evicted glyphs, the mesh is from whatever generation the atlas is now at
+                atlas_generation: wgpu_state.get_glyph_atlas().get_generation(),
+                content_scale,
+                mesh,
+                last_used: self.frame
+            });
+        }
+
+        let origin = [text::snap_to_pixel(origin[0], content_scale), text::snap_to_pixel(origin[1], content_scale)];
+        self.lines[&number].mesh.add_to(render_state, origin);
+    }
+}
+
+impl Default for LineMeshCache{
+    fn default() -> Self{
+        LineMeshCache::new()
+    }
+}
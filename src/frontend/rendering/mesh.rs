@@ -4,16 +4,17 @@
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
-    pub color: [f32; 3]
+    ///RGBA in sRGB, not premultiplied. The shaders convert it for the surface and premultiply it.
+    pub color: [f32; 4]
 }
 
 pub const VERTICES: &[Vertex] = &[
-    Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-    Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-    Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+    Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+    Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+    Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
 ];
 
-pub const INDICES: &[u16] = &[
+pub const INDICES: &[u32] = &[
     0, 1, 2,
 ];
 
@@ -32,7 +33,7 @@ impl Vertex{
                 wgpu::VertexAttribute{
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3
+                    format: wgpu::VertexFormat::Float32x4
                 }
             ]
         }
@@ -41,10 +42,12 @@ impl Vertex{
     ///Check if two vertices are bitwise equal to eachother.
     pub fn bitwise_equal(a: Vertex, b: Vertex) -> bool{
         for i in 0..3 {
-            if a.color[i] != b.color[i]{
+            if a.position[i] != b.position[i]{
                 return false
             }
-            if a.position[i] != b.position[i]{
+        };
+        for i in 0..4 {
+            if a.color[i] != b.color[i]{
                 return false
             }
         };
@@ -52,23 +55,308 @@ impl Vertex{
     }
 }
 
+///[QuadInstance::flags] for a quad filled with its color.
+pub const QUAD_SOLID: u32 = 0;
+///[QuadInstance::flags] for a glyph: the color is masked by the coverage in the glyph atlas at `uv_rect`.
+pub const QUAD_GLYPH: u32 = 1;
+///[QuadInstance::flags] for a cached pane: the texture at `uv_rect` is drawn as is, it's already premultiplied and
+///encoded for the target.
+pub const QUAD_TEXTURE: u32 = 2;
+///[QuadInstance::flags] for a glyph drawn from a signed distance field in the glyph atlas at `uv_rect`, so it stays
+///sharp at any size.
+pub const QUAD_SDF: u32 = 4;
+///[QuadInstance::flags] for a subpixel glyph, three texels of coverage per pixel in the glyph atlas at `uv_rect`.
+///The quad pipeline skips these, they're blended a channel at a time by the LCD pipelines.
+pub const QUAD_LCD: u32 = 8;
+///[QuadInstance::flags] for a color glyph like an emoji, drawn in its own colors from the glyph atlas's color page at
+///`uv_rect`.  Only the alpha of the quad's color is used, unless it's [QUAD_TINTED] too.
+pub const QUAD_COLOR: u32 = 16;
+///[QuadInstance::flags] for a color glyph with parts in the text's color, which are drawn in the quad's color.  How
+///much of each pixel they cover is on the color page to the right of `uv_rect`.
+pub const QUAD_TINTED: u32 = 32;
+
+///A rectangle drawn by the instanced quad pipeline.  The vertex shader expands a unit quad to `position` and `size`,
+///so each glyph or rectangle costs 32 bytes of upload instead of four vertices and six indices.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuadInstance {
+    ///Top left corner, in logical pixels.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    ///Top left and bottom right of the area of the glyph atlas to sample, as fractions of 65535.
+    pub uv_rect: [u16; 4],
+    ///RGBA in sRGB, 0 to 255, not premultiplied.
+    pub color: [u8; 4],
+    pub flags: u32
+}
+
+impl QuadInstance{
+    ///A rectangle of solid `color`, from `min` to `max` (top left to bottom right).
+    pub fn solid(min: [f32; 2], max: [f32; 2], color: [f32; 4]) -> QuadInstance{
+        QuadInstance{
+            position: min,
+            size: [max[0] - min[0], max[1] - min[1]],
+            uv_rect: [0; 4],
+            color: pack_color(color),
+            flags: QUAD_SOLID
+        }
+    }
+
+    ///A glyph taking its shape from the atlas between `uv_min` and `uv_max`.
+    pub fn glyph(position: [f32; 2], size: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) -> QuadInstance{
+        let uv = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+        QuadInstance{
+            position,
+            size,
+            uv_rect: [uv(uv_min[0]), uv(uv_min[1]), uv(uv_max[0]), uv(uv_max[1])],
+            color: pack_color(color),
+            flags: QUAD_GLYPH
+        }
+    }
+
+    ///A glyph taking its shape from a signed distance field in the atlas between `uv_min` and `uv_max`.
+    pub fn sdf_glyph(position: [f32; 2], size: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) -> QuadInstance{
+        QuadInstance{
+            flags: QUAD_SDF,
+            ..QuadInstance::glyph(position, size, uv_min, uv_max, color)
+        }
+    }
+
+    ///A glyph taking the coverage of each of its subpixels from the atlas between `uv_min` and `uv_max`.
+    pub fn lcd_glyph(position: [f32; 2], size: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) -> QuadInstance{
+        QuadInstance{
+            flags: QUAD_LCD,
+            ..QuadInstance::glyph(position, size, uv_min, uv_max, color)
+        }
+    }
+
+    ///A glyph drawn in its own colors from the atlas's color page between `uv_min` and `uv_max`, faded by `alpha`.
+    pub fn color_glyph(position: [f32; 2], size: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], alpha: f32) -> QuadInstance{
+        QuadInstance{
+            flags: QUAD_COLOR,
+            ..QuadInstance::glyph(position, size, uv_min, uv_max, [1.0, 1.0, 1.0, alpha])
+        }
+    }
+
+    ///A color glyph like [QuadInstance::color_glyph] whose parts in the text's color are drawn in `color`.
+    pub fn tinted_color_glyph(position: [f32; 2], size: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]) -> QuadInstance{
+        QuadInstance{
+            flags: QUAD_COLOR | QUAD_TINTED,
+            ..QuadInstance::glyph(position, size, uv_min, uv_max, color)
+        }
+    }
+
+    ///The whole of a texture bound in place of the glyph atlas, drawn at `position` and `size`.
+    pub fn texture(position: [f32; 2], size: [f32; 2]) -> QuadInstance{
+        QuadInstance{
+            position,
+            size,
+            uv_rect: [0, 0, u16::MAX, u16::MAX],
+            color: pack_color([1.0, 1.0, 1.0, 1.0]),
+            flags: QUAD_TEXTURE
+        }
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout{
+            array_stride: std::mem::size_of::<QuadInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 16,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Unorm16x4
+                },
+                wgpu::VertexAttribute{
+                    offset: 24,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Unorm8x4
+                },
+                wgpu::VertexAttribute{
+                    offset: 28,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Uint32
+                }
+            ]
+        }
+    }
+}
+
+///A line drawn just inside the edge of a [RectInstance].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Border{
+    ///In logical pixels.
+    pub width: f32,
+    ///RGBA in sRGB, not premultiplied.
+    pub color: [f32; 4]
+}
+
+///A soft shadow drawn under a [RectInstance], the shape of the rectangle moved by `offset` and blurred.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shadow{
+    ///In logical pixels.
+    pub offset: [f32; 2],
+    ///How far the shadow fades out past its edge, in logical pixels.
+    pub blur: f32,
+    ///RGBA in sRGB, not premultiplied.
+    pub color: [f32; 4]
+}
+
+///A rounded rectangle drawn by the rect pipeline, which works out its shape, border and shadow from a signed distance
+///function per pixel, so corners and edges are antialiased at any size.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RectInstance {
+    ///Top left corner, in logical pixels.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    ///Radius of the corners in logical pixels, at most half the shortest side.
+    pub radius: f32,
+    pub border_width: f32,
+    pub shadow_offset: [f32; 2],
+    pub shadow_blur: f32,
+    ///RGBA in sRGB, 0 to 255, not premultiplied.
+    pub fill: [u8; 4],
+    pub border_color: [u8; 4],
+    pub shadow_color: [u8; 4]
+}
+
+impl RectInstance{
+    ///A rectangle from `min` to `max` (top left to bottom right) filled with `fill`, with rounded corners of `radius`
+    ///and an optional border and shadow.
+    pub fn new(min: [f32; 2], max: [f32; 2], radius: f32, fill: [f32; 4], border: Option<Border>, shadow: Option<Shadow>) -> RectInstance{
+        let size = [(max[0] - min[0]).max(0.0), (max[1] - min[1]).max(0.0)];
+        let border = border.unwrap_or(Border{ width: 0.0, color: [0.0; 4] });
+        let shadow = shadow.unwrap_or(Shadow{ offset: [0.0, 0.0], blur: 0.0, color: [0.0; 4] });
+
+        RectInstance{
+            position: min,
+            size,
+            radius: radius.clamp(0.0, size[0].min(size[1]) / 2.0),
+            border_width: border.width.max(0.0),
+            shadow_offset: shadow.offset,
+            shadow_blur: shadow.blur.max(0.0),
+            fill: pack_color(fill),
+            border_color: pack_color(border.color),
+            shadow_color: pack_color(shadow.color)
+        }
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout{
+            array_stride: std::mem::size_of::<RectInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 16,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 24,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute{
+                    offset: 32,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32
+                },
+                wgpu::VertexAttribute{
+                    offset: 36,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Unorm8x4
+                },
+                wgpu::VertexAttribute{
+                    offset: 40,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Unorm8x4
+                },
+                wgpu::VertexAttribute{
+                    offset: 44,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Unorm8x4
+                }
+            ]
+        }
+    }
+}
+
+///Converts a 0.0 to 1.0 RGBA color to 8 bit RGBA.
+fn pack_color(color: [f32; 4]) -> [u8; 4]{
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color[0]), channel(color[1]), channel(color[2]), channel(color[3])]
+}
+
 #[cfg(test)]
 mod tests{
     use super::*;
 
     #[test]
     fn bitwise_equal(){
-        let a: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5]};
-        let b: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5]};
+        let a: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5, 1.0]};
+        let b: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5, 1.0]};
 
         assert!(Vertex::bitwise_equal(a, b));
     }
 
     #[test]
     fn bitwise_not_equal(){
-        let a: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5]};
-        let b: Vertex = Vertex { position: [0.0, -0.5, 0.0], color: [0.0, 1.0, 0.5]};
+        let a: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5, 1.0]};
+        let b: Vertex = Vertex { position: [0.0, -0.5, 0.0], color: [0.0, 1.0, 0.5, 1.0]};
 
         assert!(!Vertex::bitwise_equal(a, b));
     }
+
+    #[test]
+    fn bitwise_not_equal_alpha(){
+        let a: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5, 1.0]};
+        let b: Vertex = Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.5, 0.5]};
+
+        assert!(!Vertex::bitwise_equal(a, b));
+    }
+
+    #[test]
+    fn quad_instance_is_32_bytes(){
+        assert_eq!(std::mem::size_of::<QuadInstance>(), 32);
+    }
+
+    #[test]
+    fn rect_instance_is_48_bytes(){
+        assert_eq!(std::mem::size_of::<RectInstance>(), 48);
+    }
+
+    #[test]
+    fn rect_radius_fits_the_rect(){
+        let rect = RectInstance::new([0.0, 0.0], [20.0, 10.0], 8.0, [1.0; 4], None, None);
+        assert_eq!(rect.radius, 5.0);
+        assert_eq!(rect.shadow_color, [0; 4]);
+    }
+
+    #[test]
+    fn glyph_uvs_are_normalized(){
+        let quad = QuadInstance::glyph([0.0, 0.0], [1.0, 1.0], [0.0, 0.5], [1.0, 1.0], [1.0, 0.0, 0.0, 0.5]);
+        assert_eq!(quad.uv_rect, [0, 32768, 65535, 65535]);
+        assert_eq!(quad.color, [255, 0, 0, 128]);
+    }
 }
\ No newline at end of file
@@ -1,3 +1,22 @@
 pub mod render;
 pub mod render_state;
-pub mod mesh;
\ No newline at end of file
+pub mod mesh;
+pub mod gpu_buffer;
+pub mod font;
+pub mod glyph_atlas;
+pub mod text;
+pub mod shaping;
+pub mod view;
+pub mod line_cache;
+pub mod image;
+pub mod readback;
+#[cfg(test)]
+pub mod snapshot;
+pub mod color;
+pub mod frame;
+pub mod layer;
+pub mod pane_cache;
+pub mod sdf;
+pub mod lcd;
+pub mod bitmap_font;
+pub mod color_font;
\ No newline at end of file
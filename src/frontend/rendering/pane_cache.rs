@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::frontend::rendering::layer::ClipRect;
+use crate::frontend::rendering::mesh::QuadInstance;
+use crate::frontend::rendering::view::View;
+
+///Identifies a pane across frames.
+pub type PaneId = u32;
+
+///A part of the window whose content is drawn into its own texture and reused while it doesn't change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pane{
+    pub id: PaneId,
+    ///Where the pane is on screen, its content is clipped to this.
+    pub rect: ClipRect,
+    ///Changes whenever anything drawn in the pane changes, like the viewport's revision for an editor pane.
+    pub revision: u64,
+    ///The sRGB colour the pane is filled with before its content is drawn, or None to leave it transparent so whatever
+    ///is under it in the window shows through.  Subpixel text needs an opaque background to blend each channel with,
+    ///so it's drawn in grayscale in transparent panes.
+    pub background: Option<[f32; 3]>
+}
+
+///A pane's texture, drawn the last time the pane changed.
+pub struct CachedPane{
+    target: wgpu::TextureView,
+    ///Samples the texture, with the same layout as the glyph atlas so the quad pipeline can draw it.
+    bind_group: wgpu::BindGroup,
+    ///Maps the pane's part of the window onto the texture.
+    view: View,
+    ///The single quad the texture is drawn to the window with.
+    quad_buffer: wgpu::Buffer,
+    size: (u32, u32),
+    rect: ClipRect,
+    revision: u64,
+    used: bool
+}
+
+impl CachedPane{
+    ///Get the view to render the pane's content into.
+    pub fn get_target(&self) -> &wgpu::TextureView{
+        &self.target
+    }
+
+    ///Get the view uniform to render the pane's content with.
+    pub fn get_view(&self) -> &View{
+        &self.view
+    }
+
+    ///Get the bind group the quad pipeline draws the texture through, in place of the glyph atlas.
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup{
+        &self.bind_group
+    }
+
+    ///Get the buffer holding the quad instance covering the pane on screen.
+    pub fn get_quad_buffer(&self) -> &wgpu::Buffer{
+        &self.quad_buffer
+    }
+}
+
+///Textures holding the content of panes which haven't changed, so they're drawn as one quad instead of being rebuilt.
+///Textures are in surface pixels, so everything is dropped when the surface is resized or the content scale changes.
+pub struct PaneCache{
+    panes: HashMap<PaneId, CachedPane>,
+    sampler: wgpu::Sampler
+}
+
+impl PaneCache{
+    pub fn new(device: &wgpu::Device) -> PaneCache{
+        //textures are drawn at the size they were rendered at, so there's nothing to filter
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor{
+            label: Some("Pane Sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        PaneCache{
+            panes: HashMap::new(),
+            sampler
+        }
+    }
+
+    ///Drops the textures of panes which weren't drawn last frame.  Call once at the start of each frame.
+    pub fn begin_frame(&mut self){
+        self.panes.retain(|_, cached| cached.used);
+        for cached in self.panes.values_mut() {
+            cached.used = false;
+        }
+    }
+
+    ///Returns true if `pane`'s texture is up to date, so its content doesn't have to be built this frame.
+    ///Either way the pane is kept for the next frame.
+    pub fn is_current(&mut self, pane: &Pane) -> bool{
+        match self.panes.get_mut(&pane.id) {
+            Some(cached) => {
+                cached.used = true;
+                is_current(cached.rect, cached.revision, pane)
+            },
+            None => false
+        }
+    }
+
+    ///Makes sure `pane` has a texture covering its part of a `surface_size` surface, ready for its content to be drawn into
+    ///with the view uniform from [CachedPane::get_view].  `main_view` is the window's view, which the pane's is based on.
+    ///Returns None if the pane isn't on the surface.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, pane: &Pane, texture_layout: &wgpu::BindGroupLayout, main_view: &View, format: wgpu::TextureFormat, surface_size: (u32, u32)) -> Option<&CachedPane>{
+        let scale = main_view.get_content_scale();
+        let (x, y, width, height) = match pane.rect.to_scissor(scale, surface_size) {
+            Some(scissor) => scissor,
+            None => {
+                self.panes.remove(&pane.id);
+                return None;
+            }
+        };
+
+        if self.panes.get(&pane.id).is_none_or(|cached| cached.size != (width, height)) {
+            let cached = self.create_pane(device, (width, height), texture_layout, main_view, format);
+            self.panes.insert(pane.id, cached);
+        }
+
+        let cached = self.panes.get_mut(&pane.id).unwrap();
+        cached.rect = pane.rect;
+        cached.revision = pane.revision;
+        cached.used = true;
+
+        //the texture covers whole surface pixels, so it lines up with the pixels it's drawn over
+        let origin = [x as f32 / scale, y as f32 / scale];
+        let scroll = main_view.get_scroll();
+        cached.view.set_scroll(queue, [scroll[0] + origin[0], scroll[1] + origin[1]]);
+
+        let quad = QuadInstance::texture(origin, [width as f32 / scale, height as f32 / scale]);
+        queue.write_buffer(&cached.quad_buffer, 0, bytemuck::bytes_of(&quad));
+
+        Some(cached)
+    }
+
+    fn create_pane(&self, device: &wgpu::Device, size: (u32, u32), texture_layout: &wgpu::BindGroupLayout, main_view: &View, format: wgpu::TextureFormat) -> CachedPane{
+        let texture = device.create_texture(&wgpu::TextureDescriptor{
+            label: Some("Pane Texture"),
+            size: wgpu::Extent3d{
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            //the pipelines only draw to the format frames are drawn in
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        });
+        let target = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            label: Some("Pane Bind Group"),
+            layout: texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry{
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target)
+                },
+                wgpu::BindGroupEntry{
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                },
+                //the layout also has the atlas's color page, which texture quads don't sample
+                wgpu::BindGroupEntry{
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&target)
+                }
+            ]
+        });
+
+        let quad_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor{
+            label: Some("Pane Quad Buffer"),
+            contents: &[0; std::mem::size_of::<QuadInstance>()],
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+        });
+
+        CachedPane{
+            target,
+            bind_group,
+            view: View::new(device, (size.0 as i32, size.1 as i32), main_view.get_content_scale()),
+            quad_buffer,
+            size,
+            rect: ClipRect::new(0.0, 0.0, 0.0, 0.0),
+            revision: 0,
+            used: true
+        }
+    }
+
+    ///Get the texture of the pane `id`, if it has one.
+    pub fn get(&self, id: PaneId) -> Option<&CachedPane>{
+        self.panes.get(&id)
+    }
+
+    ///Drops every texture, so each pane is drawn again the next time it's used.
+    pub fn clear(&mut self){
+        self.panes.clear();
+    }
+}
+
+///Returns true if a texture drawn for a pane at `rect` with `revision` still shows `pane`.
+fn is_current(rect: ClipRect, revision: u64, pane: &Pane) -> bool{
+    rect == pane.rect && revision == pane.revision
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn pane_is_redrawn_when_it_changes_or_moves(){
+        let rect = ClipRect::new(0.0, 0.0, 100.0, 50.0);
+        let pane = Pane{ id: 0, rect, revision: 3, background: None };
+
+        assert!(is_current(rect, 3, &pane));
+        assert!(!is_current(rect, 2, &pane));
+        assert!(!is_current(ClipRect::new(0.0, 0.0, 100.0, 60.0), 3, &pane));
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::mpsc;
+
+use crate::frontend::rendering::image::RgbaImage;
+
+///Copies `texture` back to the cpu as an RGBA image.  `size` is the texture's size in pixels and `format` its format,
+///which must be an 8 bit RGBA or BGRA format.  Blocks until the gpu has finished the copy.
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, size: (u32, u32), format: wgpu::TextureFormat) -> Result<RgbaImage, String>{
+    let bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => return Err(format!("Can't read back a {:?} texture", format))
+    };
+
+    let (width, height) = size;
+    let padded_row = padded_bytes_per_row(width);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor{
+        label: Some("Readback Buffer"),
+        size: padded_row as u64 * height as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
+        label: Some("Readback Encoder")
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture{
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All
+        },
+        wgpu::ImageCopyBuffer{
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout{
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                rows_per_image: std::num::NonZeroU32::new(height)
+            }
+        },
+        wgpu::Extent3d{
+            width,
+            height,
+            depth_or_array_layers: 1
+        }
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    //wait for the copy, then map the buffer
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    match receiver.recv() {
+        Ok(Ok(())) => {},
+        Ok(Err(error)) => return Err(format!("Failed to map readback buffer: {}", error)),
+        Err(_) => return Err(String::from("Readback buffer was never mapped"))
+    }
+
+    let pixels = unpad_rows(&slice.get_mapped_range(), width, height, padded_row, bgra);
+    buffer.unmap();
+
+    Ok(RgbaImage{
+        width,
+        height,
+        pixels
+    })
+}
+
+///Rows copied out of a texture have to start on a multiple of [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT] bytes.
+pub fn padded_bytes_per_row(width: u32) -> u32{
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(alignment) * alignment
+}
+
+///Drops the padding from the end of each row, and swaps blue and red if the data is BGRA.
+fn unpad_rows(data: &[u8], width: u32, height: u32, padded_row: u32, bgra: bool) -> Vec<u8>{
+    let row = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row * height as usize);
+
+    for y in 0..height as usize {
+        let start = y * padded_row as usize;
+        pixels.extend_from_slice(&data[start..start + row]);
+    }
+
+    if bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_copy_alignment(){
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(1), 256);
+    }
+
+    #[test]
+    fn padding_is_dropped_and_bgra_swapped(){
+        let mut data = vec![0; 512];
+        data[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        data[256..260].copy_from_slice(&[5, 6, 7, 8]);
+
+        assert_eq!(unpad_rows(&data, 1, 2, 256, false), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(unpad_rows(&data, 1, 2, 256, true), vec![3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+}
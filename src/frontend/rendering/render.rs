@@ -1,46 +1,610 @@
-use crate::frontend::wgpu_state::WGPUState;
+use crate::frontend::wgpu_state::{WGPUState, RenderTarget};
 use super::render_state::RenderState;
+use super::layer::{Layer, ClipRect};
+use super::pane_cache::Pane;
+use super::view::View;
+use super::image::RgbaImage;
+use super::readback;
+use super::color;
 
 
+///Colour the frame is cleared to before anything is drawn, the editor's background, in sRGB.
+pub const CLEAR_COLOR: [f32; 3] = [0.1, 0.2, 0.3];
 
-///Render the things designated to be rendered in render_state to the surface in wgpu_state.
+///Render the frame last uploaded to wgpu_state with [WGPUState::set_vertices_and_indices] to its surface.
+///Returns the error if the surface couldn't give a texture to draw to, nothing is drawn in that case.
 ///adapted from tuturial https://sotrh.github.io/learn-wgpu/beginner/tutorial2-surface/#render
-pub fn render(wgpu_state: &WGPUState, render_state: &RenderState){
-    
-    let output = wgpu_state.get_surface().get_current_texture().unwrap();
+pub fn render(wgpu_state: &WGPUState) -> Result<(), wgpu::SurfaceError>{
 
-    let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    match wgpu_state.get_target() {
+        RenderTarget::Surface(surface) => {
+            let output = surface.get_current_texture()?;
+            let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+            draw_frame(wgpu_state, &view);
+            output.present();
+        },
+        #[cfg(test)]
+        RenderTarget::Offscreen(texture) => {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            draw_frame(wgpu_state, &view);
+        },
+        RenderTarget::Released => return Err(wgpu::SurfaceError::Lost)
+    }
+
+    Ok(())
+}
+
+///Draws the frame to `target`, which is in the target's format.  Frames are drawn in sRGB, so if the target isn't the
+///frame is drawn into wgpu_state's frame texture and copied to it, see color.rs.
+fn draw_frame(wgpu_state: &WGPUState, target: &wgpu::TextureView){
+    match wgpu_state.get_frame() {
+        Some(frame) => {
+            draw(wgpu_state, frame.get_view(), wgpu_state.get_view(), None);
+            frame.copy_to(wgpu_state.get_device(), wgpu_state.get_queue(), target);
+        },
+        None => draw(wgpu_state, target, wgpu_state.get_view(), None)
+    }
+}
+
+///Renders the frame uploaded to wgpu_state and reads it back.  Offscreen targets are drawn to and read directly, surfaces
+///can't be read, so for a window the frame is drawn again into a texture of the same size, in the format frames are
+///drawn in.
+pub fn render_to_image(wgpu_state: &WGPUState) -> Result<RgbaImage, String>{
+    let config = wgpu_state.get_config();
+    let size = (config.width, config.height);
+
+    match wgpu_state.get_target() {
+        #[cfg(test)]
+        RenderTarget::Offscreen(texture) => {
+            render(wgpu_state).map_err(|error| error.to_string())?;
+            readback::read_texture(wgpu_state.get_device(), wgpu_state.get_queue(), texture, size, config.format)
+        },
+        RenderTarget::Released => Err(String::from("the surface was released after the GPU device was lost")),
+        RenderTarget::Surface(_) => {
+            let format = wgpu_state.get_drawing_format();
+            let texture = wgpu_state.get_device().create_texture(&wgpu::TextureDescriptor{
+                label: Some("Capture Target"),
+                size: wgpu::Extent3d{
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                //the pipelines only draw to the format frames are drawn in
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+            });
+            draw(wgpu_state, &texture.create_view(&wgpu::TextureViewDescriptor::default()), wgpu_state.get_view(), None);
+            readback::read_texture(wgpu_state.get_device(), wgpu_state.get_queue(), &texture, size, format)
+        }
+    }
+}
+
+///Draws `render_state` into `pane`'s texture, which is drawn to the window by adding the pane with
+///[RenderState::add_pane] until the pane changes.  `render_state` is uploaded to the pane buffers, so the frame
+///uploaded to wgpu_state is left as it was.
+pub fn render_pane(wgpu_state: &mut WGPUState, pane: &Pane, render_state: &mut RenderState) -> Result<(), &'static str>{
+    wgpu_state.set_pane_vertices_and_indices(render_state)?;
+
+    //a pane off the surface has nothing to draw
+    if wgpu_state.prepare_pane(pane).is_none() {
+        return Ok(());
+    }
+
+    let cached = wgpu_state.get_pane_cache().get(pane.id).unwrap();
+    draw(wgpu_state, cached.get_target(), cached.get_view(), Some(pane));
+    Ok(())
+}
+
+///Records and submits a render pass drawing the uploaded layers into `target` with `view`, which is the window's view
+///unless a pane's texture is being drawn, in which case `target_pane` is that pane and the pane buffers are drawn.
+fn draw(wgpu_state: &WGPUState, target: &wgpu::TextureView, view: &View, target_pane: Option<&Pane>){
 
     let mut encoder = wgpu_state.get_device().create_command_encoder(&wgpu::CommandEncoderDescriptor{
         label: Some("Render Encoder")
     });
 
+    //the clear colour doesn't go through the shaders, so convert it to linear light here.  panes without a background
+    //are cleared to transparent so whatever is under them in the window shows through
+    let background = match target_pane {
+        Some(pane) => pane.background,
+        None => Some(CLEAR_COLOR)
+    };
+    let clear_color = match background {
+        Some(background) => {
+            let clear_color = background.map(color::srgb_to_linear);
+            wgpu::Color{
+                r: clear_color[0] as f64,
+                g: clear_color[1] as f64,
+                b: clear_color[2] as f64,
+                a: 1.0
+            }
+        },
+        None => wgpu::Color::TRANSPARENT
+    };
+
+    //clip rectangles are in the window, move them by however far the view is scrolled past the window's
+    let surface_size = (view.get_surface_size().0 as u32, view.get_surface_size().1 as u32);
+    let clip_offset = [
+        view.get_scroll()[0] - wgpu_state.get_view().get_scroll()[0],
+        view.get_scroll()[1] - wgpu_state.get_view().get_scroll()[1]
+    ];
+
     {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment{
-                view: &view,
+                view: target,
                 resolve_target: None,
                 ops: wgpu::Operations{
-                    load: wgpu::LoadOp::Clear(wgpu::Color{
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0
-                    }),
+                    load: wgpu::LoadOp::Clear(clear_color),
                     store: true
                 }
             })],
             depth_stencil_attachment: None
         });
 
-        render_pass.set_pipeline(&wgpu_state.get_render_pipeline());
-        render_pass.set_vertex_buffer(0, wgpu_state.get_vertex_buffer().slice(..));
-        render_pass.set_index_buffer(wgpu_state.get_index_buffer().slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..wgpu_state.get_num_indices(), 0, 0..1);
+        render_pass.set_bind_group(0, view.get_bind_group(), &[]);
+        render_pass.set_bind_group(1, wgpu_state.get_glyph_atlas().get_bind_group(), &[]);
+
+        //layers are drawn back to front, each batch in a layer clipped to its own rectangle
+        let content_scale = view.get_content_scale();
+        let pipelines = wgpu_state.get_pipelines();
+        for layer in Layer::ALL {
+            let buffers = match target_pane {
+                Some(_) => wgpu_state.get_pane_layer_buffers(layer),
+                None => wgpu_state.get_layer_buffers(layer)
+            };
+
+            for batch in buffers.get_batches() {
+                let scissor = match batch.clip {
+                    Some(clip) => {
+                        let clip = ClipRect::new(clip.x - clip_offset[0], clip.y - clip_offset[1], clip.width, clip.height);
+                        match clip.to_scissor(content_scale, surface_size) {
+                            Some(scissor) => scissor,
+                            //the clip rectangle is off the surface, so nothing in the batch can be seen
+                            None => continue
+                        }
+                    },
+                    None => (0, 0, surface_size.0, surface_size.1)
+                };
+                render_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+
+                //a cached pane is one quad sampling the pane's texture in place of the atlas
+                if let Some(id) = batch.pane {
+                    let cached = match wgpu_state.get_pane_cache().get(id) {
+                        Some(cached) if Some(id) != target_pane.map(|pane| pane.id) => cached,
+                        //not drawn yet, or drawn since the device was lost
+                        _ => continue
+                    };
+                    render_pass.set_pipeline(&pipelines.quad);
+                    render_pass.set_bind_group(1, cached.get_bind_group(), &[]);
+                    render_pass.set_vertex_buffer(0, cached.get_quad_buffer().slice(..));
+                    render_pass.draw(0..6, 0..1);
+                    render_pass.set_bind_group(1, wgpu_state.get_glyph_atlas().get_bind_group(), &[]);
+                    continue;
+                }
+
+                if !batch.indices.is_empty() {
+                    render_pass.set_pipeline(&pipelines.mesh);
+                    render_pass.set_vertex_buffer(0, buffers.get_vertex_buffer().slice(..));
+                    render_pass.set_index_buffer(buffers.get_index_buffer().slice(..), buffers.get_index_format());
+                    render_pass.draw_indexed(batch.indices.clone(), 0, 0..1);
+                }
+
+                //rounded rectangles go on top of the batch's meshes, one instance each
+                if !batch.rects.is_empty() {
+                    render_pass.set_pipeline(&pipelines.rect);
+                    render_pass.set_vertex_buffer(0, buffers.get_rect_buffer().slice(..));
+                    render_pass.draw(0..6, batch.rects.clone());
+                }
+
+                //glyphs and rectangles go on top of those, one instance each.  runs of subpixel glyphs are drawn in their
+                //place with the LCD pipelines, so quads after them still go on top
+                if !batch.quads.is_empty() {
+                    render_pass.set_vertex_buffer(0, buffers.get_quad_buffer().slice(..));
+                    let mut start = batch.quads.start;
+                    for run in &batch.lcd {
+                        if start < run.start {
+                            render_pass.set_pipeline(&pipelines.quad);
+                            render_pass.draw(0..6, start..run.start);
+                        }
+
+                        match background {
+                            //drawn a channel at a time: the mask darkens what's under each subpixel, then the color is added
+                            Some(_) => {
+                                render_pass.set_pipeline(&pipelines.lcd_mask);
+                                render_pass.draw(0..6, run.clone());
+                                render_pass.set_pipeline(&pipelines.lcd_color);
+                                render_pass.draw(0..6, run.clone());
+                            },
+                            //a transparent pane has nothing under the text to blend each channel with
+                            None => {
+                                render_pass.set_pipeline(&pipelines.lcd_grayscale);
+                                render_pass.draw(0..6, run.clone());
+                            }
+                        }
+                        start = run.end;
+                    }
+                    if start < batch.quads.end {
+                        render_pass.set_pipeline(&pipelines.quad);
+                        render_pass.draw(0..6, start..batch.quads.end);
+                    }
+                }
+            }
+        }
 
     }
 
     wgpu_state.get_queue().submit(std::iter::once(encoder.finish()));
-    output.present();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::frontend::rendering::mesh::{Vertex, Border, Shadow};
+    use crate::frontend::rendering::layer::ClipRect;
+    use crate::frontend::rendering::snapshot::{self, Tolerance};
+    use crate::frontend::rendering::shaping::{FontChain, Shaper};
+    use crate::frontend::rendering::font::Font;
+    use crate::frontend::wgpu_state::OFFSCREEN_FORMAT;
+    use crate::frontend::rendering::text;
+    use crate::frontend::graphics_config::{GraphicsConfig, SubpixelOrder};
+
+    fn render_snapshot(name: &str, size: (i32, i32), build: impl FnOnce(&mut RenderState)){
+        let mut wgpu_state = match snapshot::offscreen_state(size) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let mut render_state = RenderState::new();
+        build(&mut render_state);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+
+        let image = render_to_image(&wgpu_state).unwrap();
+        assert_eq!((image.width, image.height), (size.0 as u32, size.1 as u32));
+        snapshot::check_snapshot(name, &image, Tolerance::default()).unwrap();
+    }
+
+    #[test]
+    fn triangle_snapshot(){
+        render_snapshot("triangle", (64, 48), |render_state| {
+            render_state.add_mesh(
+                &[
+                    Vertex { position: [48.0, 36.0, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                    Vertex { position: [32.0, 12.0, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                    Vertex { position: [16.0, 36.0, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
+                ],
+                &[0, 1, 2]
+            );
+        });
+    }
+
+    #[test]
+    fn quads_snapshot(){
+        render_snapshot("quads", (64, 48), |render_state| {
+            render_state.add_quad([4.0, 4.0], [28.0, 20.0], [1.0, 1.0, 1.0, 1.0]);
+            render_state.add_quad([20.0, 12.0], [60.0, 44.0], [0.9, 0.5, 0.1, 1.0]);
+        });
+    }
+
+    #[test]
+    fn translucent_quads_snapshot(){
+        render_snapshot("translucent_quads", (64, 48), |render_state| {
+            render_state.add_quad([4.0, 4.0], [40.0, 32.0], [1.0, 0.2, 0.2, 1.0]);
+            render_state.add_quad([24.0, 16.0], [60.0, 44.0], [0.2, 0.4, 1.0, 0.5]);
+        });
+    }
+
+    #[test]
+    fn colors_match_on_srgb_and_linear_targets(){
+        let draw = |format: wgpu::TextureFormat| {
+            let mut wgpu_state = snapshot::offscreen_state_with_format((32, 16), format)?;
+            let mut render_state = RenderState::new();
+            render_state.add_quad([0.0, 0.0], [8.0, 16.0], [0.9, 0.5, 0.1, 1.0]);
+            //a translucent fill over the quad and the background, and a circle whose edge is antialiased
+            render_state.add_quad([4.0, 0.0], [20.0, 4.0], [0.2, 0.4, 1.0, 0.5]);
+            render_state.add_rect([18.0, 2.0], [30.0, 14.0], 6.0, [1.0, 1.0, 1.0, 1.0], None, None);
+            wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+            Some(render_to_image(&wgpu_state).unwrap())
+        };
+
+        let (srgb, linear) = match (draw(wgpu::TextureFormat::Rgba8UnormSrgb), draw(wgpu::TextureFormat::Rgba8Unorm)) {
+            (Some(srgb), Some(linear)) => (srgb, linear),
+            _ => return
+        };
+
+        //the theme's colour comes out as the same bytes, on the quad and on the cleared background, give or take rounding
+        let close = |a: [u8; 4], b: [u8; 4]| a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1);
+        assert!(close(linear.get_pixel(4, 8), [230, 128, 26, 255]), "{:?}", linear.get_pixel(4, 8));
+        assert!(close(linear.get_pixel(12, 8), [26, 51, 77, 255]), "{:?}", linear.get_pixel(12, 8));
+
+        //blending happens in linear light on both, so half of the fill over the quad is lighter than halfway in sRGB, 140
+        assert!(linear.get_pixel(6, 2)[2] > 170, "{:?}", linear.get_pixel(6, 2));
+        //the circle's edge is partly covered
+        let edge = (0..16).map(|y| linear.get_pixel(19, y)[0]).filter(|&red| red > 30 && red < 250).count();
+        assert!(edge > 0);
+
+        //and every pixel, the fill and the edges included, matches
+        let diff = snapshot::diff_images(&srgb, &linear, 1).unwrap();
+        assert_eq!(diff.differing_pixels, 0, "largest difference {}", diff.max_difference);
+    }
+
+    #[test]
+    fn solid_quad_covers_its_pixels(){
+        let mut wgpu_state = match snapshot::offscreen_state((16, 16)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let mut render_state = RenderState::new();
+        render_state.add_quad([4.0, 4.0], [12.0, 12.0], [1.0, 1.0, 1.0, 1.0]);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+
+        let image = render_to_image(&wgpu_state).unwrap();
+        assert_eq!(image.get_pixel(8, 8), [255, 255, 255, 255]);
+        assert_ne!(image.get_pixel(1, 1), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn later_layers_are_drawn_on_top(){
+        let mut wgpu_state = match snapshot::offscreen_state((16, 16)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        //the popup is added first but still covers the text
+        let mut render_state = RenderState::new();
+        render_state.set_layer(Layer::Popup);
+        render_state.add_quad([0.0, 0.0], [16.0, 16.0], [1.0, 0.0, 0.0, 1.0]);
+        render_state.set_layer(Layer::Text);
+        render_state.add_quad([0.0, 0.0], [16.0, 16.0], [0.0, 0.0, 1.0, 1.0]);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+
+        let image = render_to_image(&wgpu_state).unwrap();
+        assert_eq!(image.get_pixel(8, 8), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn clipped_panes_snapshot(){
+        render_snapshot("clipped_panes", (64, 48), |render_state| {
+            //two panes side by side, each drawing past its edges
+            render_state.set_layer(Layer::Text);
+            render_state.set_clip(Some(ClipRect::new(0.0, 0.0, 32.0, 48.0)));
+            render_state.add_quad([8.0, 8.0], [48.0, 24.0], [1.0, 1.0, 1.0, 1.0]);
+            render_state.set_clip(Some(ClipRect::new(32.0, 0.0, 32.0, 48.0)));
+            render_state.add_quad([16.0, 24.0], [56.0, 40.0], [0.9, 0.5, 0.1, 1.0]);
+
+            //nothing in a clip rectangle off the surface is drawn
+            render_state.set_clip(Some(ClipRect::new(100.0, 0.0, 10.0, 10.0)));
+            render_state.add_quad([0.0, 0.0], [64.0, 48.0], [1.0, 0.0, 0.0, 1.0]);
+        });
+    }
+
+    #[test]
+    fn cached_pane_looks_like_drawing_its_content(){
+        let mut wgpu_state = match snapshot::offscreen_state((64, 48)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let pane = Pane{ id: 1, rect: ClipRect::new(8.0, 8.0, 40.0, 30.0), revision: 1, background: None };
+        let content = |render_state: &mut RenderState| {
+            render_state.set_layer(Layer::Text);
+            render_state.set_clip(Some(pane.rect));
+            render_state.add_quad([0.0, 0.0], [30.0, 20.0], [1.0, 1.0, 1.0, 1.0]);
+            render_state.add_quad([20.0, 16.0], [64.0, 48.0], [0.9, 0.5, 0.1, 1.0]);
+        };
+
+        let mut direct = RenderState::new();
+        content(&mut direct);
+        wgpu_state.set_vertices_and_indices(&mut direct).unwrap();
+        let expected = render_to_image(&wgpu_state).unwrap();
+
+        let mut pane_content = RenderState::new();
+        content(&mut pane_content);
+        render_pane(&mut wgpu_state, &pane, &mut pane_content).unwrap();
+        assert!(wgpu_state.get_pane_cache_mut().is_current(&pane));
+
+        let mut frame = RenderState::new();
+        frame.add_pane(&pane);
+        wgpu_state.set_vertices_and_indices(&mut frame).unwrap();
+        let image = render_to_image(&wgpu_state).unwrap();
+
+        let diff = snapshot::diff_images(&expected, &image, 1).unwrap();
+        assert_eq!(diff.differing_pixels, 0, "largest difference {}", diff.max_difference);
+    }
+
+    #[test]
+    fn drawing_a_pane_keeps_the_uploaded_frame(){
+        let mut wgpu_state = match snapshot::offscreen_state((32, 32)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let mut frame = RenderState::new();
+        frame.add_quad([0.0, 0.0], [16.0, 32.0], [1.0, 0.0, 0.0, 1.0]);
+        wgpu_state.set_vertices_and_indices(&mut frame).unwrap();
+        let expected = render_to_image(&wgpu_state).unwrap();
+
+        let pane = Pane{ id: 1, rect: ClipRect::new(0.0, 0.0, 32.0, 32.0), revision: 1, background: None };
+        let mut pane_content = RenderState::new();
+        pane_content.set_clip(Some(pane.rect));
+        pane_content.add_quad([0.0, 0.0], [32.0, 32.0], [0.0, 0.0, 1.0, 1.0]);
+        render_pane(&mut wgpu_state, &pane, &mut pane_content).unwrap();
+
+        //the next frame didn't upload, so the last one is drawn again without the pane's content
+        let image = render_to_image(&wgpu_state).unwrap();
+        let diff = snapshot::diff_images(&expected, &image, 0).unwrap();
+        assert_eq!(diff.differing_pixels, 0, "largest difference {}", diff.max_difference);
+    }
+
+    #[test]
+    fn rounded_rects_snapshot(){
+        render_snapshot("rounded_rects", (64, 48), |render_state| {
+            render_state.add_rect([6.0, 6.0], [30.0, 26.0], 6.0, [0.9, 0.5, 0.1, 1.0], None, None);
+            render_state.add_rect(
+                [36.0, 8.0], [58.0, 30.0], 11.0, [1.0, 1.0, 1.0, 1.0],
+                Some(Border{ width: 2.0, color: [0.2, 0.4, 1.0, 1.0] }), None
+            );
+            render_state.add_rect(
+                [10.0, 32.0], [50.0, 42.0], 3.0, [0.95, 0.95, 0.95, 1.0],
+                None, Some(Shadow{ offset: [2.0, 2.0], blur: 3.0, color: [0.0, 0.0, 0.0, 0.8] })
+            );
+        });
+    }
+
+    #[test]
+    fn rect_corners_are_rounded(){
+        let mut wgpu_state = match snapshot::offscreen_state((32, 32)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let mut render_state = RenderState::new();
+        render_state.add_rect([0.0, 0.0], [32.0, 32.0], 12.0, [1.0, 1.0, 1.0, 1.0], None, None);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+
+        //the middle is filled and the corner is cut off, showing the background
+        let image = render_to_image(&wgpu_state).unwrap();
+        assert_eq!(image.get_pixel(16, 16), [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(16, 0), [255, 255, 255, 255]);
+        assert!(image.get_pixel(0, 0)[0] < 100, "{:?}", image.get_pixel(0, 0));
+    }
+
+    ///A bitmap font with one glyph, a bar for 'l', so text renders the same on every machine.
+    const BAR_FONT: &str = "STARTFONT 2.1
+SIZE 4 75 75
+FONTBOUNDINGBOX 1 4 0 0
+STARTPROPERTIES 2
+FONT_ASCENT 4
+FONT_DESCENT 0
+ENDPROPERTIES
+CHARS 1
+STARTCHAR l
+ENCODING 108
+DWIDTH 3 0
+BBX 1 4 0 0
+BITMAP
+80
+80
+80
+80
+ENDCHAR
+ENDFONT
+";
+
+    fn bar_font() -> FontChain{
+        FontChain::new(vec![Font::from_bytes(BAR_FONT.as_bytes().to_vec()).unwrap()])
+    }
+
+    fn subpixel_config() -> GraphicsConfig{
+        GraphicsConfig{ subpixel: Some(SubpixelOrder::Rgb), ..GraphicsConfig::default() }
+    }
+
+    ///Renders white text with `graphics_config` and returns the biggest difference between the coverage of a pixel's
+    ///red and blue channels, or None if there's no font or adapter to test with.
+    fn text_channel_coverage_difference(graphics_config: GraphicsConfig) -> Option<f32>{
+        let fonts = FontChain::load(None)?;
+        let mut wgpu_state = snapshot::offscreen_state_with_config((64, 32), wgpu::TextureFormat::Rgba8Unorm, graphics_config)?;
+
+        let mut render_state = RenderState::new();
+        text::add_text(&mut render_state, &mut wgpu_state, &mut Shaper::new(), &fonts, "lil", [4.0, 24.0], 20.0, [1.0, 1.0, 1.0, 1.0]);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+        Some(channel_coverage_difference(&render_to_image(&wgpu_state).unwrap()))
+    }
+
+    ///Returns the biggest difference between the coverage of a pixel's red and blue channels by white drawn over the
+    ///background in the top left pixel.
+    fn channel_coverage_difference(image: &RgbaImage) -> f32{
+        //blending happens in linear light, so there the same coverage moves every channel the same fraction of the way to white
+        let linear = |pixel: [u8; 4], channel: usize| color::srgb_to_linear(pixel[channel] as f32 / 255.0);
+        let background = image.get_pixel(0, 0);
+        let coverage = |pixel: [u8; 4], channel: usize| (linear(pixel, channel) - linear(background, channel)) / (1.0 - linear(background, channel));
+        let mut difference: f32 = 0.0;
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let pixel = image.get_pixel(x, y);
+                difference = difference.max((coverage(pixel, 0) - coverage(pixel, 2)).abs());
+            }
+        }
+        difference
+    }
+
+    #[test]
+    fn subpixel_text_covers_each_channel_separately(){
+        let (grayscale, subpixel) = match (text_channel_coverage_difference(GraphicsConfig::default()), text_channel_coverage_difference(subpixel_config())) {
+            (Some(grayscale), Some(subpixel)) => (grayscale, subpixel),
+            _ => return
+        };
+
+        assert!(grayscale < 0.05, "{}", grayscale);
+        assert!(subpixel > 0.2, "{}", subpixel);
+    }
+
+    ///Draws white subpixel text into a pane with `background` and draws the pane to the window, or returns None if
+    ///there's no adapter to test with.
+    fn render_subpixel_pane(background: Option<[f32; 3]>) -> Option<RgbaImage>{
+        let mut wgpu_state = snapshot::offscreen_state_with_config((32, 16), OFFSCREEN_FORMAT, subpixel_config())?;
+
+        let pane = Pane{ id: 1, rect: ClipRect::new(0.0, 0.0, 32.0, 16.0), revision: 1, background };
+        let mut pane_content = RenderState::new();
+        pane_content.set_layer(Layer::Text);
+        pane_content.set_clip(Some(pane.rect));
+        text::add_text(&mut pane_content, &mut wgpu_state, &mut Shaper::new(), &bar_font(), "lll", [4.0, 12.0], 8.0, [1.0, 1.0, 1.0, 1.0]);
+        render_pane(&mut wgpu_state, &pane, &mut pane_content).unwrap();
+
+        let mut frame = RenderState::new();
+        frame.add_pane(&pane);
+        wgpu_state.set_vertices_and_indices(&mut frame).unwrap();
+        Some(render_to_image(&wgpu_state).unwrap())
+    }
+
+    #[test]
+    fn subpixel_text_in_panes_snapshot(){
+        let image = match render_subpixel_pane(Some(CLEAR_COLOR)) {
+            Some(image) => image,
+            None => return
+        };
+
+        //the pane is opaque, so each channel is blended with its background like text drawn to the window
+        assert!(channel_coverage_difference(&image) > 0.2, "{}", channel_coverage_difference(&image));
+        snapshot::check_snapshot("subpixel_pane_text", &image, Tolerance::default()).unwrap();
+    }
+
+    #[test]
+    fn subpixel_text_in_transparent_panes_is_grayscale(){
+        let image = match render_subpixel_pane(None) {
+            Some(image) => image,
+            None => return
+        };
+
+        //the pane is transparent where the text is drawn, so it's drawn in grayscale instead of leaving coloured fringes
+        assert!(image.get_pixel(5, 8)[0] > 200, "{:?}", image.get_pixel(5, 8));
+        assert!(channel_coverage_difference(&image) < 0.05, "{}", channel_coverage_difference(&image));
+    }
+
+    #[test]
+    fn quads_after_subpixel_text_stay_on_top(){
+        let mut wgpu_state = match snapshot::offscreen_state_with_config((32, 16), OFFSCREEN_FORMAT, subpixel_config()) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        //text and then a quad over it, in the same batch
+        let mut render_state = RenderState::new();
+        text::add_text(&mut render_state, &mut wgpu_state, &mut Shaper::new(), &bar_font(), "lll", [4.0, 12.0], 8.0, [1.0, 1.0, 1.0, 1.0]);
+        render_state.add_quad([0.0, 0.0], [16.0, 16.0], [1.0, 0.0, 0.0, 1.0]);
+        wgpu_state.set_vertices_and_indices(&mut render_state).unwrap();
+        assert_eq!(wgpu_state.get_layer_buffers(Layer::Background).get_batches().len(), 1);
+
+        let image = render_to_image(&wgpu_state).unwrap();
+        for x in 0..16 {
+            assert_eq!(image.get_pixel(x, 8), [255, 0, 0, 255], "{}", x);
+        }
+        //the text outside the quad is still drawn with subpixel coverage
+        assert!(channel_coverage_difference(&image) > 0.2, "{}", channel_coverage_difference(&image));
+    }
+}
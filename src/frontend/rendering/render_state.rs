@@ -1,51 +1,141 @@
-use crate::frontend::rendering::mesh::{Vertex, VERTICES};
+use crate::frontend::rendering::mesh::{Vertex, QuadInstance, RectInstance, Border, Shadow};
+use crate::frontend::rendering::layer::{Layer, LayerContent, ClipRect};
+use crate::frontend::rendering::pane_cache::Pane;
 
 
 pub struct RenderState{
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u16>,
-    pub num_indices: u32
+    ///What's been added to each layer, indexed by [Layer::index].
+    layers: Vec<LayerContent>,
+    ///The layer meshes and quads are added to.
+    layer: Layer,
+    ///The rectangle meshes and quads are clipped to, or None for the whole surface.
+    clip: Option<ClipRect>,
+    ///Set when a mesh couldn't be added, so the frame is reported as failed instead of drawing garbage.
+    error: Option<&'static str>
 }
 
 ///Stores vertex and index information which can later be sent to the gpu using [[WGPUState]]
+///Everything is added to the current layer, clipped to the current clip rectangle, see [RenderState::set_layer] and
+///[RenderState::set_clip].  Both are reset when the RenderState is cleared.
 impl RenderState{
     pub fn new() -> RenderState{
         RenderState {  
-            vertices: Vec::new(),
-            indices: Vec::new(),
-            num_indices: 0
+            layers: Layer::ALL.iter().map(|_| LayerContent::new()).collect(),
+            layer: Layer::Background,
+            clip: None,
+            error: None
         }
     }
 
-    pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[u16]){
-        //indices are for the current mesh and we probably already have some so they need to be adjusted
-        let index_offset = self.vertices.len() as u16;
+    ///Set the layer meshes and quads are added to from now on.
+    pub fn set_layer(&mut self, layer: Layer){
+        self.layer = layer;
+    }
 
-        //add vertices
-        for v in vertices{
-            self.vertices.push(*v);
-        }
+    ///Get the layer meshes and quads are currently added to.
+    #[cfg(test)]
+    pub fn get_layer(&self) -> Layer{
+        self.layer
+    }
+
+    ///Clip meshes and quads added from now on to `clip`, usually the pane they belong to, or to nothing with None.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>){
+        self.clip = clip;
+    }
+
+    ///Get the rectangle meshes and quads are currently clipped to.
+    #[cfg(test)]
+    pub fn get_clip(&self) -> Option<ClipRect>{
+        self.clip
+    }
 
-        //add indices, offset by the calculated factor
-        for i in indices{
-            self.indices.push(*i + index_offset);
+    ///Get what has been added to `layer`.
+    #[cfg(test)]
+    pub fn get_layer_content(&self, layer: Layer) -> &LayerContent{
+        &self.layers[layer.index()]
+    }
+
+    ///Get what has been added to each layer, in the order they're drawn.
+    pub fn get_layers(&self) -> &[LayerContent]{
+        &self.layers
+    }
+
+    ///Add vertices and indices, drawn with the solid pipeline.  `indices` count from the first of `vertices`.
+    ///A mesh which doesn't fit is dropped and the error is returned from [RenderState::get_error].
+    pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[u32]){
+        let clip = self.clip;
+        if let Err(error) = self.layers[self.layer.index()].add_mesh(vertices, indices, clip) {
+            self.error.get_or_insert(error);
         }
+    }
 
-        self.num_indices += indices.len() as u32;
+    ///Add glyphs or rectangles, drawn with the instanced quad pipeline after the layer's meshes.
+    pub fn add_quads(&mut self, quads: impl IntoIterator<Item = QuadInstance>){
+        let clip = self.clip;
+        self.layers[self.layer.index()].add_quads(quads, clip);
     }
 
-    ///Remove all vertices and indices from this RenderState.
+    ///Add a rectangle from `min` to `max` (top left to bottom right, in logical pixels) filled with `fill`, with corners
+    ///rounded to `radius` and an optional border and drop shadow.  Drawn with the rect pipeline, after the layer's
+    ///meshes and before its quads, so text can go on top.
+    pub fn add_rect(&mut self, min: [f32; 2], max: [f32; 2], radius: f32, fill: [f32; 4], border: Option<Border>, shadow: Option<Shadow>){
+        let clip = self.clip;
+        self.layers[self.layer.index()].add_rects([RectInstance::new(min, max, radius, fill, border, shadow)], clip);
+    }
+
+    ///Add the cached texture of `pane`, drawn with [crate::frontend::rendering::render::render_pane] this frame or an
+    ///earlier one.  Nothing is drawn if the pane has no texture.
+    pub fn add_pane(&mut self, pane: &Pane){
+        let clip = self.clip;
+        self.layers[self.layer.index()].add_pane(pane.id, clip);
+    }
+
+    ///Get the first error from adding a mesh since this RenderState was cleared.
+    pub fn get_error(&self) -> Option<&'static str>{
+        self.error
+    }
+
+    ///Add a solid colored rectangle from `min` to `max` (top left to bottom right, in logical pixels).
+    pub fn add_quad(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]){
+        self.add_quads([QuadInstance::solid(min, max, color)]);
+    }
+
+    ///Get the number of vertices in every layer.
+    pub fn num_vertices(&self) -> usize{
+        self.layers.iter().map(|layer| layer.vertices.len()).sum()
+    }
+
+    ///Get the number of indices in every layer, not counting padding.
+    pub fn num_indices(&self) -> u32{
+        self.layers.iter().map(|layer| layer.num_indices).sum()
+    }
+
+    ///Get the number of rounded rectangles in every layer.
+    #[cfg(test)]
+    pub fn num_rects(&self) -> usize{
+        self.layers.iter().map(|layer| layer.rects.len()).sum()
+    }
+
+    ///Get the number of quads in every layer.
+    pub fn num_quads(&self) -> usize{
+        self.layers.iter().map(|layer| layer.quads.len()).sum()
+    }
+
+    ///Remove all vertices and indices from this RenderState, and go back to drawing unclipped in the background layer.
     pub fn clear(&mut self){
-        self.vertices.clear();
-        self.indices.clear();
-        self.num_indices = 0;
+        for layer in &mut self.layers {
+            layer.clear();
+        }
+        self.layer = Layer::Background;
+        self.clip = None;
+        self.error = None;
     }
 
-    ///Pads the index buffer so that it is a multiple of 4, but does NOT increase the num_indices count.  
+    ///Pads each layer's index buffer so that it is a multiple of 4, but does NOT increase the num_indices count.  
     ///May cause unexpected behaviour if called multiple times before clearing.
     pub fn pad_index_buffer(&mut self){
-        while self.indices.len() % 4 != 0{
-            self.indices.push(0);
+        for layer in &mut self.layers {
+            layer.pad_index_buffer();
         }
     }
 }
@@ -54,24 +144,28 @@ impl RenderState{
 mod test{
     use super::*;
 
+    fn background(render_state: &RenderState) -> &LayerContent{
+        render_state.get_layer_content(Layer::Background)
+    }
+
     #[test]
     fn add_mesh_vertices(){
         let mut render_state = RenderState::new();
 
         render_state.add_mesh(
             &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
             ],
             &[
                 0, 1, 2,
             ] 
         );
 
-        assert!(Vertex::bitwise_equal(render_state.vertices[0], Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]}));
-        assert!(Vertex::bitwise_equal(render_state.vertices[1], Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]}));
-        assert!(Vertex::bitwise_equal(render_state.vertices[2], Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}));
+        assert!(Vertex::bitwise_equal(background(&render_state).vertices[0], Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]}));
+        assert!(Vertex::bitwise_equal(background(&render_state).vertices[1], Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]}));
+        assert!(Vertex::bitwise_equal(background(&render_state).vertices[2], Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}));
     }
 
     #[test]
@@ -80,16 +174,16 @@ mod test{
 
         render_state.add_mesh(
             &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
             ],
             &[
                 0, 1, 2,
             ] 
         );
 
-        assert_eq!(render_state.indices, vec![0, 1, 2]);
+        assert_eq!(background(&render_state).indices, vec![0, 1, 2]);
     }
 
     #[test]
@@ -98,16 +192,28 @@ mod test{
 
         render_state.add_mesh(
             &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
             ],
             &[
                 0, 1, 2,
             ] 
         );
 
-        assert_eq!(render_state.num_indices, 3);
+        assert_eq!(render_state.num_indices(), 3);
+    }
+
+    #[test]
+    fn add_quad_is_one_instance(){
+        let mut render_state = RenderState::new();
+
+        render_state.add_quad([1.0, 2.0], [4.0, 6.0], [1.0, 1.0, 1.0, 1.0]);
+
+        assert_eq!(background(&render_state).quads.len(), 1);
+        assert_eq!(background(&render_state).quads[0].size, [3.0, 4.0]);
+        assert_eq!(background(&render_state).vertices.len(), 0);
+        assert_eq!(render_state.num_indices(), 0);
     }
 
     #[test]
@@ -116,9 +222,9 @@ mod test{
 
         render_state.add_mesh(
             &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
             ],
             &[
                 0, 1, 2,
@@ -127,8 +233,33 @@ mod test{
 
         render_state.clear();
 
-        assert_eq!(render_state.num_indices, 0);
-        assert_eq!(render_state.vertices.len(), 0);
+        assert_eq!(render_state.num_indices(), 0);
+        assert_eq!(background(&render_state).vertices.len(), 0);
+    }
+
+    #[test]
+    fn indices_past_u16_are_not_wrapped(){
+        let mut render_state = RenderState::new();
+        let vertex = Vertex { position: [0.0, 0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0]};
+
+        render_state.add_mesh(&vec![vertex; 70_000], &[0]);
+        render_state.add_mesh(&[vertex], &[0]);
+
+        assert_eq!(background(&render_state).indices, vec![0, 70_000]);
+        assert_eq!(render_state.get_error(), None);
+    }
+
+    #[test]
+    fn bad_mesh_is_an_error(){
+        let mut render_state = RenderState::new();
+        let vertex = Vertex { position: [0.0, 0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0]};
+
+        render_state.add_mesh(&[vertex], &[0, 1]);
+
+        assert!(render_state.get_error().is_some());
+        assert_eq!(render_state.num_indices(), 0);
+        render_state.clear();
+        assert_eq!(render_state.get_error(), None);
     }
 
     #[test]
@@ -137,9 +268,9 @@ mod test{
 
         render_state.add_mesh(
             &[
-                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0]},
-                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0]},
-                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0]}
+                Vertex { position: [0.5, -0.5, 0.0], color: [1.0, 0.0, 0.0, 1.0]},
+                Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 1.0, 0.0, 1.0]},
+                Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0, 1.0]}
             ],
             &[
                 0, 1, 2,
@@ -148,8 +279,30 @@ mod test{
 
         render_state.pad_index_buffer();
 
-        assert_eq!(render_state.indices.len(), 4);
+        assert_eq!(background(&render_state).indices.len(), 4);
+
+    }
+
+    #[test]
+    fn quads_go_to_the_current_layer_and_clip(){
+        let mut render_state = RenderState::new();
+        let pane = ClipRect::new(0.0, 0.0, 100.0, 50.0);
 
+        render_state.set_layer(Layer::Popup);
+        render_state.add_quad([1.0, 2.0], [4.0, 6.0], [1.0, 1.0, 1.0, 1.0]);
+        render_state.set_layer(Layer::Text);
+        render_state.set_clip(Some(pane));
+        render_state.add_quad([1.0, 2.0], [4.0, 6.0], [1.0, 1.0, 1.0, 1.0]);
+
+        assert_eq!(render_state.get_layer_content(Layer::Popup).batches[0].clip, None);
+        assert_eq!(render_state.get_layer_content(Layer::Text).batches[0].clip, Some(pane));
+        assert!(background(&render_state).is_empty());
+        assert_eq!(render_state.num_quads(), 2);
+
+        render_state.clear();
+        assert_eq!(render_state.get_layer(), Layer::Background);
+        assert_eq!(render_state.get_clip(), None);
+        assert_eq!(render_state.num_quads(), 0);
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use crate::frontend::rendering::font::RasterizedGlyph;
+
+///Stands in for an infinite squared distance, far enough that it never wins a minimum against a real one.
+const FAR: f32 = 1e20;
+
+///Turns a coverage bitmap into a signed distance field, `spread` pixels bigger on every side so the field has room
+///to fall off outside the outline.  Each byte is 128 on the outline, rising by 128 / `spread` per pixel inside it
+///and falling by the same outside, so the quad shader finds the edge at 0.5 at any scale.
+///
+///Distances are exact Euclidean distances to the nearest pixel on the other side of the outline (Felzenszwalb and
+///Huttenlocher), with partly covered pixels placed part of a pixel from the edge by their coverage.
+pub fn to_sdf(glyph: &RasterizedGlyph, spread: u32) -> RasterizedGlyph{
+    let width = glyph.width + spread * 2;
+    let height = glyph.height + spread * 2;
+    let size = (width * height) as usize;
+
+    //squared distance to the outline from outside and from inside, starting at 0 on the far side of it
+    let mut outside = vec![FAR; size];
+    let mut inside = vec![0.0; size];
+    for row in 0..glyph.height{
+        for column in 0..glyph.width{
+            let coverage = glyph.coverage[(row * glyph.width + column) as usize] as f32 / 255.0;
+            let index = ((row + spread) * width + column + spread) as usize;
+
+            (outside[index], inside[index]) = if coverage >= 1.0 {
+                (0.0, FAR)
+            }else if coverage <= 0.0 {
+                (FAR, 0.0)
+            }else{
+                ((0.5 - coverage).max(0.0).powi(2), (coverage - 0.5).max(0.0).powi(2))
+            };
+        }
+    }
+
+    distance_transform(&mut outside, width as usize, height as usize);
+    distance_transform(&mut inside, width as usize, height as usize);
+
+    let coverage = outside.iter()
+        .zip(&inside)
+        .map(|(outside, inside)| {
+            //positive outside the outline
+            let distance = outside.sqrt() - inside.sqrt();
+            (128.0 - distance * 128.0 / spread as f32).round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+
+    RasterizedGlyph{
+        width,
+        height,
+        left: glyph.left - spread as i32,
+        top: glyph.top - spread as i32,
+        coverage
+    }
+}
+
+///Replaces each value in a `width` x `height` grid with the smallest of every other value plus its squared distance,
+///one column pass and one row pass.
+fn distance_transform(grid: &mut [f32], width: usize, height: usize){
+    let length = width.max(height);
+    let mut scratch = Scratch{
+        values: vec![0.0; length],
+        parabolas: vec![0; length],
+        boundaries: vec![0.0; length + 1]
+    };
+
+    for column in 0..width{
+        distance_transform_1d(grid, column, width, height, &mut scratch);
+    }
+    for row in 0..height{
+        distance_transform_1d(grid, row * width, 1, width, &mut scratch);
+    }
+}
+
+///Buffers reused across the rows and columns of one [distance_transform].
+struct Scratch{
+    values: Vec<f32>,
+    ///Where the parabolas making up the lower envelope are centred.
+    parabolas: Vec<usize>,
+    ///Where each parabola in the envelope takes over from the one before it.
+    boundaries: Vec<f32>
+}
+
+///One dimensional squared distance transform of the `length` values starting at `offset`, `stride` apart, as the
+///lower envelope of a parabola rooted at each value.
+fn distance_transform_1d(grid: &mut [f32], offset: usize, stride: usize, length: usize, scratch: &mut Scratch){
+    let Scratch{ values, parabolas, boundaries } = scratch;
+    for (q, value) in values.iter_mut().take(length).enumerate(){
+        *value = grid[offset + q * stride];
+    }
+
+    parabolas[0] = 0;
+    boundaries[0] = -FAR;
+    boundaries[1] = FAR;
+    let mut k = 0;
+    for q in 1..length{
+        let mut boundary;
+        loop{
+            let r = parabolas[k];
+            boundary = (values[q] - values[r] + (q * q) as f32 - (r * r) as f32) / (q - r) as f32 / 2.0;
+            if boundary > boundaries[k] || k == 0 {
+                break;
+            }
+            k -= 1;
+        }
+        //the first parabola is only replaced if the new one is lower everywhere
+        if boundary <= boundaries[k] {
+            parabolas[k] = q;
+            boundaries[k + 1] = FAR;
+            continue;
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = boundary;
+        boundaries[k + 1] = FAR;
+    }
+
+    k = 0;
+    for q in 0..length{
+        while boundaries[k + 1] < q as f32{
+            k += 1;
+        }
+        let r = parabolas[k];
+        let distance = q as f32 - r as f32;
+        grid[offset + q * stride] = values[r] + distance * distance;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn square(size: u32) -> RasterizedGlyph{
+        RasterizedGlyph{
+            width: size,
+            height: size,
+            left: 0,
+            top: -(size as i32),
+            coverage: vec![255; (size * size) as usize]
+        }
+    }
+
+    #[test]
+    fn field_is_padded_by_the_spread(){
+        let sdf = to_sdf(&square(4), 3);
+
+        assert_eq!((sdf.width, sdf.height), (10, 10));
+        assert_eq!((sdf.left, sdf.top), (-3, -7));
+        assert_eq!(sdf.coverage.len(), 100);
+    }
+
+    #[test]
+    fn field_rises_towards_the_middle_of_the_glyph(){
+        let sdf = to_sdf(&square(8), 4);
+        let row: Vec<u8> = sdf.coverage[8 * 16..9 * 16].to_vec();
+
+        //outside the square, then the edge between columns 3 and 4, then inside
+        assert_eq!(row[0], 0);
+        assert!(row[3] < 128 && row[4] > 128, "{:?}", row);
+        assert!(row.windows(2).take(8).all(|pair| pair[0] <= pair[1]), "{:?}", row);
+        assert_eq!(row[..8].iter().rev().collect::<Vec<_>>(), row[8..].iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn distances_are_euclidean(){
+        let mut grid = vec![FAR; 25];
+        grid[12] = 0.0;
+        distance_transform(&mut grid, 5, 5);
+
+        assert_eq!(grid[0], 8.0);
+        assert_eq!(grid[13], 1.0);
+        assert_eq!(grid[24], 8.0);
+    }
+}
@@ -0,0 +1,29 @@
+// Copies a frame drawn into an sRGB texture to a target which isn't sRGB, see frame.rs
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0)
+var frame_texture: texture_2d<f32>;
+
+// Vertex Shader, one triangle covering the whole target
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Fragment Shader, the frame is the target's size so each pixel is copied as is.  Reading the texture decoded it to
+// linear light, so it's encoded to sRGB again for the target to store
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let colour = textureLoad(frame_texture, vec2<i32>(in.clip_position.xy), 0);
+    let rgb = select(1.055 * pow(colour.rgb, vec3<f32>(1.0 / 2.4)) - 0.055, colour.rgb * 12.92, colour.rgb <= vec3<f32>(0.0031308));
+    return vec4<f32>(rgb, colour.a);
+}
\ No newline at end of file
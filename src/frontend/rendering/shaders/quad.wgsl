@@ -0,0 +1,169 @@
+// Vertex Shader, each instance is a rectangle and the six vertices of a unit quad are expanded to cover it
+
+struct QuadInput{
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) uv_rect: vec4<f32>,
+    @location(3) colour: vec4<f32>,
+    @location(4) flags: u32
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) colour: vec4<f32>,
+    @location(2) @interpolate(flat) flags: u32,
+    // width of uv_rect, the foreground of a tinted colour glyph is this far to the right plus the padding
+    @location(3) @interpolate(flat) uv_width: f32
+};
+
+struct View {
+    projection: mat4x4<f32>,
+    scroll: vec2<f32>,
+    scale: f32,
+    _padding: u32
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+// colours are sRGB, frames are drawn to an sRGB texture which expects linear light and encodes it again when writing
+fn linear_colour(colour: vec4<f32>) -> vec4<f32> {
+    let rgb = colour.rgb;
+    let linear = select(pow((rgb + 0.055) / 1.055, vec3<f32>(2.4)), rgb / 12.92, rgb <= vec3<f32>(0.04045));
+    return vec4<f32>(linear, colour.a);
+}
+
+@group(1) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+// premultiplied sRGB colour glyphs
+@group(1) @binding(2)
+var colour_texture: texture_2d<f32>;
+
+// match QUAD_GLYPH, QUAD_TEXTURE, QUAD_SDF, QUAD_LCD, QUAD_COLOR and QUAD_TINTED in mesh.rs
+let QUAD_GLYPH: u32 = 1u;
+let QUAD_TEXTURE: u32 = 2u;
+let QUAD_SDF: u32 = 4u;
+let QUAD_LCD: u32 = 8u;
+let QUAD_COLOR: u32 = 16u;
+let QUAD_TINTED: u32 = 32u;
+// match GLYPH_PADDING in glyph_atlas.rs
+let GLYPH_PADDING: f32 = 1.0;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    quad: QuadInput
+) -> VertexOutput {
+    // two triangles, counter clockwise on screen: bottom left, bottom right, top right, then bottom left, top right, top left
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 0.0)
+    );
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    out.uv = mix(quad.uv_rect.xy, quad.uv_rect.zw, corner);
+    // colour glyphs are blended with the text's colour in sRGB before they're converted
+    out.colour = select(linear_colour(quad.colour), quad.colour, (quad.flags & QUAD_COLOR) != 0u);
+    out.flags = quad.flags;
+    out.uv_width = quad.uv_rect.z - quad.uv_rect.x;
+    //positions are in logical pixels, see ViewUniform
+    let pixel = (quad.position + corner * quad.size - view.scroll) * view.scale;
+    out.clip_position = view.projection * vec4<f32>(pixel, 0.0, 1.0);
+    return out;
+}
+
+// Fragment Shader, glyphs use the coverage in the atlas as alpha, colour glyphs come from the atlas's colour page and
+// cached panes are bound in place of the atlas
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // sampled for every quad, texture sampling and derivatives have to happen in uniform control flow
+    let texel = textureSample(atlas_texture, atlas_sampler, in.uv);
+    let colour_texel = textureSample(colour_texture, atlas_sampler, in.uv);
+    let foreground_offset = vec2<f32>(in.uv_width + GLYPH_PADDING / f32(textureDimensions(colour_texture).x), 0.0);
+    let foreground = textureSample(colour_texture, atlas_sampler, in.uv + foreground_offset).a;
+    // how much the distance field changes across one surface pixel, so its edge is always a pixel wide
+    let field_width = max(fwidth(texel.r), 0.0001);
+    // a pane's texture was drawn by these pipelines, so it's already premultiplied and in linear light
+    if ((in.flags & QUAD_TEXTURE) != 0u) {
+        return texel * in.colour.a;
+    }
+    // colour glyphs are stored premultiplied in sRGB, so they're unpremultiplied to convert them to linear. their parts
+    // in the text's colour are black, and the text's colour is added by how much of the pixel they cover
+    if ((in.flags & QUAD_COLOR) != 0u) {
+        var premultiplied = colour_texel.rgb;
+        if ((in.flags & QUAD_TINTED) != 0u) {
+            premultiplied = premultiplied + in.colour.rgb * foreground;
+        }
+        let rgb = linear_colour(vec4<f32>(premultiplied / max(colour_texel.a, 0.0001), 1.0)).rgb;
+        return vec4<f32>(rgb * colour_texel.a, colour_texel.a) * in.colour.a;
+    }
+    // subpixel glyphs are drawn by fs_lcd_mask and fs_lcd_colour instead
+    if ((in.flags & QUAD_LCD) != 0u) {
+        return vec4<f32>(0.0);
+    }
+    var alpha = in.colour.a;
+    if ((in.flags & QUAD_GLYPH) != 0u) {
+        alpha = alpha * texel.r;
+    }
+    // the outline is where the distance field crosses 0.5
+    if ((in.flags & QUAD_SDF) != 0u) {
+        alpha = alpha * clamp((texel.r - 0.5) / field_width + 0.5, 0.0, 1.0);
+    }
+    // premultiplied alpha
+    return vec4<f32>(in.colour.rgb * alpha, alpha);
+}
+
+// Subpixel glyphs have three texels of coverage per pixel, and are blended a channel at a time in two draws: the mask
+// darkens each channel under the glyph by its subpixel's coverage, then the colour is added by the same coverage.
+// Alpha is the largest of the three, which is only exact over something opaque
+
+// coverage of the subpixels of the pixel at `uv`, which is the middle texel of the three
+fn subpixel_coverage(uv: vec2<f32>) -> vec3<f32> {
+    let texel = vec2<f32>(1.0 / f32(textureDimensions(atlas_texture).x), 0.0);
+    return vec3<f32>(
+        textureSample(atlas_texture, atlas_sampler, uv - texel).r,
+        textureSample(atlas_texture, atlas_sampler, uv).r,
+        textureSample(atlas_texture, atlas_sampler, uv + texel).r
+    );
+}
+
+@fragment
+fn fs_lcd_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = subpixel_coverage(in.uv) * in.colour.a;
+    // everything else was drawn by fs_main, the mask leaves it alone
+    if ((in.flags & QUAD_LCD) == 0u) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(coverage, max(coverage.r, max(coverage.g, coverage.b)));
+}
+
+@fragment
+fn fs_lcd_colour(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = subpixel_coverage(in.uv) * in.colour.a;
+    if ((in.flags & QUAD_LCD) == 0u) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(in.colour.rgb * coverage, max(coverage.r, max(coverage.g, coverage.b)));
+}
+
+// Subpixel glyphs drawn somewhere transparent, like a cached pane, have nothing to blend each channel with, so they're
+// drawn in one draw with the subpixels' average coverage like a grayscale glyph
+
+@fragment
+fn fs_lcd_grayscale(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = subpixel_coverage(in.uv) * in.colour.a;
+    if ((in.flags & QUAD_LCD) == 0u) {
+        return vec4<f32>(0.0);
+    }
+    let alpha = (coverage.r + coverage.g + coverage.b) / 3.0;
+    return vec4<f32>(in.colour.rgb * alpha, alpha);
+}
\ No newline at end of file
@@ -0,0 +1,125 @@
+// Vertex Shader, each instance is a rounded rectangle and the six vertices of a unit quad are expanded to cover it,
+// its shadow and a pixel either side for antialiasing
+
+struct RectInput{
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    // radius, border width
+    @location(2) shape: vec2<f32>,
+    @location(3) shadow_offset: vec2<f32>,
+    @location(4) shadow_blur: f32,
+    @location(5) fill: vec4<f32>,
+    @location(6) border: vec4<f32>,
+    @location(7) shadow: vec4<f32>
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // position relative to the centre of the rectangle, in logical pixels
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) shape: vec2<f32>,
+    @location(3) shadow_offset: vec2<f32>,
+    @location(4) shadow_blur: f32,
+    @location(5) fill: vec4<f32>,
+    @location(6) border: vec4<f32>,
+    @location(7) shadow: vec4<f32>,
+    // surface pixels per logical pixel, the view uniform is only visible to the vertex stage
+    @location(8) @interpolate(flat) scale: f32
+};
+
+struct View {
+    projection: mat4x4<f32>,
+    scroll: vec2<f32>,
+    scale: f32,
+    _padding: u32
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+// colours are sRGB, frames are drawn to an sRGB texture which expects linear light and encodes it again when writing
+fn linear_colour(colour: vec4<f32>) -> vec4<f32> {
+    let rgb = colour.rgb;
+    let linear = select(pow((rgb + 0.055) / 1.055, vec3<f32>(2.4)), rgb / 12.92, rgb <= vec3<f32>(0.04045));
+    return vec4<f32>(linear, colour.a);
+}
+
+fn premultiply(colour: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(colour.rgb * colour.a, colour.a);
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    rect: RectInput
+) -> VertexOutput {
+    // two triangles, counter clockwise on screen: bottom left, bottom right, top right, then bottom left, top right, top left
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 0.0)
+    );
+    let corner = corners[vertex_index];
+
+    // matches RectInstance::bounds in mesh.rs
+    var bounds_min = rect.position;
+    var bounds_max = rect.position + rect.size;
+    if (rect.shadow.a > 0.0) {
+        bounds_min = min(bounds_min, rect.position + rect.shadow_offset - vec2<f32>(rect.shadow_blur));
+        bounds_max = max(bounds_max, rect.position + rect.size + rect.shadow_offset + vec2<f32>(rect.shadow_blur));
+    }
+    let margin = vec2<f32>(1.0 / view.scale);
+    let position = mix(bounds_min - margin, bounds_max + margin, corner);
+
+    var out: VertexOutput;
+    out.local = position - (rect.position + rect.size * 0.5);
+    out.half_size = rect.size * 0.5;
+    out.shape = rect.shape;
+    out.shadow_offset = rect.shadow_offset;
+    out.shadow_blur = rect.shadow_blur;
+    out.fill = linear_colour(rect.fill);
+    out.border = linear_colour(rect.border);
+    out.shadow = linear_colour(rect.shadow);
+    out.scale = view.scale;
+    //positions are in logical pixels, see ViewUniform
+    let pixel = (position - view.scroll) * view.scale;
+    out.clip_position = view.projection * vec4<f32>(pixel, 0.0, 1.0);
+    return out;
+}
+
+// Fragment Shader, the rectangle, its border and its shadow are each covered where the distance to their edge is
+// negative, with a one pixel ramp for antialiasing
+
+// distance from p to the edge of a rounded rectangle centred on the origin, negative inside
+fn rounded_rect_distance(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// how much of the pixel at `distance` from an edge is inside it, `scale` converts the distance to surface pixels
+fn coverage(distance: f32, scale: f32) -> f32 {
+    return clamp(0.5 - distance * scale, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let radius = in.shape.x;
+    let border_width = in.shape.y;
+
+    let distance = rounded_rect_distance(in.local, in.half_size, radius);
+    let outer = coverage(distance, in.scale);
+    let inner = coverage(distance + border_width, in.scale);
+    let shape = premultiply(in.fill) * inner + premultiply(in.border) * (outer - inner);
+
+    // the shadow fades out over its blur either side of its edge, and is only seen where the rectangle lets it through
+    let shadow_distance = rounded_rect_distance(in.local - in.shadow_offset, in.half_size, radius);
+    let softness = max(in.shadow_blur, 1.0 / in.scale);
+    let shadow = premultiply(in.shadow) * (1.0 - smoothstep(-softness, softness, shadow_distance));
+
+    // premultiplied alpha, the rectangle over its shadow
+    return shape + shadow * (1.0 - shape.a);
+}
@@ -2,25 +2,45 @@
 
 struct VertexInput{
     @location(0) position: vec3<f32>,
-    @location(1) colour: vec3<f32>
+    @location(1) colour: vec4<f32>
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) colour: vec3<f32>
+    @location(0) colour: vec4<f32>
 };
 
+struct View {
+    projection: mat4x4<f32>,
+    scroll: vec2<f32>,
+    scale: f32,
+    _padding: u32
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+// colours are sRGB, frames are drawn to an sRGB texture which expects linear light and encodes it again when writing
+fn linear_colour(colour: vec4<f32>) -> vec4<f32> {
+    let rgb = colour.rgb;
+    let linear = select(pow((rgb + 0.055) / 1.055, vec3<f32>(2.4)), rgb / 12.92, rgb <= vec3<f32>(0.04045));
+    return vec4<f32>(linear, colour.a);
+}
+
 @vertex
 fn vs_main(
     model: VertexInput
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.colour = model.colour;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.colour = linear_colour(model.colour);
+    //positions are in logical pixels, see ViewUniform
+    let pixel = (model.position.xy - view.scroll) * view.scale;
+    out.clip_position = view.projection * vec4<f32>(pixel, model.position.z, 1.0);
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.colour, 1.0);
+    // premultiplied alpha
+    return vec4<f32>(in.colour.rgb * in.colour.a, in.colour.a);
 }
\ No newline at end of file
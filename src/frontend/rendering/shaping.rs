@@ -0,0 +1,587 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::Path;
+
+use unicode_properties::emoji::{EmojiStatus, UnicodeEmoji};
+use unicode_script::{Script, UnicodeScript};
+
+use crate::frontend::rendering::font::Font;
+
+///Fonts tried, in order, for characters the primary font doesn't have.
+const FALLBACK_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSansSymbols-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSansSymbols2-Regular.ttf",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Apple Symbols.ttf",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\seguisym.ttf",
+    //color emoji
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+    "/usr/share/fonts/twemoji/twemoji.ttf",
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+    "C:\\Windows\\Fonts\\seguiemj.ttf"
+];
+
+///How many frames a shaped line is kept after it was last drawn.
+const SHAPE_CACHE_FRAMES: u64 = 120;
+
+///A primary font followed by the fonts used for characters it is missing.
+pub struct FontChain{
+    fonts: Vec<Font>
+}
+
+impl FontChain{
+    ///Creates a chain from `fonts`, the first being the primary font.
+    pub fn new(fonts: Vec<Font>) -> FontChain{
+        FontChain{
+            fonts
+        }
+    }
+
+    ///Loads `font`, or the default font if it's None or can't be loaded, followed by every font from
+    ///[FALLBACK_FONT_PATHS] which exists.
+    pub fn load(font: Option<&Path>) -> Option<FontChain>{
+        let configured = font.and_then(|path| {
+            Font::from_file(path)
+                .map_err(|error| log::warn!("Failed to load font {:?}: {}", path, error))
+                .ok()
+        });
+        let primary = configured.or_else(Font::find_default)?;
+        let mut fonts = vec![primary];
+
+        for path in FALLBACK_FONT_PATHS.iter().map(Path::new){
+            if !path.exists() || fonts.iter().any(|font| font.get_path() == Some(path)){
+                continue;
+            }
+            match Font::from_file(path){
+                Ok(font) => fonts.push(font),
+                Err(error) => log::warn!("Failed to load fallback font {:?}: {}", path, error)
+            }
+        }
+
+        Some(FontChain::new(fonts))
+    }
+
+    ///Get the primary font, used for metrics.
+    pub fn get_primary(&self) -> &Font{
+        &self.fonts[0]
+    }
+
+    ///Get the font at `index` in the chain.
+    pub fn get(&self, index: usize) -> Option<&Font>{
+        self.fonts.get(index)
+    }
+
+    ///Get the number of fonts in the chain.
+    pub fn len(&self) -> usize{
+        self.fonts.len()
+    }
+
+    ///Iterates over the fonts in the chain, primary first.
+    pub fn iter(&self) -> impl Iterator<Item = &Font>{
+        self.fonts.iter()
+    }
+}
+
+///A glyph positioned by the shaper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph{
+    ///Index into the [FontChain] of the font this glyph is from.
+    pub font: usize,
+    pub glyph: u16,
+    ///Offset from the start of the line to this glyph's pen position, in pixels.
+    pub x: f32,
+    ///Offset from the baseline, in pixels. Negative is above the baseline.
+    pub y: f32,
+    ///Byte index in the line of the first character this glyph was made from.
+    pub cluster: usize
+}
+
+///A line of text after shaping.
+#[derive(Debug, Clone, Default)]
+pub struct ShapedLine{
+    pub glyphs: Vec<ShapedGlyph>,
+    ///Total advance of the line, in pixels.
+    pub width: f32
+}
+
+///Returns true for characters which must stay in the same run as the character before them,
+///so marks, joiners and variation selectors are shaped with their base character.
+fn continues_cluster(character: char) -> bool{
+    matches!(character,
+        '\u{0300}'..='\u{036F}' |
+        '\u{1AB0}'..='\u{1AFF}' |
+        '\u{1DC0}'..='\u{1DFF}' |
+        '\u{200C}'..='\u{200D}' |
+        '\u{20D0}'..='\u{20FF}' |
+        '\u{FE00}'..='\u{FE0F}' |
+        '\u{FE20}'..='\u{FE2F}' |
+        '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+///Returns true for characters drawn as emoji rather than as text: those which are emoji by default, and any emoji
+///followed by the emoji variation selector.
+fn is_emoji_presentation(character: char, next: Option<char>) -> bool{
+    match next{
+        Some('\u{FE0E}') => false,
+        Some('\u{FE0F}') => character.is_emoji_char(),
+        _ => matches!(character.emoji_status(),
+            EmojiStatus::EmojiPresentation | EmojiStatus::EmojiPresentationAndModifierBase |
+            EmojiStatus::EmojiPresentationAndEmojiComponent | EmojiStatus::EmojiPresentationAndModifierAndEmojiComponent
+        )
+    }
+}
+
+///How a character affects the direction text is laid out in, a simplified set of the bidi classes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BidiClass{
+    Left,
+    Right,
+    EuropeanNumber,
+    ArabicNumber,
+    Neutral
+}
+
+///Returns true for scripts written right to left.
+fn is_right_to_left(script: Script) -> bool{
+    matches!(script,
+        Script::Arabic | Script::Hebrew | Script::Syriac | Script::Thaana | Script::Nko | Script::Samaritan |
+        Script::Mandaic | Script::Adlam | Script::Hanifi_Rohingya | Script::Yezidi
+    )
+}
+
+///Gets the bidi class of a character from its script, since there's no table of the real classes.
+fn bidi_class(character: char) -> BidiClass{
+    let script = character.script();
+    match character{
+        '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}' => BidiClass::ArabicNumber,
+        _ if character.is_numeric() && !is_right_to_left(script) => BidiClass::EuropeanNumber,
+        _ if is_right_to_left(script) => BidiClass::Right,
+        _ if character.is_alphabetic() || !matches!(script, Script::Common | Script::Inherited | Script::Unknown) => BidiClass::Left,
+        _ => BidiClass::Neutral
+    }
+}
+
+///Resolves the embedding level of each character of `text`: 0 for left to right, 1 for right to left and 2 for
+///numbers in right to left text.  Lines are always laid out left to right, like code, so this is a simplified bidi
+///algorithm without explicit embeddings: numbers after left to right text are left to right (rule W7), and neutral
+///characters take the direction of the text around them if it's the same on both sides, otherwise left to right (N1).
+fn bidi_levels(text: &str) -> Vec<u8>{
+    let mut classes: Vec<BidiClass> = Vec::new();
+    let mut last_strong = BidiClass::Left;
+    for character in text.chars(){
+        let class = match bidi_class(character){
+            //marks take the class of their base character (W1)
+            _ if continues_cluster(character) || character.script() == Script::Inherited => classes.last().copied().unwrap_or(BidiClass::Neutral),
+            BidiClass::EuropeanNumber if last_strong == BidiClass::Left => BidiClass::Left,
+            class => class
+        };
+        if matches!(class, BidiClass::Left | BidiClass::Right){
+            last_strong = class;
+        }
+        classes.push(class);
+    }
+
+    //numbers count as right to left for the neutrals next to them
+    let is_right = |class: BidiClass| class != BidiClass::Left;
+    let mut levels = vec![0; classes.len()];
+    let mut index = 0;
+    while index < classes.len(){
+        match classes[index]{
+            BidiClass::Left => levels[index] = 0,
+            BidiClass::Right => levels[index] = 1,
+            BidiClass::EuropeanNumber | BidiClass::ArabicNumber => levels[index] = 2,
+            BidiClass::Neutral => {
+                let end = classes[index..].iter().position(|&class| class != BidiClass::Neutral).map_or(classes.len(), |length| index + length);
+                let before = index.checked_sub(1).is_some_and(|before| is_right(classes[before]));
+                let after = classes.get(end).is_some_and(|&after| is_right(after));
+                let level = if before && after { 1 } else { 0 };
+                levels[index..end].fill(level);
+                index = end;
+                continue;
+            }
+        }
+        index += 1;
+    }
+
+    levels
+}
+
+///A part of a line shaped in one go, with one font, one script and one direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun{
+    ///Index into the [FontChain] of the font the run is shaped with.
+    pub font: usize,
+    ///Byte range of the run in the line.
+    pub range: Range<usize>,
+    pub script: Script,
+    ///Bidi embedding level, odd levels are right to left.
+    pub level: u8
+}
+
+///Splits `text` into runs which can each be shaped with one font, in one script and direction, in logical order.
+///`has_glyph(font, character)` tells whether font number `font` of `font_count` covers `character`, and
+///`is_color_font(font)` whether it's a color font like an emoji font.
+///Each letter gets the first font in the chain which has it, so text goes back to the primary font after a fallback.
+///Emoji try the color fonts first, since the symbol fonts before them in the chain often have a plain version.
+///Spaces and punctuation stay in the run before them while its font covers them.
+///Characters no font has are given to the primary font, which draws them as its missing glyph.
+pub fn itemize(text: &str, font_count: usize, has_glyph: impl Fn(usize, char) -> bool, is_color_font: impl Fn(usize) -> bool) -> Vec<TextRun>{
+    let levels = bidi_levels(text);
+
+    //spaces, punctuation and marks are in the script of the text before them at the same level, or after them if
+    //that's at a different level, so they don't split runs
+    let characters: Vec<(usize, char)> = text.char_indices().collect();
+    let mut scripts: Vec<Option<Script>> = characters.iter().map(|(_, character)| {
+        match character.script(){
+            Script::Common | Script::Inherited | Script::Unknown => None,
+            script => Some(script)
+        }
+    }).collect();
+    for index in 1..scripts.len(){
+        if scripts[index].is_none() && levels[index] == levels[index - 1]{
+            scripts[index] = scripts[index - 1];
+        }
+    }
+    for index in (0..scripts.len().saturating_sub(1)).rev(){
+        if scripts[index].is_none() && levels[index] == levels[index + 1]{
+            scripts[index] = scripts[index + 1];
+        }
+    }
+
+    let mut runs: Vec<TextRun> = Vec::new();
+    for (position, (index, character)) in characters.iter().copied().enumerate(){
+        let end = index + character.len_utf8();
+        let script = scripts[position].unwrap_or(Script::Common);
+        let level = levels[position];
+
+        let emoji = is_emoji_presentation(character, characters.get(position + 1).map(|(_, next)| *next));
+        let font = first_font(font_count, &has_glyph, |font| emoji && is_color_font(font), character);
+
+        if let Some(run) = runs.last_mut(){
+            let is_letter = emoji || !matches!(character.script(), Script::Common | Script::Inherited | Script::Unknown);
+            let same_run = run.script == script && run.level == level && (!is_letter || run.font == font);
+            if continues_cluster(character) || (same_run && has_glyph(run.font, character)){
+                run.range.end = end;
+                continue;
+            }
+        }
+
+        match runs.last_mut(){
+            Some(run) if run.font == font && run.script == script && run.level == level => run.range.end = end,
+            _ => runs.push(TextRun{ font, range: index..end, script, level })
+        }
+    }
+
+    runs
+}
+
+///Gets the first font of the chain which has `character`, trying the fonts it should `prefer` first, or the primary
+///font if none has it.
+fn first_font(font_count: usize, has_glyph: impl Fn(usize, char) -> bool, prefer: impl Fn(usize) -> bool, character: char) -> usize{
+    let preferred = (0..font_count).filter(|&font| prefer(font));
+    let others = (0..font_count).filter(|&font| !prefer(font));
+    preferred.chain(others).find(|&font| has_glyph(font, character)).unwrap_or(0)
+}
+
+///Puts runs in the order they're drawn from left to right by reversing each sequence of runs at a level or higher,
+///from the highest level down to 1 (rule L2 of the bidi algorithm).
+fn visual_order(runs: &mut [TextRun]){
+    let highest = runs.iter().map(|run| run.level).max().unwrap_or(0);
+    for level in (1..=highest).rev(){
+        let mut start = 0;
+        while start < runs.len(){
+            if runs[start].level < level{
+                start += 1;
+                continue;
+            }
+            let end = runs[start..].iter().position(|run| run.level < level).map_or(runs.len(), |length| start + length);
+            runs[start..end].reverse();
+            start = end;
+        }
+    }
+}
+
+///Shapes `text` with `font` at `size` pixels per em, appending the glyphs to `line` in the order they're drawn.
+///Byte offsets in the glyph clusters are relative to `text`, `cluster_offset` is added to them.
+///Odd `level`s are shaped right to left.
+fn shape_run(line: &mut ShapedLine, font: &Font, font_index: usize, text: &str, cluster_offset: usize, level: u8, size: f32){
+    let face = match font.data().and_then(|data| rustybuzz::Face::from_slice(data, 0)){
+        Some(face) => face,
+        None => return shape_run_simple(line, font, font_index, text, cluster_offset, level, size)
+    };
+    let scale = size / face.units_per_em() as f32;
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_direction(if level % 2 == 1 { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+    buffer.guess_segment_properties();
+
+    //the default features include ligatures, contextual alternates and kerning
+    let output = rustybuzz::shape(&face, &[], buffer);
+
+    for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()){
+        line.glyphs.push(ShapedGlyph{
+            font: font_index,
+            glyph: info.glyph_id as u16,
+            x: line.width + position.x_offset as f32 * scale,
+            y: -position.y_offset as f32 * scale,
+            cluster: cluster_offset + info.cluster as usize
+        });
+        line.width += position.x_advance as f32 * scale;
+    }
+}
+
+///Places one glyph per character at its advance, for bitmap fonts which have no shaping tables.
+fn shape_run_simple(line: &mut ShapedLine, font: &Font, font_index: usize, text: &str, cluster_offset: usize, level: u8, size: f32){
+    let mut characters: Vec<(usize, char)> = text.char_indices().collect();
+    if level % 2 == 1{
+        characters.reverse();
+    }
+    for (offset, character) in characters{
+        let glyph = match font.glyph_id(character){
+            Some(glyph) => glyph,
+            None => continue
+        };
+        line.glyphs.push(ShapedGlyph{
+            font: font_index,
+            glyph,
+            x: line.width,
+            y: 0.0,
+            cluster: cluster_offset + offset
+        });
+        line.width += font.advance(glyph, size);
+    }
+}
+
+///Shapes a line of text, falling back through `fonts` for characters the primary font doesn't have.
+///The glyphs are in the order they're drawn from left to right, which isn't the order of the text where it's right to left.
+pub fn shape_line(fonts: &FontChain, text: &str, size: f32) -> ShapedLine{
+    let mut line = ShapedLine::default();
+
+    let mut runs = itemize(text, fonts.len(), |font, character| {
+        fonts.get(font).and_then(|font| font.glyph_id(character)).is_some()
+    }, |font| fonts.get(font).is_some_and(Font::has_color_glyphs));
+    visual_order(&mut runs);
+    for run in runs{
+        let font = fonts.get(run.font).expect("Runs only use fonts from the chain!");
+        shape_run(&mut line, font, run.font, &text[run.range.clone()], run.range.start, run.level, size);
+    }
+
+    line
+}
+
+///Hashes a line's contents and size for the shaped line cache.
+fn content_hash(text: &str, size: f32) -> u64{
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    size.to_bits().hash(&mut hasher);
+    hasher.finish()
+}
+
+struct CachedLine{
+    text: String,
+    size: f32,
+    line: ShapedLine,
+    last_used: u64
+}
+
+///Shapes lines of text, caching the results by content so unchanged lines aren't reshaped every frame.
+pub struct Shaper{
+    cache: HashMap<u64, CachedLine>,
+    frame: u64
+}
+
+impl Shaper{
+    pub fn new() -> Shaper{
+        Shaper{
+            cache: HashMap::new(),
+            frame: 0
+        }
+    }
+
+    ///Starts a new frame, dropping lines which haven't been drawn for [SHAPE_CACHE_FRAMES] frames.
+    pub fn begin_frame(&mut self){
+        self.frame += 1;
+        let frame = self.frame;
+        self.cache.retain(|_, cached| cached.last_used + SHAPE_CACHE_FRAMES >= frame);
+    }
+
+    ///Gets `text` shaped at `size` pixels per em, from the cache if it was shaped recently.
+    pub fn shape(&mut self, fonts: &FontChain, text: &str, size: f32) -> &ShapedLine{
+        let frame = self.frame;
+        let cached = self.cache.entry(content_hash(text, size))
+            .or_insert_with(|| CachedLine{
+                text: String::new(),
+                size: f32::NAN,
+                line: ShapedLine::default(),
+                last_used: frame
+            });
+
+        //a different line with the same hash replaces the old one
+        if cached.text != text || cached.size != size{
+            cached.text = text.to_string();
+            cached.size = size;
+            cached.line = shape_line(fonts, text, size);
+        }
+        cached.last_used = frame;
+
+        &cached.line
+    }
+
+    ///Get the number of lines in the cache.
+    #[cfg(test)]
+    pub fn cached_lines(&self) -> usize{
+        self.cache.len()
+    }
+}
+
+impl Default for Shaper{
+    fn default() -> Self{
+        Shaper::new()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //font 0 covers ascii, font 1 covers everything
+    fn ascii_then_all(font: usize, character: char) -> bool{
+        font == 1 || character.is_ascii()
+    }
+
+    //every font covers everything
+    fn all(_font: usize, _character: char) -> bool{
+        true
+    }
+
+    fn no_color_fonts(_font: usize) -> bool{
+        false
+    }
+
+    fn fonts_and_ranges(runs: Vec<TextRun>) -> Vec<(usize, Range<usize>)>{
+        runs.into_iter().map(|run| (run.font, run.range)).collect()
+    }
+
+    fn levels_and_ranges(runs: Vec<TextRun>) -> Vec<(u8, Range<usize>)>{
+        runs.into_iter().map(|run| (run.level, run.range)).collect()
+    }
+
+    #[test]
+    fn text_one_font_covers_is_one_run(){
+        assert_eq!(fonts_and_ranges(itemize("fn main()", 2, ascii_then_all, no_color_fonts)), vec![(0, 0..9)]);
+    }
+
+    #[test]
+    fn missing_characters_fall_back(){
+        let text = "a 日本 b";
+        assert_eq!(fonts_and_ranges(itemize(text, 2, ascii_then_all, no_color_fonts)), vec![(0, 0..2), (1, 2..9), (0, 9..text.len())]);
+    }
+
+    #[test]
+    fn later_characters_go_back_to_the_primary_font(){
+        //the fallback font has the space and 'b' too, but only the space stays in its run
+        assert_eq!(fonts_and_ranges(itemize("日 b", 2, ascii_then_all, no_color_fonts)), vec![(1, 0..4), (0, 4..5)]);
+    }
+
+    #[test]
+    fn latin_and_cjk_are_split_by_script(){
+        //one font covering both still shapes each script separately, punctuation stays with the text before it
+        let text = "let 名前 = x;";
+        let runs = itemize(text, 1, all, no_color_fonts);
+        assert_eq!(runs.iter().map(|run| run.script).collect::<Vec<_>>(), vec![Script::Latin, Script::Han, Script::Latin]);
+        assert_eq!(runs.iter().map(|run| &text[run.range.clone()]).collect::<Vec<_>>(), vec!["let ", "名前 = ", "x;"]);
+    }
+
+    #[test]
+    fn right_to_left_text_is_split_by_level(){
+        let text = "abc שלום def";
+        let hebrew = 4..4 + "שלום".len();
+        assert_eq!(levels_and_ranges(itemize(text, 1, all, no_color_fonts)), vec![(0, 0..4), (1, hebrew.clone()), (0, hebrew.end..text.len())]);
+    }
+
+    #[test]
+    fn numbers_in_right_to_left_text_are_left_to_right(){
+        //the space between the hebrew and the number is right to left like both sides
+        let text = "שלום 123";
+        let number = text.len() - 3;
+        assert_eq!(levels_and_ranges(itemize(text, 1, all, no_color_fonts)), vec![(1, 0..number), (2, number..text.len())]);
+
+        //after left to right text a number is just left to right
+        assert_eq!(levels_and_ranges(itemize("abc 123", 1, all, no_color_fonts)), vec![(0, 0..7)]);
+    }
+
+    #[test]
+    fn right_to_left_runs_are_reversed_for_drawing(){
+        let mut runs: Vec<TextRun> = [0, 1, 2, 1, 0].iter().enumerate().map(|(index, &level)| {
+            TextRun{ font: 0, range: index..index + 1, script: Script::Common, level }
+        }).collect();
+        visual_order(&mut runs);
+        assert_eq!(runs.iter().map(|run| run.range.start).collect::<Vec<_>>(), vec![0, 3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn emoji_try_color_fonts_first(){
+        //font 1 is a symbol font with plain emoji, font 2 is the color emoji font
+        let has_glyph = |font: usize, character: char| font > 0 || character.is_ascii();
+        let is_color_font = |font: usize| font == 2;
+
+        assert_eq!(fonts_and_ranges(itemize("a😀", 3, has_glyph, is_color_font)), vec![(0, 0..1), (2, 1..5)]);
+        //☺ is text by default, unless it's followed by the emoji variation selector
+        assert_eq!(fonts_and_ranges(itemize("☺", 3, has_glyph, is_color_font)), vec![(1, 0..3)]);
+        assert_eq!(fonts_and_ranges(itemize("☺\u{FE0F}", 3, has_glyph, is_color_font)), vec![(2, 0..6)]);
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_base(){
+        //font 0 has no combining acute accent, but it mustn't be split from its 'e'
+        let text = "e\u{0301}x";
+        assert_eq!(fonts_and_ranges(itemize(text, 2, ascii_then_all, no_color_fonts)), vec![(0, 0..text.len())]);
+    }
+
+    #[test]
+    fn uncovered_characters_use_the_primary_font(){
+        assert_eq!(fonts_and_ranges(itemize("a\u{10FFFD}", 2, |_, character| character.is_ascii(), no_color_fonts)), vec![(0, 0.."a\u{10FFFD}".len())]);
+    }
+
+    #[test]
+    fn bitmap_fonts_are_shaped_a_character_at_a_time(){
+        let bdf = "STARTFONT 2.1\nSIZE 4 75 75\nSTARTPROPERTIES 2\nFONT_ASCENT 3\nFONT_DESCENT 1\nENDPROPERTIES\nCHARS 1\n\
+            STARTCHAR x\nENCODING 120\nDWIDTH 3 0\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\nENDFONT\n";
+        let fonts = FontChain::new(vec![Font::from_bytes(bdf.as_bytes().to_vec()).unwrap()]);
+
+        let line = shape_line(&fonts, "xx", 8.0);
+        assert_eq!(line.glyphs.iter().map(|glyph| (glyph.x, glyph.cluster)).collect::<Vec<_>>(), vec![(0.0, 0), (6.0, 1)]);
+        assert_eq!(line.width, 12.0);
+    }
+
+    #[test]
+    fn shaper_caches_by_content(){
+        let fonts = FontChain::new(Vec::new());
+        let mut shaper = Shaper::new();
+
+        shaper.shape(&fonts, "", 14.0);
+        shaper.shape(&fonts, "", 14.0);
+        assert_eq!(shaper.cached_lines(), 1);
+        shaper.shape(&fonts, "", 16.0);
+        assert_eq!(shaper.cached_lines(), 2);
+
+        for _ in 0..=SHAPE_CACHE_FRAMES{
+            shaper.begin_frame();
+        }
+        assert_eq!(shaper.cached_lines(), 0);
+    }
+}
@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use crate::frontend::graphics_config::{FallbackAdapter, GraphicsConfig};
+use crate::frontend::rendering::image::RgbaImage;
+use crate::frontend::wgpu_state::{WGPUState, OFFSCREEN_FORMAT};
+
+///Set this environment variable to write the rendered images as the new expected images instead of comparing with them.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "DIGIT_UPDATE_SNAPSHOTS";
+
+///How far rendered images may be from the expected ones.  Software rasterizers differ slightly in how they
+///round colours and cover edges, so exact comparisons would fail on machines other than the one that made the image.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance{
+    ///Largest difference in any channel for a pixel to still count as the same.
+    pub channel: u8,
+    ///How many pixels may differ by more than `channel`.
+    pub pixels: usize
+}
+
+impl Default for Tolerance{
+    fn default() -> Self{
+        Tolerance{
+            channel: 2,
+            pixels: 0
+        }
+    }
+}
+
+///How two images differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageDiff{
+    ///Pixels with at least one channel further apart than the tolerance.
+    pub differing_pixels: usize,
+    ///Largest difference in any channel of any pixel.
+    pub max_difference: u8,
+    ///Differing pixels in red over a faded copy of the expected image, to see where the differences are.
+    pub image: RgbaImage
+}
+
+///Compares `actual` with `expected` pixel by pixel, counting pixels where a channel differs by more than `channel_tolerance`.
+pub fn diff_images(actual: &RgbaImage, expected: &RgbaImage, channel_tolerance: u8) -> Result<ImageDiff, String>{
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        return Err(format!("Image is {}x{}, expected {}x{}", actual.width, actual.height, expected.width, expected.height));
+    }
+
+    let mut diff = ImageDiff{
+        differing_pixels: 0,
+        max_difference: 0,
+        image: RgbaImage::new(actual.width, actual.height)
+    };
+
+    for (index, (a, e)) in actual.pixels.chunks_exact(4).zip(expected.pixels.chunks_exact(4)).enumerate() {
+        let difference = a.iter().zip(e).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+        diff.max_difference = diff.max_difference.max(difference);
+
+        let pixel = if difference > channel_tolerance {
+            diff.differing_pixels += 1;
+            [255, 0, 0, 255]
+        }else{
+            [e[0] / 4, e[1] / 4, e[2] / 4, 255]
+        };
+        diff.image.pixels[index * 4..index * 4 + 4].copy_from_slice(&pixel);
+    }
+
+    Ok(diff)
+}
+
+///Get the directory expected images are kept in.
+pub fn snapshot_dir() -> PathBuf{
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("frontend").join("rendering").join("snapshots")
+}
+
+///Compares `actual` with the expected image `<name>.png` in [snapshot_dir].  On a mismatch the rendered image and a diff
+///are written next to it as `<name>.actual.png` and `<name>.diff.png`, and an error describing the difference is returned.
+///Only when [UPDATE_SNAPSHOTS_VAR] is set is `actual` written as the expected image.  A missing expected image is an
+///error like a mismatch, so a mistyped name can't quietly become a new baseline.
+pub fn check_snapshot(name: &str, actual: &RgbaImage, tolerance: Tolerance) -> Result<(), String>{
+    let dir = snapshot_dir();
+    let expected_path = dir.join(format!("{}.png", name));
+    let actual_path = dir.join(format!("{}.actual.png", name));
+    let diff_path = dir.join(format!("{}.diff.png", name));
+
+    let write = |path: &Path, image: &RgbaImage| {
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| image.save_png(path))
+            .map_err(|error| format!("Failed to write {}: {}", path.display(), error))
+    };
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        return write(&expected_path, actual);
+    }
+    //tests must not pass by writing images nobody looked at
+    if !expected_path.exists() {
+        write(&actual_path, actual)?;
+        return Err(format!(
+            "Snapshot {} has no expected image, wrote {} to review. Set {} to accept it.",
+            name, actual_path.display(), UPDATE_SNAPSHOTS_VAR
+        ));
+    }
+
+    let expected = RgbaImage::load_png(&expected_path).map_err(|error| format!("Failed to read {}: {}", expected_path.display(), error))?;
+    let diff = match diff_images(actual, &expected, tolerance.channel) {
+        Ok(diff) => diff,
+        Err(error) => {
+            write(&actual_path, actual)?;
+            return Err(format!("Snapshot {}: {}, wrote {}", name, error, actual_path.display()));
+        }
+    };
+
+    if diff.differing_pixels > tolerance.pixels {
+        write(&actual_path, actual)?;
+        write(&diff_path, &diff.image)?;
+        return Err(format!(
+            "Snapshot {}: {} pixels differ by more than {} (largest difference {}), wrote {} and {}. Set {} to accept the new image.",
+            name, diff.differing_pixels, tolerance.channel, diff.max_difference, actual_path.display(), diff_path.display(), UPDATE_SNAPSHOTS_VAR
+        ));
+    }
+
+    //a passing run cleans up after an earlier failure
+    let _ = std::fs::remove_file(&actual_path);
+    let _ = std::fs::remove_file(&diff_path);
+    Ok(())
+}
+
+///Creates an offscreen wgpu_state on the software fallback adapter, so snapshots look the same on every machine.
+///Returns None if there's no fallback adapter here, so tests can be skipped on machines without one.  On CI, where
+///skipping would let every GPU test pass without running, it panics instead.
+pub fn offscreen_state(size: (i32, i32)) -> Option<WGPUState>{
+    offscreen_state_with_format(size, OFFSCREEN_FORMAT)
+}
+
+///Like [offscreen_state], rendering to a texture of `format`.
+pub fn offscreen_state_with_format(size: (i32, i32), format: wgpu::TextureFormat) -> Option<WGPUState>{
+    offscreen_state_with_config(size, format, GraphicsConfig::default())
+}
+
+///Like [offscreen_state_with_format], with the text and glyph options from `graphics_config`.  The adapter options are
+///replaced, it's always the fallback adapter.
+pub fn offscreen_state_with_config(size: (i32, i32), format: wgpu::TextureFormat, graphics_config: GraphicsConfig) -> Option<WGPUState>{
+    let graphics_config = GraphicsConfig{
+        fallback_adapter: FallbackAdapter::Force,
+        ..graphics_config
+    };
+
+    match pollster::block_on(WGPUState::new_offscreen(size, 1.0, format, &graphics_config)) {
+        Ok(wgpu_state) => Some(wgpu_state),
+        Err(error) if std::env::var_os("CI").is_some() => panic!("No fallback adapter for GPU tests on CI: {}", error),
+        Err(error) => {
+            log::warn!("Skipping GPU test, no fallback adapter: {}", error);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage{
+        let mut image = RgbaImage::new(width, height);
+        for pixel_bytes in image.pixels.chunks_exact_mut(4) {
+            pixel_bytes.copy_from_slice(&pixel);
+        }
+        image
+    }
+
+    #[test]
+    fn small_differences_are_within_tolerance(){
+        let diff = diff_images(&filled(4, 4, [100, 100, 100, 255]), &filled(4, 4, [102, 99, 100, 255]), 2).unwrap();
+        assert_eq!(diff.differing_pixels, 0);
+        assert_eq!(diff.max_difference, 2);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted_and_marked(){
+        let expected = filled(4, 4, [0, 0, 0, 255]);
+        let mut actual = expected.clone();
+        actual.set_pixel(1, 2, [200, 0, 0, 255]);
+
+        let diff = diff_images(&actual, &expected, 2).unwrap();
+        assert_eq!(diff.differing_pixels, 1);
+        assert_eq!(diff.max_difference, 200);
+        assert_eq!(diff.image.get_pixel(1, 2), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn different_sizes_are_an_error(){
+        assert!(diff_images(&RgbaImage::new(2, 2), &RgbaImage::new(2, 3), 0).is_err());
+    }
+
+    #[test]
+    fn missing_snapshots_fail_without_writing_a_baseline(){
+        if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+            return;
+        }
+        let name = format!("missing-snapshot-test-{}", std::process::id());
+        let result = check_snapshot(&name, &RgbaImage::new(2, 2), Tolerance::default());
+        let _ = std::fs::remove_file(snapshot_dir().join(format!("{}.actual.png", name)));
+
+        assert!(result.is_err());
+        assert!(!snapshot_dir().join(format!("{}.png", name)).exists());
+    }
+}
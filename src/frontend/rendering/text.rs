@@ -0,0 +1,199 @@
+use crate::frontend::graphics_config::{GraphicsConfig, GlyphMode};
+use crate::frontend::rendering::glyph_atlas::{GlyphFormat, GlyphKey, SDF_SIZE};
+use crate::frontend::rendering::mesh::QuadInstance;
+use crate::frontend::rendering::render_state::RenderState;
+use crate::frontend::rendering::shaping::{FontChain, Shaper};
+use crate::frontend::wgpu_state::WGPUState;
+
+///A line of text laid out with its pen starting at (0, 0) on the baseline, ready to be placed anywhere.
+#[derive(Debug, Clone, Default)]
+pub struct TextMesh{
+    ///One quad per glyph, positioned relative to the pen's starting point.
+    pub quads: Vec<QuadInstance>,
+    ///The atlas glyphs the mesh uses, so they can be kept in the atlas while the mesh is reused.
+    pub glyphs: Vec<GlyphKey>,
+    ///Pen advance over the whole line, in logical pixels.
+    pub width: f32
+}
+
+impl TextMesh{
+    ///Adds this mesh to `render_state` with its pen starting at `origin`.
+    ///`origin` should be on a whole surface pixel or the glyphs will be blurred.
+    pub fn add_to(&self, render_state: &mut RenderState, origin: [f32; 2]){
+        render_state.add_quads(self.quads.iter().map(|quad| QuadInstance{
+            position: [quad.position[0] + origin[0], quad.position[1] + origin[1]],
+            ..*quad
+        }));
+    }
+}
+
+///Rounds `position` to the nearest whole surface pixel.
+pub fn snap_to_pixel(position: f32, content_scale: f32) -> f32{
+    (position * content_scale).round() / content_scale
+}
+
+///Text smaller than this many surface pixels per em is drawn from bitmaps even in [GlyphMode::Sdf], a distance field
+///scaled that far down loses the thin stems and sharp corners a bitmap rasterized at the size keeps.
+pub const SDF_MIN_SIZE: f32 = 20.0;
+
+///Subpixel text is turned off from this content scale up, where pixels are small enough that grayscale looks as sharp.
+pub const SUBPIXEL_MAX_SCALE: f32 = 2.0;
+
+///Picks the atlas format for glyphs `size` pixels per em drawn at `content_scale` with `config`.
+///`transparent` is true if the window can be seen through, which turns subpixel text off.
+pub fn glyph_format(config: &GraphicsConfig, size: f32, content_scale: f32, transparent: bool) -> GlyphFormat{
+    match (config.glyph_mode, config.subpixel){
+        (GlyphMode::Sdf, _) if size * content_scale >= SDF_MIN_SIZE => GlyphFormat::Sdf,
+        (_, Some(order)) if content_scale < SUBPIXEL_MAX_SCALE && !transparent => GlyphFormat::Lcd(order),
+        _ => GlyphFormat::Bitmap
+    }
+}
+
+///Shapes `text` with `shaper` and builds a quad instance for each glyph, rasterizing glyphs into the atlas as needed.
+///`size` is logical pixels per em.  Bitmaps are rasterized at the content scale, so they stay sharp on HiDPI
+///surfaces and are rasterized again when the scale changes.
+pub fn build_text_mesh(wgpu_state: &mut WGPUState, shaper: &mut Shaper, fonts: &FontChain, text: &str, size: f32, color: [f32; 4]) -> TextMesh{
+    let scale = wgpu_state.get_view().get_content_scale();
+    let format = glyph_format(wgpu_state.get_graphics_config(), size, scale, wgpu_state.is_transparent());
+    let line = shaper.shape(fonts, text, size);
+    let mut mesh = TextMesh{
+        width: line.width,
+        ..TextMesh::default()
+    };
+
+    for shaped in &line.glyphs{
+        let font = match fonts.get(shaped.font){
+            Some(font) => font,
+            None => continue
+        };
+
+        let key = GlyphKey{
+            font: shaped.font,
+            glyph: shaped.glyph,
+            size: match format{
+                GlyphFormat::Sdf => SDF_SIZE,
+                _ => (size * scale).round() as u32
+            },
+            format
+        };
+
+        if let Some(glyph) = wgpu_state.get_glyph(font, key){
+            let quad = match format{
+                GlyphFormat::Bitmap | GlyphFormat::Lcd(_) => {
+                    //the bitmap is in surface pixels, snapped to whole ones so it isn't blurred by filtering
+                    let x = snap_to_pixel(shaped.x + glyph.left as f32 / scale, scale);
+                    let y = snap_to_pixel(shaped.y + glyph.top as f32 / scale, scale);
+                    let glyph_size = [glyph.width as f32 / scale, glyph.height as f32 / scale];
+                    match format{
+                        _ if glyph.tinted => QuadInstance::tinted_color_glyph([x, y], glyph_size, glyph.uv_min, glyph.uv_max, color),
+                        _ if glyph.color => QuadInstance::color_glyph([x, y], glyph_size, glyph.uv_min, glyph.uv_max, color[3]),
+                        GlyphFormat::Lcd(_) => QuadInstance::lcd_glyph([x, y], glyph_size, glyph.uv_min, glyph.uv_max, color),
+                        _ => QuadInstance::glyph([x, y], glyph_size, glyph.uv_min, glyph.uv_max, color)
+                    }
+                },
+                GlyphFormat::Sdf => {
+                    //the field is filtered anyway, so it's scaled from SDF_SIZE and placed exactly
+                    let factor = size / SDF_SIZE as f32;
+                    let position = [shaped.x + glyph.left as f32 * factor, shaped.y + glyph.top as f32 * factor];
+                    let glyph_size = [glyph.width as f32 * factor, glyph.height as f32 * factor];
+                    if glyph.tinted {
+                        QuadInstance::tinted_color_glyph(position, glyph_size, glyph.uv_min, glyph.uv_max, color)
+                    }else if glyph.color {
+                        QuadInstance::color_glyph(position, glyph_size, glyph.uv_min, glyph.uv_max, color[3])
+                    }else{
+                        QuadInstance::sdf_glyph(position, glyph_size, glyph.uv_min, glyph.uv_max, color)
+                    }
+                }
+            };
+
+            mesh.quads.push(quad);
+            mesh.glyphs.push(key);
+        }
+    }
+
+    mesh
+}
+
+///Adds a line of text to `render_state`, shaping it with `shaper` and rasterizing glyphs into the atlas as needed.
+///`origin` is the pen position on the baseline in logical pixels from the top left of the surface, and `size` is pixels per em.
+///Returns the pen's x position after the last glyph.
+#[allow(clippy::too_many_arguments)]
+pub fn add_text(render_state: &mut RenderState, wgpu_state: &mut WGPUState, shaper: &mut Shaper, fonts: &FontChain, text: &str, origin: [f32; 2], size: f32, color: [f32; 4]) -> f32{
+    let scale = wgpu_state.get_view().get_content_scale();
+    let mesh = build_text_mesh(wgpu_state, shaper, fonts, text, size, color);
+    mesh.add_to(render_state, [snap_to_pixel(origin[0], scale), snap_to_pixel(origin[1], scale)]);
+
+    origin[0] + mesh.width
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::frontend::graphics_config::SubpixelOrder;
+    use crate::frontend::rendering::snapshot;
+
+    #[test]
+    fn moved_mesh_is_offset_by_origin(){
+        let mesh = TextMesh{
+            quads: vec![QuadInstance::glyph([1.0, 2.0], [3.0, 4.0], [0.0, 0.0], [1.0, 1.0], [1.0, 1.0, 1.0, 1.0]); 2],
+            ..TextMesh::default()
+        };
+
+        let mut render_state = RenderState::new();
+        mesh.add_to(&mut render_state, [10.0, 20.0]);
+
+        let quads = &render_state.get_layer_content(render_state.get_layer()).quads;
+        assert_eq!(quads.len(), 2);
+        assert_eq!(quads[1].position, [11.0, 22.0]);
+        assert_eq!(quads[1].size, [3.0, 4.0]);
+    }
+
+    #[test]
+    fn small_text_falls_back_to_bitmaps(){
+        let bitmap = GraphicsConfig::default();
+        let sdf = GraphicsConfig{ glyph_mode: GlyphMode::Sdf, ..GraphicsConfig::default() };
+
+        assert_eq!(glyph_format(&bitmap, 64.0, 1.0, false), GlyphFormat::Bitmap);
+        assert_eq!(glyph_format(&sdf, 12.0, 1.0, false), GlyphFormat::Bitmap);
+        assert_eq!(glyph_format(&sdf, 12.0, 2.0, false), GlyphFormat::Sdf);
+        assert_eq!(glyph_format(&sdf, 32.0, 1.0, false), GlyphFormat::Sdf);
+    }
+
+    #[test]
+    fn subpixel_text_is_off_at_high_scales_and_in_transparent_windows(){
+        let config = GraphicsConfig{ subpixel: Some(SubpixelOrder::Bgr), ..GraphicsConfig::default() };
+
+        assert_eq!(glyph_format(&config, 14.0, 1.0, false), GlyphFormat::Lcd(SubpixelOrder::Bgr));
+        assert_eq!(glyph_format(&config, 14.0, 2.0, false), GlyphFormat::Bitmap);
+        assert_eq!(glyph_format(&config, 14.0, 1.0, true), GlyphFormat::Bitmap);
+    }
+
+    ///Builds the mesh for a glyph at 20 pixels per em with `content_scale`, returning the atlas size it was rasterized
+    ///at and its quad's size in logical pixels.  None if there's no font or adapter to test with.
+    fn glyph_at_scale(content_scale: f32) -> Option<(u32, [f32; 2])>{
+        let fonts = FontChain::load(None)?;
+        let mut wgpu_state = snapshot::offscreen_state((64, 64))?;
+        wgpu_state.set_content_scale(content_scale);
+
+        let mesh = build_text_mesh(&mut wgpu_state, &mut Shaper::new(), &fonts, "H", 20.0, [1.0, 1.0, 1.0, 1.0]);
+        Some((mesh.glyphs[0].size, mesh.quads[0].size))
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_at_the_content_scale(){
+        let ((size, quad), (scaled_size, scaled_quad)) = match (glyph_at_scale(1.0), glyph_at_scale(2.0)) {
+            (Some(glyph), Some(scaled)) => (glyph, scaled),
+            _ => return
+        };
+
+        assert_eq!((size, scaled_size), (20, 40));
+        //twice the surface pixels for the same logical size, give or take rounding each bitmap to whole pixels
+        assert!((quad[0] - scaled_quad[0]).abs() <= 1.0 && (quad[1] - scaled_quad[1]).abs() <= 1.0, "{:?} {:?}", quad, scaled_quad);
+    }
+
+    #[test]
+    fn snapping_uses_surface_pixels(){
+        assert_eq!(snap_to_pixel(10.3, 1.0), 10.0);
+        assert_eq!(snap_to_pixel(10.3, 2.0), 10.5);
+    }
+}
@@ -0,0 +1,192 @@
+use wgpu::util::DeviceExt;
+
+///The uniform block shared by every pipeline, matching `View` in the shaders.
+///Vertex positions are in logical pixels from the top left of the window, y down. The shaders subtract `scroll`,
+///multiply by `scale` to get surface pixels, and then apply `projection` to get clip space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ViewUniform{
+    ///Column major orthographic projection from surface pixels to clip space.
+    pub projection: [[f32; 4]; 4],
+    ///Offset of the view, in logical pixels.
+    pub scroll: [f32; 2],
+    ///Surface pixels per logical pixel.
+    pub scale: f32,
+    ///Pads the block to a multiple of 16 bytes.
+    pub _padding: u32
+}
+
+impl ViewUniform{
+    pub fn new(surface_size: (i32, i32), scroll: [f32; 2], scale: f32) -> ViewUniform{
+        ViewUniform{
+            projection: orthographic_projection(surface_size),
+            scroll,
+            scale,
+            _padding: 0
+        }
+    }
+
+    ///Applies this view to a position in logical pixels, the same way the shaders do.
+    #[cfg(test)]
+    pub fn to_clip(self, position: [f32; 2]) -> [f32; 2]{
+        let x = (position[0] - self.scroll[0]) * self.scale;
+        let y = (position[1] - self.scroll[1]) * self.scale;
+        [
+            self.projection[0][0] * x + self.projection[1][0] * y + self.projection[3][0],
+            self.projection[0][1] * x + self.projection[1][1] * y + self.projection[3][1]
+        ]
+    }
+}
+
+///Builds a projection mapping (0, 0) to the top left of the surface and `surface_size` to the bottom right.
+pub fn orthographic_projection(surface_size: (i32, i32)) -> [[f32; 4]; 4]{
+    let width = surface_size.0.max(1) as f32;
+    let height = surface_size.1.max(1) as f32;
+
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0, 1.0]
+    ]
+}
+
+///The view uniform buffer and its bind group, bound as group 0 by every pipeline.
+pub struct View{
+    uniform: ViewUniform,
+    surface_size: (i32, i32),
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup
+}
+
+impl View{
+    pub fn new(device: &wgpu::Device, surface_size: (i32, i32), scale: f32) -> View{
+        let uniform = ViewUniform::new(surface_size, [0.0, 0.0], scale);
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor{
+            label: Some("View Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
+            label: Some("View Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry{
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer{
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ]
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            label: Some("View Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry{
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        View{
+            uniform,
+            surface_size,
+            buffer,
+            bind_group_layout,
+            bind_group
+        }
+    }
+
+    ///Get the layout pipelines use for group 0.
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout{
+        &self.bind_group_layout
+    }
+
+    ///Get the bind group holding the view uniform buffer.
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup{
+        &self.bind_group
+    }
+
+    ///Get the scroll offset, in logical pixels.
+    pub fn get_scroll(&self) -> [f32; 2]{
+        self.uniform.scroll
+    }
+
+    ///Get the number of surface pixels per logical pixel.
+    pub fn get_content_scale(&self) -> f32{
+        self.uniform.scale
+    }
+
+    ///Get the size of the surface in surface pixels.
+    pub fn get_surface_size(&self) -> (i32, i32){
+        self.surface_size
+    }
+
+    ///Get the size of the surface in logical pixels, which is the space layout is done in.
+    pub fn get_logical_size(&self) -> (f32, f32){
+        (self.surface_size.0 as f32 / self.uniform.scale, self.surface_size.1 as f32 / self.uniform.scale)
+    }
+
+    ///Updates the projection for a resized surface.
+    pub fn set_surface_size(&mut self, queue: &wgpu::Queue, surface_size: (i32, i32)){
+        self.surface_size = surface_size;
+        self.uniform.projection = orthographic_projection(surface_size);
+        self.upload(queue);
+    }
+
+    pub fn set_scroll(&mut self, queue: &wgpu::Queue, scroll: [f32; 2]){
+        self.uniform.scroll = scroll;
+        self.upload(queue);
+    }
+
+    ///Sets the content scale, from glfw's ContentScale event. Non positive scales are ignored.
+    pub fn set_content_scale(&mut self, queue: &wgpu::Queue, scale: f32){
+        if scale > 0.0 {
+            self.uniform.scale = scale;
+            self.upload(queue);
+        }
+    }
+
+    fn upload(&self, queue: &wgpu::Queue){
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.uniform));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn surface_corners_map_to_clip_corners(){
+        let view = ViewUniform::new((640, 480), [0.0, 0.0], 1.0);
+        assert_eq!(view.to_clip([0.0, 0.0]), [-1.0, 1.0]);
+        assert_eq!(view.to_clip([640.0, 480.0]), [1.0, -1.0]);
+    }
+
+    #[test]
+    fn scale_maps_logical_pixels_to_surface_pixels(){
+        let view = ViewUniform::new((1280, 960), [0.0, 0.0], 2.0);
+        assert_eq!(view.to_clip([640.0, 480.0]), [1.0, -1.0]);
+    }
+
+    #[test]
+    fn scroll_moves_content_up_and_left(){
+        let view = ViewUniform::new((640, 480), [320.0, 240.0], 1.0);
+        assert_eq!(view.to_clip([320.0, 240.0]), [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn uniform_matches_shader_layout(){
+        //mat4x4 + vec2 + f32 + padding, a multiple of 16 bytes
+        assert_eq!(std::mem::size_of::<ViewUniform>(), 80);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::intermediary::message_queue::{BackendMessage, BufferLine, FrontendMessage};
+
+///Lines loaded above and below the visible ones, so scrolling a little doesn't wait on the backend.
+pub const OVERSCAN_LINES: usize = 32;
+
+///The part of a buffer shown in the editor view.  Only the visible lines plus [OVERSCAN_LINES] either side
+///are fetched from the backend, so the cost of drawing doesn't depend on the size of the buffer.
+pub struct Viewport{
+    buffer: Option<usize>,
+    ///Offset of the top of the view from the top of the buffer, in logical pixels.
+    scroll: f32,
+    height: f32,
+    line_height: f32,
+    total_lines: usize,
+    lines: HashMap<usize, BufferLine>,
+    ///Lines the backend has sent since the buffer last changed.
+    loaded: Range<usize>,
+    ///Lines asked for which haven't arrived yet.
+    pending: Option<Range<usize>>,
+    ///The buffer has changed since the lines were loaded.
+    stale: bool,
+    ///Increased whenever what the view shows may have changed, so a cached drawing of it can be reused until then.
+    revision: u64
+}
+
+impl Viewport{
+    pub fn new(height: f32, line_height: f32) -> Viewport{
+        Viewport{
+            buffer: None,
+            scroll: 0.0,
+            height,
+            line_height,
+            total_lines: 0,
+            lines: HashMap::new(),
+            loaded: 0..0,
+            pending: None,
+            stale: false,
+            revision: 0
+        }
+    }
+
+    ///Starts showing the buffer at `buffer`, from the top.
+    pub fn show_buffer(&mut self, buffer: usize){
+        self.buffer = Some(buffer);
+        self.scroll = 0.0;
+        self.total_lines = 0;
+        self.lines.clear();
+        self.loaded = 0..0;
+        self.pending = None;
+        self.stale = true;
+        self.revision += 1;
+    }
+
+    ///Sets the height of the view and of each line, in logical pixels.
+    pub fn set_size(&mut self, height: f32, line_height: f32){
+        let old = (self.height, self.line_height, self.scroll);
+        self.height = height.max(0.0);
+        self.line_height = line_height.max(1.0);
+        self.clamp_scroll();
+        if (self.height, self.line_height, self.scroll) != old {
+            self.revision += 1;
+        }
+    }
+
+    ///Get a number which changes whenever what the view shows may have changed.
+    pub fn get_revision(&self) -> u64{
+        self.revision
+    }
+
+    ///Get the offset of the top of the view from the top of the buffer, in logical pixels.
+    #[cfg(test)]
+    pub fn get_scroll(&self) -> f32{
+        self.scroll
+    }
+
+    ///Scrolls down by `delta` logical pixels (up if negative), stopping at the ends of the buffer.
+    pub fn scroll_by(&mut self, delta: f32){
+        let old = self.scroll;
+        self.scroll += delta;
+        self.clamp_scroll();
+        if self.scroll != old {
+            self.revision += 1;
+        }
+    }
+
+    fn clamp_scroll(&mut self){
+        let max_scroll = (self.total_lines as f32 * self.line_height - self.height).max(0.0);
+        self.scroll = self.scroll.clamp(0.0, max_scroll);
+    }
+
+    ///Get the lines which are at least partly inside the view.
+    pub fn visible_lines(&self) -> Range<usize>{
+        let first = (self.scroll / self.line_height).floor() as usize;
+        let last = ((self.scroll + self.height) / self.line_height).ceil() as usize;
+        first.min(self.total_lines)..last.min(self.total_lines)
+    }
+
+    ///Get the y position of the top of `line` relative to the top of the view, in logical pixels.
+    pub fn line_top(&self, line: usize) -> f32{
+        line as f32 * self.line_height - self.scroll
+    }
+
+    ///Get a loaded line, or None if it hasn't arrived from the backend yet.
+    pub fn get_line(&self, line: usize) -> Option<&BufferLine>{
+        self.lines.get(&line)
+    }
+
+    ///Returns a request for lines if the visible lines aren't loaded, or the buffer changed since they were.
+    ///Nothing is requested while an earlier request which covers the view is still on its way.
+    pub fn take_request(&mut self) -> Option<FrontendMessage>{
+        let buffer = self.buffer?;
+
+        //until the first reply the length isn't known, so ask for a screenful from the top
+        let visible = if self.total_lines == 0 {
+            let first = (self.scroll / self.line_height).floor() as usize;
+            first..first + (self.height / self.line_height).ceil() as usize + 1
+        }else{
+            self.visible_lines()
+        };
+
+        let covers = |range: &Range<usize>| range.start <= visible.start && visible.end <= range.end;
+        if self.pending.as_ref().is_some_and(covers) {
+            return None;
+        }
+        if !self.stale && covers(&self.loaded) {
+            return None;
+        }
+
+        let first_line = visible.start.saturating_sub(OVERSCAN_LINES);
+        let count = visible.end + OVERSCAN_LINES - first_line;
+        self.pending = Some(first_line..first_line + count);
+        self.stale = false;
+
+        Some(FrontendMessage::RequestLines{ buffer, first_line, count })
+    }
+
+    ///Updates the view from a message from the backend. Returns true if what's shown may have changed.
+    pub fn update(&mut self, message: &BackendMessage) -> bool{
+        match message{
+            BackendMessage::ShowBuffer(buffer) => {
+                self.show_buffer(*buffer);
+                true
+            },
+            BackendMessage::BufferChanged(buffer) if Some(*buffer) == self.buffer => {
+                self.stale = true;
+                self.pending = None;
+                false
+            },
+            BackendMessage::Lines{ buffer, first_line, total_lines, lines } if Some(*buffer) == self.buffer => {
+                self.total_lines = *total_lines;
+                self.lines.clear();
+                self.lines.extend(lines.iter().cloned().enumerate().map(|(offset, line)| (first_line + offset, line)));
+                self.loaded = *first_line..first_line + lines.len();
+                //past the end of the buffer counts as loaded, there's nothing more to ask for
+                if self.loaded.end == self.total_lines {
+                    self.loaded.end = usize::MAX;
+                }
+                self.pending = None;
+                self.clamp_scroll();
+                self.revision += 1;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn lines_message(first_line: usize, count: usize, total_lines: usize) -> BackendMessage{
+        BackendMessage::Lines{
+            buffer: 0,
+            first_line,
+            total_lines,
+            lines: (first_line..first_line + count).map(|line| BufferLine{ revision: 0, text: line.to_string() }).collect()
+        }
+    }
+
+    fn requested(viewport: &mut Viewport) -> Option<(usize, usize)>{
+        match viewport.take_request(){
+            Some(FrontendMessage::RequestLines{ first_line, count, .. }) => Some((first_line, count)),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn only_visible_lines_and_overscan_are_requested(){
+        let mut viewport = Viewport::new(100.0, 10.0);
+        viewport.update(&BackendMessage::ShowBuffer(0));
+        assert_eq!(requested(&mut viewport), Some((0, 11 + OVERSCAN_LINES)));
+        assert_eq!(requested(&mut viewport), None);
+
+        viewport.update(&lines_message(0, 11 + OVERSCAN_LINES, 1_000_000));
+        viewport.scroll_by(500_000.0);
+        assert_eq!(viewport.visible_lines(), 50_000..50_010);
+        assert_eq!(requested(&mut viewport), Some((50_000 - OVERSCAN_LINES, 10 + OVERSCAN_LINES * 2)));
+    }
+
+    #[test]
+    fn scrolling_inside_loaded_lines_requests_nothing(){
+        let mut viewport = Viewport::new(100.0, 10.0);
+        viewport.update(&BackendMessage::ShowBuffer(0));
+        viewport.take_request();
+        viewport.update(&lines_message(0, 50, 1000));
+
+        viewport.scroll_by(50.0);
+        assert_eq!(requested(&mut viewport), None);
+        assert_eq!(viewport.get_line(7).map(|line| line.text.as_str()), Some("7"));
+    }
+
+    #[test]
+    fn changed_buffer_is_requested_again(){
+        let mut viewport = Viewport::new(100.0, 10.0);
+        viewport.update(&BackendMessage::ShowBuffer(0));
+        viewport.take_request();
+        viewport.update(&lines_message(0, 20, 20));
+
+        assert_eq!(requested(&mut viewport), None);
+        viewport.update(&BackendMessage::BufferChanged(0));
+        assert!(requested(&mut viewport).is_some());
+    }
+
+    #[test]
+    fn revision_changes_with_what_is_shown(){
+        let mut viewport = Viewport::new(100.0, 10.0);
+        viewport.update(&BackendMessage::ShowBuffer(0));
+        viewport.update(&lines_message(0, 50, 50));
+        let revision = viewport.get_revision();
+
+        viewport.set_size(100.0, 10.0);
+        viewport.scroll_by(-10.0);
+        assert_eq!(viewport.get_revision(), revision);
+
+        viewport.scroll_by(10.0);
+        assert!(viewport.get_revision() > revision);
+    }
+
+    #[test]
+    fn scroll_stops_at_end_of_buffer(){
+        let mut viewport = Viewport::new(100.0, 10.0);
+        viewport.update(&BackendMessage::ShowBuffer(0));
+        viewport.update(&lines_message(0, 15, 15));
+
+        viewport.scroll_by(1000.0);
+        assert_eq!(viewport.get_scroll(), 50.0);
+        viewport.scroll_by(-1000.0);
+        assert_eq!(viewport.get_scroll(), 0.0);
+    }
+}
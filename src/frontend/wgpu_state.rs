@@ -1,178 +1,309 @@
-use bytemuck::bytes_of;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use wgpu;
-use wgpu::util::DeviceExt;
 use crate::frontend::rendering::{
-    mesh::{Vertex, VERTICES, INDICES},
-    render_state::RenderState};
-
+    mesh::{Vertex, QuadInstance, RectInstance},
+    render_state::RenderState,
+    layer::{Layer, LayerBuffers, Pipelines},
+    glyph_atlas::{GlyphAtlas, GlyphKey, AtlasGlyph},
+    pane_cache::{Pane, PaneCache, CachedPane},
+    view::View,
+    frame::Frame,
+    color,
+    lcd::{self, CoverageCurve},
+    font::Font};
+use crate::frontend::graphics_config::GraphicsConfig;
+use crate::intermediary::panic_hook;
+use crate::intermediary::supervisor::panic_message;
+
+
+///Texture format of offscreen targets, so read back pixels are already RGBA.
+#[cfg(test)]
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+///Where a WGPUState draws frames to.
+pub enum RenderTarget{
+    ///A window's surface, presented after each frame.
+    Surface(wgpu::Surface),
+    ///A texture which can be copied back to the cpu, for tests which run without a window.
+    #[cfg(test)]
+    Offscreen(wgpu::Texture),
+    ///The surface was freed after the device was lost, so a new one can be created for the window.  Nothing can be
+    ///drawn until [WGPUState::recreate] succeeds.
+    Released
+}
 
 ///Handles the surface created with WGPU, and the device configuration.  
 /// Also handles resizing the surface in case of a window resize.
 pub struct WGPUState{
-    surface: wgpu::Surface,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    adapter_info: wgpu::AdapterInfo,
     config: wgpu::SurfaceConfiguration,
     size: (i32, i32),
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-    vertex_buffer_size: u32,
-    index_buffer_size: u32
+    view: View,
+    ///The texture frames are drawn into before they're copied to the target, if the target isn't sRGB.
+    frame: Option<Frame>,
+    ///Pipelines shared by every layer.
+    pipelines: Pipelines,
+    ///Buffers holding each layer of the last uploaded frame, indexed by [Layer::index].
+    layer_buffers: Vec<LayerBuffers>,
+    ///Buffers holding each layer of the last pane drawn, so drawing a pane leaves the frame's buffers alone.
+    pane_buffers: Vec<LayerBuffers>,
+    glyph_atlas: GlyphAtlas,
+    pane_cache: PaneCache,
+    buffer_reallocations: u32,
+    graphics_config: GraphicsConfig,
+    ///The window can be seen through, so there's nothing opaque to blend subpixel text against.
+    transparent: bool,
+    ///Set from wgpu's error callback when the device is lost, which can happen on any thread.
+    device_lost: Arc<AtomicBool>
 }
 
 impl WGPUState{
     //Create a new wgpustate from a glfw window.  Taken from https://sotrh.github.io/learn-wgpu/beginner/tutorial2-surface/#state-new
-    pub async fn new(window: &glfw::Window) -> WGPUState{
+    //The backend, adapter and present mode are picked using graphics_config.
+    //Returns an error if the window has no size (like when it's minimized) or no adapter could draw to it.
+    pub async fn new(window: &glfw::Window, graphics_config: &GraphicsConfig) -> Result<WGPUState, String>{
         
-        //Get size of window
-        let size = window.get_size();
+        //Get size of the framebuffer in pixels, which is bigger than the window's size in screen coordinates on HiDPI displays
+        let size = window.get_framebuffer_size();
+
+        //Confirm the size is valid
+        if size.0 <= 0 || size.1 <= 0 {
+            return Err(String::from("Size of window cannot be zero in either dimension!"));
+        }
 
         //Create surface from window
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(graphics_config.backends);
         let surface = unsafe { instance.create_surface(window) };
 
-        //Create adapter from surface
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions{
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: true //Tutorial uses false but that crashes for some reason
-            }
-        ).await.unwrap();
-
-        //Get device and queue from adapter
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-                label: None
-            },
-            None
-        ).await.unwrap();
+        //Create adapter and get device and queue from it, falling back to the software adapter if the hardware one fails
+        let (adapter, device, queue) = request_device(&instance, Some(&surface), graphics_config).await?;
 
-        //Confirm the size is valid
-        if(size.0 == 0 || size.1 == 0){
-            panic!("Size of window cannot be zero in either dimension!");
-        }
+        let adapter_info = adapter.get_info();
+        log::info!("Using adapter {:?}", adapter_info);
 
         //Create config
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
+            format: color::choose_surface_format(&surface.get_supported_formats(&adapter)).ok_or("Surface isn't supported by the adapter!")?,
             width: size.0 as u32,
             height: size.1 as u32,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: graphics_config.choose_present_mode(&surface.get_supported_present_modes(&adapter)),
             alpha_mode: wgpu::CompositeAlphaMode::Auto
         };
-        surface.configure(&device, &config);
+        //the device can be lost again while it's being set up, which wgpu reports by panicking here
+        let device_lost = AtomicBool::new(false);
+        configure_surface(&surface, &device, &config, &device_lost);
+        if device_lost.load(Ordering::SeqCst) {
+            //leaked for the same reason as in release, but the surface is freed so the window can be given another one
+            std::mem::forget((device, queue));
+            catch_fatal_error(&device_lost, || drop(surface));
+            return Err(String::from("Couldn't configure the surface"));
+        }
 
-        //create shader for render pipeline
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
+        let (content_scale, _) = window.get_content_scale();
+
+        Ok(WGPUState::from_device(RenderTarget::Surface(surface), adapter_info, device, queue, config, content_scale, window.is_framebuffer_transparent(), graphics_config))
+    }
+
+    ///Create a new wgpustate which renders into a texture instead of a window, for tests which run without a display.
+    ///`size` is in surface pixels, and `format` must be an 8 bit RGBA or BGRA format to be read back, usually [OFFSCREEN_FORMAT].
+    ///Returns an error if no adapter allowed by graphics_config could create a device.
+    #[cfg(test)]
+    pub async fn new_offscreen(size: (i32, i32), content_scale: f32, format: wgpu::TextureFormat, graphics_config: &GraphicsConfig) -> Result<WGPUState, String>{
+        if size.0 <= 0 || size.1 <= 0 {
+            return Err(String::from("Size of an offscreen target cannot be zero in either dimension!"));
+        }
+
+        let instance = wgpu::Instance::new(graphics_config.backends);
+        let (adapter, device, queue) = request_device(&instance, None, graphics_config).await?;
+
+        let adapter_info = adapter.get_info();
+        log::info!("Using adapter {:?} for offscreen rendering", adapter_info);
+
+        //the surface configuration isn't used to configure anything, but the pipelines and resize read the format and size from it
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: size.0 as u32,
+            height: size.1 as u32,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque
+        };
+        let texture = create_offscreen_texture(&device, &config);
+
+        Ok(WGPUState::from_device(RenderTarget::Offscreen(texture), adapter_info, device, queue, config, content_scale, false, graphics_config))
+    }
+
+    ///Creates everything which doesn't depend on the render target: the view uniform, pipelines, buffers and glyph atlas.
+    #[allow(clippy::too_many_arguments)]
+    fn from_device(target: RenderTarget, adapter_info: wgpu::AdapterInfo, device: wgpu::Device, queue: wgpu::Queue, config: wgpu::SurfaceConfiguration, content_scale: f32, transparent: bool, graphics_config: &GraphicsConfig) -> WGPUState{
+        let size = (config.width as i32, config.height as i32);
+
+        //watch for the device being lost, so the frontend can rebuild everything on a new one
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost_flag = device_lost.clone();
+        device.on_uncaptured_error(move |error| {
+            if is_device_lost_error(&error) {
+                log::error!("GPU device lost: {}", error);
+                lost_flag.store(true, Ordering::SeqCst);
+            }else{
+                //anything else is a bug in how digit uses wgpu.  this runs inside whichever wgpu call failed, so
+                //panicking here would take the render thread down mid frame, log it and carry on instead
+                log::error!("Uncaptured wgpu error: {}", error);
+            }
+        });
+
+        //create the view uniform, so layout can be done in logical pixels
+        let view = View::new(&device, size, content_scale);
+
+        //frames are drawn in sRGB, so targets which aren't get the frame copied to them, see color.rs
+        let frame = (!color::is_srgb(config.format)).then(|| Frame::new(&device, (config.width, config.height), config.format));
+        let format = color::drawing_format(config.format);
+
+        //create the shaders once, several pipelines use each of them
+        let mesh_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("rendering/shaders/shader.wgsl").into())
         });
-
-        //create pipeline layout for render pipeline
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
-            push_constant_ranges: &[]
+        let rect_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
+            label: Some("Rect Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("rendering/shaders/rect.wgsl").into())
         });
-
-        //create render pipeline
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState{
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    Vertex::desc()
-                ]
-            },
-            fragment: Some(wgpu::FragmentState{
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState{
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })]
-            }),
-            primitive: wgpu::PrimitiveState{
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState{
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false
-            },
-            multiview: None
+        let quad_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
+            label: Some("Quad Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("rendering/shaders/quad.wgsl").into())
         });
 
-        //create vertex buffer
-        let vertex_buffer_contents: &[u8] = bytemuck::cast_slice(VERTICES);
-        let vertex_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor{
-                label: Some("Vertex Buffer"),
-                contents: vertex_buffer_contents,
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
-            }
-        );
-
-        //create index buffer
-        let index_buffer_contents: &[u8] = bytemuck::cast_slice(INDICES);
-        let index_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor{
-                label: Some("Index Buffer"),
-                contents: index_buffer_contents,
-                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST
-            }
-        );
-
-        //calculate number of indices
-        let num_indices = INDICES.len() as u32;
+        //create glyph atlas, which the quad pipelines draw glyphs from
+        let glyph_atlas = GlyphAtlas::new(&device, CoverageCurve::new(graphics_config.text_gamma, graphics_config.text_contrast));
+        let pane_cache = PaneCache::new(&device);
+
+        //create the pipelines, which every layer shares, and the buffers for each layer.  shaders output premultiplied
+        //alpha, see color.rs
+        let blend = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+        let pipelines = Pipelines{
+            mesh: create_mesh_pipeline(&device, format, &view, &mesh_shader, blend),
+            rect: create_rect_pipeline(&device, format, &view, &rect_shader, blend),
+            quad: create_quad_pipeline(&device, format, &view, &glyph_atlas, &quad_shader, "fs_main", blend),
+            lcd_mask: create_quad_pipeline(&device, format, &view, &glyph_atlas, &quad_shader, "fs_lcd_mask", lcd::MASK_BLEND),
+            lcd_color: create_quad_pipeline(&device, format, &view, &glyph_atlas, &quad_shader, "fs_lcd_colour", lcd::COLOR_BLEND),
+            lcd_grayscale: create_quad_pipeline(&device, format, &view, &glyph_atlas, &quad_shader, "fs_lcd_grayscale", blend)
+        };
+        let layer_buffers = Layer::ALL.iter().map(|_| LayerBuffers::new(&device)).collect();
+        let pane_buffers = Layer::ALL.iter().map(|_| LayerBuffers::new(&device)).collect();
 
         WGPUState{
-            surface,
+            target,
             device,
             queue,
+            adapter_info,
             config,
             size,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
-            vertex_buffer_size: vertex_buffer_contents.len() as u32,
-            index_buffer_size: index_buffer_contents.len() as u32
+            view,
+            frame,
+            pipelines,
+            layer_buffers,
+            pane_buffers,
+            glyph_atlas,
+            pane_cache,
+            buffer_reallocations: 0,
+            graphics_config: graphics_config.clone(),
+            transparent,
+            device_lost
         }
     }
 
-    ///Called when the window resizes to update the surface to match the new window size.
-    pub fn resize(&mut self, new_size: (i32, i32)) -> Result<(), &str> { //glfw::WindowEvent::Size has i32, i32
+    ///Returns true if the device has been lost and this wgpu_state has to be recreated.
+    pub fn is_device_lost(&self) -> bool{
+        self.device_lost.load(Ordering::SeqCst)
+    }
+
+    ///Treats the device as lost, so the next frame recreates this wgpu_state.
+    ///Used when the surface keeps being lost after reconfiguring it, and by the debug command that simulates device loss.
+    pub fn mark_device_lost(&self){
+        self.device_lost.store(true, Ordering::SeqCst);
+    }
+
+    ///Runs `f`, which uses this wgpu_state, and treats a panic in it as the device being lost.  wgpu 0.14 panics in
+    ///Queue::submit, Queue::write_buffer, Queue::write_texture and Surface::configure when they fail, which is how a
+    ///lost device shows up there, instead of reporting it to the uncaptured error callback.
+    ///Returns None if `f` panicked, in which case the device is marked lost and this wgpu_state has to be recreated.
+    pub fn catch_device_loss<R>(&mut self, f: impl FnOnce(&mut WGPUState) -> R) -> Option<R>{
+        let device_lost = self.device_lost.clone();
+        catch_fatal_error(&device_lost, || f(self))
+    }
+
+    ///Recreates the surface, adapter, device, pipelines, glyph atlas and buffers for `window` after the device was lost,
+    ///and uploads `render_state` to the new buffers.  The glyph atlas starts out empty, so anything holding atlas
+    ///coordinates (like cached line meshes) must be rebuilt.
+    ///Returns an error if no new device could be created yet, like when the adapter is still resetting or the window is
+    ///minimized.  This wgpu_state stays lost with its surface released, and recreating can be tried again later.
+    pub fn recreate(&mut self, window: &glfw::Window, render_state: &mut RenderState) -> Result<(), String>{
+        //release the old surface before creating a new one for the same window
+        let target = std::mem::replace(&mut self.target, RenderTarget::Released);
+        catch_fatal_error(&self.device_lost, || drop(target));
+
+        let mut wgpu_state = pollster::block_on(WGPUState::new(window, &self.graphics_config))?;
+        wgpu_state.set_scroll(self.view.get_scroll());
+        wgpu_state.set_content_scale(self.view.get_content_scale());
+
+        if let Err(error) = wgpu_state.set_vertices_and_indices(render_state) {
+            log::error!("Couldn't upload the frame to the new device: {}", error);
+        }
+
+        std::mem::replace(self, wgpu_state).release();
+        Ok(())
+    }
+
+    ///Frees the surface or offscreen texture of a wgpu_state whose device was lost, and leaks everything else.  Once a
+    ///call has failed wgpu-hal waits on fences which will never be signalled when resources are freed, and panics,
+    ///so freeing the device's resources could take the render thread down in the middle of recovering.
+    fn release(self){
+        let WGPUState{ target, device, queue, view, frame, pipelines, layer_buffers, pane_buffers, glyph_atlas, pane_cache, device_lost, .. } = self;
+        std::mem::forget((device, queue, view, frame, pipelines, layer_buffers, pane_buffers, glyph_atlas, pane_cache));
+        catch_fatal_error(&device_lost, || drop(target));
+    }
+
+    ///Called when the window's framebuffer resizes to update the surface to match, `new_size` is in pixels.
+    pub fn resize(&mut self, new_size: (i32, i32)) -> Result<(), &str> { //glfw::WindowEvent::FramebufferSize has i32, i32
         if new_size.0 > 0 && new_size.1 > 0 {
             self.size = new_size;
             self.config.width = new_size.0 as u32;
             self.config.height = new_size.1 as u32;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                RenderTarget::Surface(surface) => configure_surface(surface, &self.device, &self.config, &self.device_lost),
+                #[cfg(test)]
+                RenderTarget::Offscreen(texture) => *texture = create_offscreen_texture(&self.device, &self.config),
+                RenderTarget::Released => ()
+            }
+            if let Some(frame) = &mut self.frame {
+                frame.resize(&self.device, (self.config.width, self.config.height));
+            }
+            let (view, queue) = (&mut self.view, &self.queue);
+            catch_fatal_error(&self.device_lost, || view.set_surface_size(queue, new_size));
+            self.pane_cache.clear();
             Ok(())
         }else{
             Err("Failed to resize surface because the size cannot be zero in any dimension!")
         }
     }
 
-    ///Get the current surface of this wgpu_state.
-    pub fn get_surface(&self) -> &wgpu::Surface{
-        &self.surface
+    ///Configures the surface again with the current size, after it was lost or became outdated.
+    ///Offscreen targets can't be lost, so this does nothing for them.
+    pub fn reconfigure(&mut self){
+        if let RenderTarget::Surface(surface) = &self.target {
+            configure_surface(surface, &self.device, &self.config, &self.device_lost);
+        }
+    }
+
+    ///Get what this wgpu_state draws frames to.
+    pub fn get_target(&self) -> &RenderTarget{
+        &self.target
     }
 
     ///Get the current device of this wgpu_state
@@ -185,174 +316,430 @@ impl WGPUState{
         &self.queue
     }
 
+    ///Get information about the adapter (gpu and driver) this wgpu_state is using
+    pub fn get_adapter_info(&self) -> &wgpu::AdapterInfo{
+        &self.adapter_info
+    }
+
     ///Get the current surface configuration of this wgpu_state
     pub fn get_config(&self) -> &wgpu::SurfaceConfiguration{
         &self.config
     }
 
+    ///Get the format frames are drawn in, which is sRGB even if the target isn't, see [color::drawing_format]
+    pub fn get_drawing_format(&self) -> wgpu::TextureFormat{
+        color::drawing_format(self.config.format)
+    }
+
+    ///Get the texture frames are drawn into before they're copied to the target, or None if they're drawn to the target
+    pub fn get_frame(&self) -> Option<&Frame>{
+        self.frame.as_ref()
+    }
+
+    ///Get the graphics options this wgpu_state was created with
+    pub fn get_graphics_config(&self) -> &GraphicsConfig{
+        &self.graphics_config
+    }
+
+    ///Returns true if the window can be seen through
+    pub fn is_transparent(&self) -> bool{
+        self.transparent
+    }
+
     ///Get the current surface size of this wgpu_state
+    #[cfg(test)]
     pub fn get_size(&self) -> (i32, i32){
         self.size
     }
 
-    ///Get the current render pipeline for this wgpu_state
-    pub fn get_render_pipeline(&self) -> &wgpu::RenderPipeline{
-        &self.render_pipeline
+    ///Get the view uniform (projection, scroll and content scale) of this wgpu_state
+    pub fn get_view(&self) -> &View{
+        &self.view
     }
 
-    ///Get the current vertex buffer for this wgpu_state
-    pub fn get_vertex_buffer(&self) -> &wgpu::Buffer {
-        &self.vertex_buffer
+    ///Get the size of the surface in logical pixels, which is the space vertex positions are in
+    pub fn get_logical_size(&self) -> (f32, f32){
+        self.view.get_logical_size()
     }
 
-    ///Get the current index buffer for this wgpu_state
-    pub fn get_index_buffer(&self) -> &wgpu::Buffer{
-        &self.index_buffer
+    ///Scrolls the view so that `scroll` (in logical pixels) is at the top left of the surface
+    pub fn set_scroll(&mut self, scroll: [f32; 2]){
+        let (view, queue) = (&mut self.view, &self.queue);
+        catch_fatal_error(&self.device_lost, || view.set_scroll(queue, scroll));
+        self.pane_cache.clear();
     }
 
+    ///Called when the window's content scale changes (glfw::WindowEvent::ContentScale), like when it's moved to a
+    ///monitor with a different scale.  Text meshes built at the old scale need building again, their glyphs were
+    ///rasterized for it.
+    pub fn set_content_scale(&mut self, scale: f32){
+        let (view, queue) = (&mut self.view, &self.queue);
+        catch_fatal_error(&self.device_lost, || view.set_content_scale(queue, scale));
+        self.pane_cache.clear();
+    }
 
-    ///Get the number of indices in this wgpu_state
-    pub fn get_num_indices(&self) -> u32{
-        self.num_indices
+    ///Get the textures of panes drawn in earlier frames
+    pub fn get_pane_cache(&self) -> &PaneCache{
+        &self.pane_cache
     }
 
-    /// Set the vertex and index buffer of this WGPUState based on the contents of render_state.
-    /// Resizes this WGPUState's vertex and index buffers if they aren't big enough for the new data.
-    /// Updates the num_indices value of this WGPUState to match the new data.
-    pub fn set_vertices_and_indices(&mut self, render_state: &mut RenderState){
+    ///Get the textures of panes drawn in earlier frames, mutably. Call begin_frame on it before laying out each frame.
+    pub fn get_pane_cache_mut(&mut self) -> &mut PaneCache{
+        &mut self.pane_cache
+    }
+
+    ///Gets a texture to draw `pane`'s content into, see [PaneCache::prepare].  Returns None if the pane is off the surface.
+    pub fn prepare_pane(&mut self, pane: &Pane) -> Option<&CachedPane>{
+        let surface_size = (self.config.width, self.config.height);
+        self.pane_cache.prepare(&self.device, &self.queue, pane, self.glyph_atlas.get_bind_group_layout(), &self.view, self.get_drawing_format(), surface_size)
+    }
 
-        render_state.pad_index_buffer();
+    ///Get the pipelines every layer is drawn with
+    pub fn get_pipelines(&self) -> &Pipelines{
+        &self.pipelines
+    }
 
-        let vertex_data: &[u8] = bytemuck::cast_slice(&render_state.vertices);
-        let index_data: &[u8] = bytemuck::cast_slice(&render_state.indices);
+    ///Get the buffers holding `layer` of the last uploaded frame
+    pub fn get_layer_buffers(&self, layer: Layer) -> &LayerBuffers{
+        &self.layer_buffers[layer.index()]
+    }
 
-        //recreate vertex buffer if current buffer is not big enough
-        if vertex_data.len() as u32 > self.vertex_buffer_size{
-            println!("Resizing Vertex Buffer");
-            self.vertex_buffer = self.device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor{
-                    label: Some("Vertex Buffer"),
-                    contents: vertex_data,
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
-                }
-            );
-            self.vertex_buffer_size = vertex_data.len() as u32
-        } else {
-            self.queue.write_buffer(&self.vertex_buffer, 0, vertex_data);
-        }
+    ///Get the buffers holding `layer` of the last pane uploaded with [WGPUState::set_pane_vertices_and_indices]
+    pub fn get_pane_layer_buffers(&self, layer: Layer) -> &LayerBuffers{
+        &self.pane_buffers[layer.index()]
+    }
 
-        //recreate index buffer if current buffer is not big enough
-        if index_data.len() as u32 > self.index_buffer_size{
-            println!("Resizing Index Buffer");
-            self.index_buffer = self.device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor{
-                    label: Some("Index Buffer"),
-                    contents: index_data,
-                    usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST
-                }
-            );
-            self.index_buffer_size = index_data.len() as u32;
-        } else {
-            self.queue.write_buffer(&self.index_buffer, 0, index_data);
+    ///Get the glyph atlas of this wgpu_state
+    pub fn get_glyph_atlas(&self) -> &GlyphAtlas{
+        &self.glyph_atlas
+    }
+
+    ///Get the glyph atlas of this wgpu_state, mutably. Call begin_frame on it before laying out each frame's text.
+    pub fn get_glyph_atlas_mut(&mut self) -> &mut GlyphAtlas{
+        &mut self.glyph_atlas
+    }
+
+    ///Gets a glyph from the glyph atlas, rasterizing and uploading it if it isn't there yet.
+    pub fn get_glyph(&mut self, font: &Font, key: GlyphKey) -> Option<AtlasGlyph>{
+        self.glyph_atlas.get_glyph(&self.queue, font, key)
+    }
+
+
+    ///Get how many times the vertex and index buffers have been recreated to fit more data
+    pub fn get_buffer_reallocations(&self) -> u32{
+        self.buffer_reallocations
+    }
+
+    /// Set the vertex, index and quad instance buffers of each layer of this WGPUState based on the contents of render_state.
+    /// Resizes this WGPUState's buffers if they aren't big enough for the new data.
+    /// Returns an error, leaving the previous frame's data in place, if a mesh didn't fit in render_state or the data is too big for the device.
+    pub fn set_vertices_and_indices(&mut self, render_state: &mut RenderState) -> Result<(), &'static str>{
+        self.buffer_reallocations += write_layers(&self.device, &self.queue, &mut self.layer_buffers, render_state)?;
+        Ok(())
+    }
+
+    /// Set the buffers a pane's content is drawn from, like [WGPUState::set_vertices_and_indices] does for the frame.
+    /// The frame's buffers are left alone, so a frame which fails to upload afterwards draws the last one that fit.
+    pub fn set_pane_vertices_and_indices(&mut self, render_state: &mut RenderState) -> Result<(), &'static str>{
+        self.buffer_reallocations += write_layers(&self.device, &self.queue, &mut self.pane_buffers, render_state)?;
+        Ok(())
+    }
+}
+
+///Writes each layer of `render_state` to `layer_buffers`, returning how many buffers had to be recreated to fit it.
+///Returns an error, leaving every buffer as it was, if a mesh didn't fit in render_state or the data is too big for the device.
+fn write_layers(device: &wgpu::Device, queue: &wgpu::Queue, layer_buffers: &mut [LayerBuffers], render_state: &mut RenderState) -> Result<u32, &'static str>{
+
+    if let Some(error) = render_state.get_error() {
+        return Err(error);
+    }
+
+    render_state.pad_index_buffer();
+
+    //check every layer fits before writing any, so a failed upload doesn't mix two frames
+    let max_buffer_size = device.limits().max_buffer_size;
+    if render_state.get_layers().iter().any(|layer| layer.largest_buffer_size() as u64 > max_buffer_size) {
+        return Err("Frame has more vertices than fit in a gpu buffer!");
+    }
+
+    //buffers are recreated if they are not big enough
+    let mut reallocations = 0;
+    for (buffers, content) in layer_buffers.iter_mut().zip(render_state.get_layers()) {
+        reallocations += buffers.write(device, queue, content);
+    }
+
+    Ok(reallocations)
+}
+
+///Requests an adapter and a device from it, trying the hardware and fallback adapters as `graphics_config` allows.
+///Returns an error describing every attempt if none of them worked.
+async fn request_device(instance: &wgpu::Instance, surface: Option<&wgpu::Surface>, graphics_config: &GraphicsConfig) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), String>{
+    let mut failures = Vec::new();
+
+    for &force_fallback_adapter in graphics_config.fallback_adapter.attempts() {
+        let kind = if force_fallback_adapter { "fallback" } else { "hardware" };
+
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions{
+                power_preference: graphics_config.power_preference,
+                compatible_surface: surface,
+                force_fallback_adapter
+            }
+        ).await;
+
+        //a software adapter may come back even without forcing the fallback, look for any other adapter instead
+        let fallback_adapter = graphics_config.fallback_adapter;
+        let adapter = match adapter {
+            Some(adapter) if !fallback_adapter.allows(adapter.get_info().device_type) => {
+                log::info!("Skipping software adapter {:?}", adapter.get_info());
+                instance.enumerate_adapters(graphics_config.backends)
+                    .filter(|adapter| fallback_adapter.allows(adapter.get_info().device_type))
+                    .find(|adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface)))
+            },
+            adapter => adapter
+        };
+
+        let adapter = match adapter {
+            Some(adapter) => adapter,
+            None => {
+                log::warn!("No {} adapter found for backends {:?}", kind, graphics_config.backends);
+                failures.push(format!("no {} adapter", kind));
+                continue;
+            }
+        };
+
+        let device = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                label: None
+            },
+            None
+        ).await;
+
+        match device {
+            Ok((device, queue)) => return Ok((adapter, device, queue)),
+            Err(error) => {
+                log::warn!("Failed to create a device on {} adapter {:?}: {}", kind, adapter.get_info(), error);
+                failures.push(format!("{} adapter {}: {}", kind, adapter.get_info().name, error));
+            }
         }
+    }
 
-        self.num_indices = render_state.num_indices;
+    Err(format!("Couldn't create a GPU device with {:?} ({})", graphics_config, failures.join(", ")))
+}
 
+///Runs `f` and sets `device_lost` if it panics, see [WGPUState::catch_device_loss].  The panic gets no crash report,
+///the device is recreated instead.
+fn catch_fatal_error<R>(device_lost: &AtomicBool, f: impl FnOnce() -> R) -> Option<R>{
+    match panic_hook::catch_panic(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            log::error!("GPU call failed, treating the device as lost: {}", panic_message(payload.as_ref()));
+            device_lost.store(true, Ordering::SeqCst);
+            None
+        }
     }
 }
 
+///Configures `surface` with `config`, setting `device_lost` if that fails.
+fn configure_surface(surface: &wgpu::Surface, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, device_lost: &AtomicBool){
+    catch_fatal_error(device_lost, || surface.configure(device, config));
+}
+
+///Creates the texture an offscreen wgpu_state renders into, the size of `config`.
 #[cfg(test)]
-mod test{
-    use super::*;
-    use once_cell::sync::Lazy;
-    use std::{sync::Mutex};
-    use glfw::Glfw;
+fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture{
+    device.create_texture(&wgpu::TextureDescriptor{
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d{
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage
+    })
+}
 
-    static mut GLFW: Lazy<Mutex<Glfw>> = Lazy::new(|| {
-        let glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-        Mutex::new(glfw)
+///Returns true if a wgpu error was caused by the device being lost.  wgpu 0.14 doesn't have a device lost callback,
+///so losing the device shows up as an uncaptured error, with wgpu-core's DeviceError::Lost somewhere in its sources,
+///when it's next used.
+pub fn is_device_lost_error(error: &wgpu::Error) -> bool{
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(error) = source {
+        if let Some(wgpu_core::device::DeviceError::Lost) = error.downcast_ref::<wgpu_core::device::DeviceError>() {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+///Creates a pipeline which draws meshes of [Vertex]s with `shader`, blending them over what's already drawn with `blend`.
+///The shader outputs premultiplied alpha.
+fn create_mesh_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, view: &View, shader: &wgpu::ShaderModule, blend: wgpu::BlendState) -> wgpu::RenderPipeline{
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[view.get_bind_group_layout()],
+        push_constant_ranges: &[]
     });
 
-    #[test]
-    fn error_on_resize_with_dimension_zero(){
-        let glfw = unsafe { GLFW.lock().unwrap() };
+    create_pipeline(device, format, "Render Pipeline", &layout, shader, Vertex::desc(), "fs_main", blend)
+}
 
-        let (mut window, events) = glfw.create_window(640, 480, "Digit", glfw::WindowMode::Windowed)
-            .expect("Failed to create window!");
+///Creates a pipeline which draws [RectInstance]s with `shader`, blending them over what's already drawn with `blend`.
+///The shader works out each rectangle's shape, border and shadow per pixel and outputs premultiplied alpha.
+fn create_rect_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, view: &View, shader: &wgpu::ShaderModule, blend: wgpu::BlendState) -> wgpu::RenderPipeline{
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+        label: Some("Rect Pipeline Layout"),
+        bind_group_layouts: &[view.get_bind_group_layout()],
+        push_constant_ranges: &[]
+    });
 
-    
-        window.set_pos_polling(true);
-        window.set_all_polling(true);
-        window.set_size_polling(true);
-        window.set_close_polling(true);
-        window.set_refresh_polling(true);
-        window.set_focus_polling(true);
-        window.set_iconify_polling(true);
-        window.set_framebuffer_size_polling(true);
-        window.set_key_polling(true);
-        window.set_char_polling(true);
-        window.set_char_mods_polling(true);
-        window.set_mouse_button_polling(true);
-        window.set_cursor_pos_polling(true);
-        window.set_cursor_enter_polling(true);
-        window.set_scroll_polling(true);
-        window.set_maximize_polling(true);
-        window.set_content_scale_polling(true);
+    create_pipeline(device, format, "Rect Pipeline", &layout, shader, RectInstance::desc(), "fs_main", blend)
+}
 
-        glfw::Context::make_current(&mut window);
+///Creates a pipeline which draws [QuadInstance]s with `shader`'s `fragment_entry`, blending them (and glyph coverage from
+///the atlas) over what's already drawn with `blend`.  The shader outputs premultiplied alpha, like the mesh pipeline.
+fn create_quad_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, view: &View, glyph_atlas: &GlyphAtlas, shader: &wgpu::ShaderModule, fragment_entry: &str, blend: wgpu::BlendState) -> wgpu::RenderPipeline{
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+        label: Some("Quad Pipeline Layout"),
+        bind_group_layouts: &[view.get_bind_group_layout(), glyph_atlas.get_bind_group_layout()],
+        push_constant_ranges: &[]
+    });
+
+    create_pipeline(device, format, "Quad Pipeline", &layout, shader, QuadInstance::desc(), fragment_entry, blend)
+}
 
+///Creates a pipeline drawing triangle lists from one vertex buffer laid out as `buffer`, with `shader`'s vs_main and
+///`fragment_entry`.
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat, label: &str, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, buffer: wgpu::VertexBufferLayout, fragment_entry: &str, blend: wgpu::BlendState) -> wgpu::RenderPipeline{
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState{
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                buffer
+            ]
+        },
+        fragment: Some(wgpu::FragmentState{
+            module: shader,
+            entry_point: fragment_entry,
+            targets: &[Some(wgpu::ColorTargetState{
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })]
+        }),
+        primitive: wgpu::PrimitiveState{
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState{
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false
+        },
+        multiview: None
+    })
+}
 
-    let mut wgpu_state = pollster::block_on(WGPUState::new(&window));
+#[cfg(test)]
+mod test{
+    use super::*;
+    use crate::frontend::rendering::snapshot;
 
-    let result = wgpu_state.resize((0, 20));
+    #[test]
+    fn device_lost_errors_are_recognised(){
+        let error = |source: wgpu_core::device::DeviceError, description: &str| wgpu::Error::Validation{
+            source: Box::new(source),
+            description: description.to_string()
+        };
 
-    match result{
-        Ok(..) => {panic!("Should have returned error!")},
-        Err(..) => {}
+        assert!(is_device_lost_error(&error(wgpu_core::device::DeviceError::Lost, "Submit failed")));
+        //only the error's kind counts, not how it's worded
+        assert!(!is_device_lost_error(&error(wgpu_core::device::DeviceError::Invalid, "Buffer was lost")));
+        assert!(!is_device_lost_error(&wgpu::Error::OutOfMemory{ source: Box::new(wgpu_core::device::DeviceError::OutOfMemory) }));
     }
 
-    window.close();
+    #[test]
+    fn error_on_resize_with_dimension_zero(){
+        let mut wgpu_state = match snapshot::offscreen_state((640, 480)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
+
+        let result = wgpu_state.resize((0, 20));
+
+        match result{
+            Ok(..) => {panic!("Should have returned error!")},
+            Err(..) => {}
+        }
     }
 
     #[test]
     fn no_error_on_resize_with_nonzero_dimension(){
-        let glfw = unsafe { GLFW.lock().unwrap() };
-
-        let (mut window, events) = glfw.create_window(640, 480, "Digit", glfw::WindowMode::Windowed)
-            .expect("Failed to create window!");
+        let mut wgpu_state = match snapshot::offscreen_state((640, 480)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
 
-    
-        window.set_pos_polling(true);
-        window.set_all_polling(true);
-        window.set_size_polling(true);
-        window.set_close_polling(true);
-        window.set_refresh_polling(true);
-        window.set_focus_polling(true);
-        window.set_iconify_polling(true);
-        window.set_framebuffer_size_polling(true);
-        window.set_key_polling(true);
-        window.set_char_polling(true);
-        window.set_char_mods_polling(true);
-        window.set_mouse_button_polling(true);
-        window.set_cursor_pos_polling(true);
-        window.set_cursor_enter_polling(true);
-        window.set_scroll_polling(true);
-        window.set_maximize_polling(true);
-        window.set_content_scale_polling(true);
+        let result = wgpu_state.resize((20, 20));
 
-        glfw::Context::make_current(&mut window);
+        match result{
+            Ok(..) => {},
+            Err(..) => {panic!("Should have returned without error!")}
+        }
+        assert_eq!(wgpu_state.get_size(), (20, 20));
+    }
 
+    #[test]
+    fn failing_submit_marks_the_device_lost(){
+        let mut wgpu_state = match snapshot::offscreen_state((16, 16)) {
+            Some(wgpu_state) => wgpu_state,
+            None => return
+        };
 
-    let mut wgpu_state = pollster::block_on(WGPUState::new(&window));
+        let buffer = |label| wgpu_state.get_device().create_buffer(&wgpu::BufferDescriptor{
+            label: Some(label),
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let (source, destination) = (buffer("Source"), buffer("Destination"));
+
+        //submitting work on a destroyed buffer fails in Queue::submit the same way a lost device does
+        let result = wgpu_state.catch_device_loss(|wgpu_state| {
+            let mut encoder = wgpu_state.get_device().create_command_encoder(&wgpu::CommandEncoderDescriptor{ label: None });
+            encoder.copy_buffer_to_buffer(&source, 0, &destination, 0, 16);
+            source.destroy();
+            wgpu_state.get_queue().submit(std::iter::once(encoder.finish()));
+        });
 
-    let result = wgpu_state.resize((20, 20));
+        assert!(result.is_none());
+        assert!(wgpu_state.is_device_lost());
 
-    match result{
-        Ok(..) => {},
-        Err(..) => {panic!("Should have returned without error!")}
+        //the failed device panics if its resources are freed, recreating it mustn't
+        drop((source, destination));
+        wgpu_state.release();
     }
 
-    window.close();
-    
+    #[test]
+    fn offscreen_target_cannot_be_empty(){
+        let result = pollster::block_on(WGPUState::new_offscreen((0, 480), 1.0, OFFSCREEN_FORMAT, &GraphicsConfig::default()));
+        assert!(result.is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///A plain text report describing a crash, made up of a headline and any number of titled sections.
+pub struct CrashReport{
+    thread_name: String,
+    panic_message: String,
+    sections: Vec<(String, String)>
+}
+
+impl CrashReport{
+    ///Creates a report for a panic on the thread named `thread_name`.
+    pub fn new(thread_name: &str, panic_message: &str) -> CrashReport{
+        CrashReport{
+            thread_name: thread_name.to_string(),
+            panic_message: panic_message.to_string(),
+            sections: Vec::new()
+        }
+    }
+
+    ///Adds a titled section to the end of the report.
+    pub fn add_section(&mut self, title: &str, contents: &str){
+        self.sections.push((title.to_string(), contents.to_string()));
+    }
+
+    ///Renders the report as text.
+    pub fn render(&self) -> String{
+        let mut text = format!(
+            "digit {} crash report\nthread '{}' panicked: {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.thread_name,
+            self.panic_message
+        );
+
+        for (title, contents) in &self.sections{
+            text.push_str(&format!("\n== {} ==\n{}\n", title, contents.trim_end()));
+        }
+
+        text
+    }
+
+    ///Writes the report to a new timestamped file in `directory`, creating the directory if needed.
+    ///Returns the path of the written report.
+    pub fn write(&self, directory: &Path) -> io::Result<PathBuf>{
+        fs::create_dir_all(directory)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let path = directory.join(format!("crash-{}.txt", timestamp));
+
+        fs::write(&path, self.render())?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn render_contains_thread_and_message(){
+        let report = CrashReport::new("render", "device lost");
+
+        let text = report.render();
+
+        assert!(text.contains("thread 'render' panicked: device lost"));
+    }
+
+    #[test]
+    fn render_contains_sections_in_order(){
+        let mut report = CrashReport::new("render", "device lost");
+        report.add_section("First", "one");
+        report.add_section("Second", "two");
+
+        let text = report.render();
+
+        assert!(text.find("== First ==\none").unwrap() < text.find("== Second ==\ntwo").unwrap());
+    }
+}
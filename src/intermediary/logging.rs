@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+
+use crate::intermediary::paths;
+
+///How many entries are kept in memory for the *log* buffer.
+pub const LOG_HISTORY_CAPACITY: usize = 2000;
+
+///The log file is rotated once it grows past this many bytes.
+pub const LOG_FILE_MAX_BYTES: u64 = 1024 * 1024;
+
+///How many rotated log files (digit.log.1, digit.log.2, ...) are kept.
+pub const LOG_FILES_KEPT: usize = 3;
+
+///Which entries are kept for the *log* buffer unless DIGIT_LOG_HISTORY says otherwise, in the same format as RUST_LOG.
+///Dependencies like wgpu log a lot at debug, which would push digit's own entries out of the history.
+pub const LOG_HISTORY_FILTER: &str = "info,digit=debug";
+
+///Recent log entries, oldest first, so the *log* buffer can show what happened before it was opened.
+static LOG_HISTORY: Lazy<Mutex<LogHistory>> = Lazy::new(|| Mutex::new(LogHistory::new(LOG_HISTORY_CAPACITY)));
+
+///A single log record, kept for the *log* buffer.
+#[derive(Debug, Clone)]
+pub struct LogEntry{
+    ///Increases by one for every entry logged, so readers can ask for what's new since they last looked.
+    pub sequence: u64,
+    pub time: SystemTime,
+    pub level: Level,
+    ///The module the entry was logged from, e.g. digit::frontend::main
+    pub target: String,
+    pub message: String
+}
+
+impl LogEntry{
+    ///Formats the entry as a single line, the same way it is written to the terminal and log file.
+    pub fn format(&self) -> String{
+        format_line(self.time, self.level, &self.target, &self.message)
+    }
+}
+
+///A fixed size history of log entries.
+pub struct LogHistory{
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    next_sequence: u64
+}
+
+impl LogHistory{
+    pub fn new(capacity: usize) -> LogHistory{
+        LogHistory{
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            next_sequence: 0
+        }
+    }
+
+    ///Adds an entry, dropping the oldest if the history is full.  Returns the entry's sequence number.
+    pub fn push(&mut self, time: SystemTime, level: Level, target: &str, message: String) -> u64{
+        if self.entries.len() == self.capacity{
+            self.entries.pop_front();
+        }
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.entries.push_back(LogEntry{
+            sequence,
+            time,
+            level,
+            target: target.to_string(),
+            message
+        });
+
+        sequence
+    }
+
+    ///Get every kept entry with a sequence number of at least `sequence`, oldest first.
+    pub fn since(&self, sequence: u64) -> Vec<LogEntry>{
+        self.entries.iter().filter(|entry| entry.sequence >= sequence).cloned().collect()
+    }
+}
+
+///Gets every kept log entry with a sequence number of at least `sequence`, oldest first.
+pub fn entries_since(sequence: u64) -> Vec<LogEntry>{
+    LOG_HISTORY.lock().unwrap_or_else(|e| e.into_inner()).since(sequence)
+}
+
+///A log file which is moved aside to digit.log.1 (and so on) once it gets too big.
+pub struct RotatingFile{
+    path: PathBuf,
+    max_bytes: u64,
+    files_kept: usize,
+    file: File,
+    written: u64
+}
+
+impl RotatingFile{
+    ///Opens `path` for appending, creating the file and its directory if needed.
+    pub fn open(path: &Path, max_bytes: u64, files_kept: usize) -> io::Result<RotatingFile>{
+        if let Some(parent) = path.parent(){
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+
+        Ok(RotatingFile{
+            path: path.to_path_buf(),
+            max_bytes,
+            files_kept,
+            file,
+            written
+        })
+    }
+
+    ///Appends a line, rotating first if the line would take the file past its maximum size.
+    pub fn write_line(&mut self, line: &str) -> io::Result<()>{
+        if self.written > 0 && self.written + line.len() as u64 + 1 > self.max_bytes{
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    ///Shifts digit.log.N to digit.log.N+1 (dropping the oldest) and starts a fresh file.
+    fn rotate(&mut self) -> io::Result<()>{
+        for i in (1..self.files_kept).rev(){
+            let from = rotated_path(&self.path, i);
+            if from.exists(){
+                fs::rename(&from, rotated_path(&self.path, i + 1))?;
+            }
+        }
+        if self.files_kept > 0{
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+///Gets the path of the `index`th rotated copy of a log file, e.g. digit.log.2
+fn rotated_path(path: &Path, index: usize) -> PathBuf{
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+///Sends log records to stderr and the log file (filtered by RUST_LOG, default info), and keeps the ones
+///`history` allows in memory so the *log* buffer can filter them however the user likes.
+pub struct Logger{
+    filter: env_logger::filter::Filter,
+    history: env_logger::filter::Filter,
+    file: Option<Mutex<RotatingFile>>
+}
+
+impl Log for Logger{
+    fn enabled(&self, metadata: &Metadata) -> bool{
+        self.history.enabled(metadata) || self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record){
+        if !self.enabled(record.metadata()){
+            return;
+        }
+
+        let time = SystemTime::now();
+        let message = record.args().to_string();
+
+        if self.filter.matches(record){
+            let line = format_line(time, record.level(), record.target(), &message);
+            eprintln!("{}", line);
+            if let Some(file) = &self.file{
+                //nowhere left to report a failure to log, so it is dropped
+                let _ = file.lock().unwrap_or_else(|e| e.into_inner()).write_line(&line);
+            }
+        }
+
+        if self.history.matches(record){
+            LOG_HISTORY.lock().unwrap_or_else(|e| e.into_inner()).push(time, record.level(), record.target(), message);
+        }
+    }
+
+    fn flush(&self){
+        if let Some(file) = &self.file{
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).file.flush();
+        }
+    }
+}
+
+///Installs the logger.  Should be called once, first thing in main.
+pub fn init(){
+    let filter = env_logger::filter::Builder::new()
+        .parse(&std::env::var("RUST_LOG").unwrap_or_else(|_| String::from("info")))
+        .build();
+    let history = history_filter(std::env::var("DIGIT_LOG_HISTORY").ok().as_deref());
+
+    let log_path = paths::log_dir().join("digit.log");
+    let file = match RotatingFile::open(&log_path, LOG_FILE_MAX_BYTES, LOG_FILES_KEPT){
+        Ok(file) => Some(Mutex::new(file)),
+        Err(error) => {
+            eprintln!("Failed to open log file {}: {}", log_path.display(), error);
+            None
+        }
+    };
+
+    let max_level = filter.filter().max(history.filter());
+    log::set_boxed_logger(Box::new(Logger{ filter, history, file })).expect("Logger was already installed!");
+    log::set_max_level(max_level);
+}
+
+///Builds the filter for which entries are kept for the *log* buffer from `spec`, in the same format as RUST_LOG,
+///or [LOG_HISTORY_FILTER] if there isn't one.
+fn history_filter(spec: Option<&str>) -> env_logger::filter::Filter{
+    env_logger::filter::Builder::new()
+        .parse(spec.unwrap_or(LOG_HISTORY_FILTER))
+        .build()
+}
+
+///Formats a log line, e.g. 2022-11-20 14:03:09.250 INFO  [digit::frontend::main] Rendering Thread Started
+fn format_line(time: SystemTime, level: Level, target: &str, message: &str) -> String{
+    format!("{} {:<5} [{}] {}", format_timestamp(time), level, target, message)
+}
+
+///Formats a time as UTC, e.g. 2022-11-20 14:03:09.250
+pub fn format_timestamp(time: SystemTime) -> String{
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year, month, day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+///Converts days since 1970-01-01 to a (year, month, day) date. http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32){
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamp_is_formatted_as_utc(){
+        let time = UNIX_EPOCH + Duration::from_millis(1_668_952_989_250);
+
+        assert_eq!(format_timestamp(time), "2022-11-20 14:03:09.250");
+    }
+
+    #[test]
+    fn history_keeps_debug_entries_from_digit_only(){
+        let history = history_filter(None);
+        let metadata = |level, target| Metadata::builder().level(level).target(target).build();
+
+        assert!(history.enabled(&metadata(Level::Debug, "digit::frontend::main")));
+        assert!(history.enabled(&metadata(Level::Info, "wgpu_core::device")));
+        assert!(!history.enabled(&metadata(Level::Debug, "wgpu_core::device")));
+
+        let history = history_filter(Some("debug"));
+        assert!(history.enabled(&metadata(Level::Debug, "wgpu_core::device")));
+    }
+
+    #[test]
+    fn history_since_returns_newer_entries(){
+        let mut history = LogHistory::new(10);
+
+        history.push(UNIX_EPOCH, Level::Info, "a", String::from("first"));
+        let second = history.push(UNIX_EPOCH, Level::Info, "a", String::from("second"));
+
+        let entries = history.since(second);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "second");
+    }
+
+    #[test]
+    fn full_history_drops_oldest(){
+        let mut history = LogHistory::new(1);
+
+        history.push(UNIX_EPOCH, Level::Info, "a", String::from("first"));
+        history.push(UNIX_EPOCH, Level::Info, "a", String::from("second"));
+
+        assert_eq!(history.since(0)[0].message, "second");
+    }
+
+    #[test]
+    fn log_file_rotates_when_full(){
+        let directory = std::env::temp_dir().join(format!("digit-log-test-{}", std::process::id()));
+        let path = directory.join("digit.log");
+
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        file.write_line("aaaaaaaa").unwrap();
+        file.write_line("bbbbbbbb").unwrap();
+        file.write_line("cccccccc").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cccccccc\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "bbbbbbbb\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "aaaaaaaa\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Write};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+///How many messages are kept in the log before the oldest are dropped.
+pub const MESSAGE_LOG_CAPACITY: usize = 64;
+
+///Entries longer than this many characters are cut short, so messages carrying lots of data don't bloat crash reports.
+pub const MESSAGE_LOG_ENTRY_LENGTH: usize = 256;
+
+///Every message sent through a [MessageQueue](super::message_queue::MessageQueue), so crash reports can show what led up to a crash.
+static MESSAGE_LOG: Lazy<Mutex<MessageLog>> = Lazy::new(|| Mutex::new(MessageLog::new(MESSAGE_LOG_CAPACITY)));
+
+///A fixed size log of the most recent messages, oldest first.
+pub struct MessageLog{
+    entries: VecDeque<String>,
+    capacity: usize
+}
+
+impl MessageLog{
+    ///Creates an empty log which keeps at most `capacity` entries.
+    pub fn new(capacity: usize) -> MessageLog{
+        MessageLog{
+            entries: VecDeque::with_capacity(capacity),
+            capacity
+        }
+    }
+
+    ///Adds an entry, dropping the oldest one if the log is full.
+    pub fn push(&mut self, mut entry: String){
+        if let Some((cut, _)) = entry.char_indices().nth(MESSAGE_LOG_ENTRY_LENGTH){
+            entry.truncate(cut);
+            entry.push_str("...");
+        }
+
+        if self.entries.len() == self.capacity{
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    ///Get the entries in the log, oldest first.
+    pub fn entries(&self) -> Vec<String>{
+        self.entries.iter().cloned().collect()
+    }
+}
+
+///Records `message`, sent through a queue of `type_name`s, in the global message log.  Queues are locked while this
+///runs, so formatting stops once the entry is long enough to be cut short instead of formatting all of a big message.
+pub fn record(type_name: &str, message: &impl Debug){
+    let mut writer = BoundedWriter{ text: String::new(), remaining: MESSAGE_LOG_ENTRY_LENGTH, truncated: false };
+    //an error only means the entry got too long
+    let _ = write!(writer, "{}::{:?}", type_name, message);
+    if writer.truncated {
+        writer.text.push_str("...");
+    }
+
+    MESSAGE_LOG.lock().unwrap_or_else(|e| e.into_inner()).push(writer.text);
+}
+
+///Keeps the first `remaining` characters written to it, then fails so the rest isn't formatted.
+struct BoundedWriter{
+    text: String,
+    remaining: usize,
+    truncated: bool
+}
+
+impl Write for BoundedWriter{
+    fn write_str(&mut self, s: &str) -> fmt::Result{
+        match s.char_indices().nth(self.remaining) {
+            Some((cut, _)) => {
+                self.text.push_str(&s[..cut]);
+                self.remaining = 0;
+                self.truncated = true;
+                Err(fmt::Error)
+            },
+            None => {
+                self.text.push_str(s);
+                self.remaining -= s.chars().count();
+                Ok(())
+            }
+        }
+    }
+}
+
+///Gets the recent messages from the global message log, oldest first.
+///Does not block, so it is safe to call from a panic hook while another thread is recording; returns None if the log is busy.
+pub fn recent() -> Option<Vec<String>>{
+    match MESSAGE_LOG.try_lock(){
+        Ok(log) => Some(log.entries()),
+        Err(std::sync::TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner().entries()),
+        Err(std::sync::TryLockError::WouldBlock) => None
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn full_log_drops_oldest_entry(){
+        let mut log = MessageLog::new(2);
+
+        log.push(String::from("a"));
+        log.push(String::from("b"));
+        log.push(String::from("c"));
+
+        assert_eq!(log.entries(), vec![String::from("b"), String::from("c")]);
+    }
+
+    #[test]
+    fn long_entries_are_truncated(){
+        let mut log = MessageLog::new(2);
+
+        log.push("x".repeat(MESSAGE_LOG_ENTRY_LENGTH * 2));
+
+        assert_eq!(log.entries()[0].len(), MESSAGE_LOG_ENTRY_LENGTH + 3);
+    }
+
+    #[test]
+    fn formatting_stops_at_the_entry_length(){
+        //formats forever unless the writer gives up
+        struct Endless;
+        impl Debug for Endless{
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+                loop {
+                    f.write_str("x")?;
+                }
+            }
+        }
+
+        let mut writer = BoundedWriter{ text: String::new(), remaining: 10, truncated: false };
+        assert!(write!(writer, "Test::{:?}", Endless).is_err());
+        assert_eq!(writer.text, "Test::xxxx");
+        assert!(writer.truncated);
+    }
+}
@@ -1,15 +1,25 @@
 use std::collections::LinkedList;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::intermediary::message_log;
+
+///Called whenever a message is added to a queue, so a thread blocked on something else can be woken up to read it.
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
 
 ///Stores a FIFO queue of messages intended for communicating between threads
 pub struct MessageQueue<T>{
-    messages: LinkedList<T>
+    messages: LinkedList<T>,
+    waker: Option<Waker>
 }
 
-impl<T> MessageQueue<T>{
+impl<T: Debug> MessageQueue<T>{
     ///Creates a new empty message queue
     pub fn new() -> MessageQueue<T>{
         MessageQueue{
-            messages: LinkedList::new()
+            messages: LinkedList::new(),
+            waker: None
         }
     }
 
@@ -18,9 +28,21 @@ impl<T> MessageQueue<T>{
         self.messages.len()
     }
 
-    ///Add a new message to the end of the queue.
+    ///Add a new message to the end of the queue.  The message is also recorded in the global message log.
     pub fn add_message(&mut self, new_message: T){
+        let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap_or_default();
+        message_log::record(type_name, &new_message);
+
         self.messages.push_back(new_message);
+
+        if let Some(waker) = &self.waker {
+            waker();
+        }
+    }
+
+    ///Sets the function called after each message is added, or removes it if None.
+    pub fn set_waker(&mut self, waker: Option<Waker>){
+        self.waker = waker;
     }
 
     ///Gets the oldest message in the queue, also removing that message from the queue.
@@ -35,18 +57,74 @@ impl<T> MessageQueue<T>{
 }
 
 ///Messages that the frontend thread can send to the backend thread
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum FrontendMessage{
     UserQuit,
+    ///Sent by the supervisor on behalf of the frontend when the render thread has stopped. The backend should finish up and exit.
+    Shutdown,
     DebugMessage(Box<String>),
+    ///Search every file under the project root for `query`.  Supersedes any search still running.
+    ProjectSearch(String),
+    ///Cancel the background job with this id.
+    CancelJob(u64),
+    ///Open the *log* buffer showing entries at least as severe as `level`, from modules starting with `module` if given.
+    OpenLogBuffer{ level: log::Level, module: Option<String> },
+    ///The frontend can't continue, for example because the gpu ran out of memory. The backend should save what it can and exit.
+    FatalError(String),
+    ///Ask for `count` lines of the buffer at index `buffer`, starting from line `first_line` (counting from 0).
+    RequestLines{ buffer: usize, first_line: usize, count: usize },
     TestMessage
 }
 
 ///Messages that the backend thread can send to the frontend thread
+#[derive(Debug)]
 pub enum BackendMessage{
+    ///Sent by the supervisor when the editor is shutting down. The frontend should finish its current frame and exit.
+    Shutdown,
+    ///Something the user should be told about, such as buffers recovered after a crash.
+    Notification(String),
+    ///The buffer at this index in the open buffer list has changed and should be redrawn.
+    BufferChanged(usize),
+    ///The buffer at this index in the open buffer list should be shown in the editor view.
+    ShowBuffer(usize),
+    ///A background job has been queued.
+    JobStarted{ id: u64, name: String },
+    ///How far along a background job is, from 0.0 to 1.0, or None if it can't tell.
+    JobProgress{ id: u64, progress: Option<f32>, status: String },
+    ///A background job has stopped.
+    JobFinished{ id: u64, outcome: JobOutcome },
+    ///Matches found by a project search job.  `truncated` is true if the search stopped at its limit before finding
+    ///every match.
+    SearchResults{ query: String, matches: Vec<SearchMatch>, truncated: bool },
+    ///Lines asked for with RequestLines, starting from `first_line`.  `total_lines` is how many lines the whole buffer has.
+    Lines{ buffer: usize, first_line: usize, total_lines: usize, lines: Vec<BufferLine> },
     TestMessage
 }
 
+///How a background job ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobOutcome{
+    Completed,
+    Cancelled,
+    Failed(String)
+}
+
+///A line matching a project search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch{
+    pub path: PathBuf,
+    ///Line number, starting from 1.
+    pub line: usize,
+    pub text: String
+}
+
+///A line of a buffer, and the revision it was last changed in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferLine{
+    pub revision: u64,
+    pub text: String
+}
+
 #[cfg(test)]
 mod tests{
     
@@ -89,6 +167,23 @@ mod tests{
         assert_eq!(x.len(), 0);
     }
 
+    #[test]
+    fn waker_is_called_for_each_message(){
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut x: MessageQueue<i32> = MessageQueue::new();
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let counter = wakes.clone();
+        x.set_waker(Some(Arc::new(move || { counter.fetch_add(1, Ordering::SeqCst); })));
+
+        x.add_message(1);
+        x.add_message(2);
+        x.set_waker(None);
+        x.add_message(3);
+
+        assert_eq!(wakes.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn new_message_queue_is_empty(){
         let x: MessageQueue<()> = MessageQueue::new();
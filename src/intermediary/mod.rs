@@ -1 +1,7 @@
-pub mod message_queue;
\ No newline at end of file
+pub mod message_queue;
+pub mod message_log;
+pub mod supervisor;
+pub mod crash_report;
+pub mod panic_hook;
+pub mod logging;
+pub mod paths;
\ No newline at end of file
@@ -0,0 +1,100 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::panic::{self, PanicHookInfo, UnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+
+use once_cell::sync::Lazy;
+
+use crate::backend::file_buffer::FileBuffer;
+use crate::backend::recovery;
+use crate::intermediary::crash_report::CrashReport;
+use crate::intermediary::{message_log, paths};
+use crate::intermediary::supervisor::panic_message;
+
+///Description of the graphics adapter in use, set by the render thread once it has one.
+static ADAPTER_INFO: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+///Records the graphics adapter in use so it can be included in crash reports.
+pub fn set_adapter_info(info: String){
+    *ADAPTER_INFO.lock().unwrap_or_else(|e| e.into_inner()) = Some(info);
+}
+
+thread_local!{
+    ///Whether a panic on this thread is caught and dealt with by the code running on it, see `catch_panic`.
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+///Runs `f` like `panic::catch_unwind`, but a panic in it gets no crash report and doesn't save the buffers for
+///recovery, since the caller deals with it and the editor keeps running (a panicking job, for example).
+pub fn catch_panic<R>(f: impl FnOnce() -> R + UnwindSafe) -> thread::Result<R>{
+    let was_catching = CATCHING_PANICS.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(f);
+    CATCHING_PANICS.with(|catching| catching.set(was_catching));
+    result
+}
+
+///Installs a panic hook which, on any thread, writes a crash report with a backtrace, the recent message log
+///and the adapter info, and saves every dirty buffer to the recovery directory.
+///The default hook still runs first so the panic is printed as usual.
+pub fn install(open_buffers: Arc<Mutex<Vec<FileBuffer>>>){
+    let default_hook = panic::take_hook();
+    let crash_handler = crash_handler(open_buffers, paths::recovery_dir(), paths::crash_dir());
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        crash_handler(info);
+    }));
+}
+
+///Makes the part of the panic hook that writes the crash report to `crash_dir` and saves the dirty buffers to
+///`recovery_dir`.  Does nothing for panics caught by `catch_panic`.
+pub(crate) fn crash_handler(open_buffers: Arc<Mutex<Vec<FileBuffer>>>, recovery_dir: PathBuf, crash_dir: PathBuf) -> impl Fn(&PanicHookInfo) + Send + Sync{
+    move |info| {
+        if CATCHING_PANICS.with(Cell::get){
+            return;
+        }
+
+        let thread_name = thread::current().name().unwrap_or("<unnamed>").to_string();
+        let mut message = panic_message(info.payload());
+        if let Some(location) = info.location(){
+            message.push_str(&format!(" at {}", location));
+        }
+
+        let mut report = CrashReport::new(&thread_name, &message);
+
+        report.add_section("Backtrace", &Backtrace::force_capture().to_string());
+
+        let messages = match message_log::recent(){
+            Some(messages) => messages.join("\n"),
+            None => String::from("<message log was busy>")
+        };
+        report.add_section("Recent messages", &messages);
+
+        let adapter_info = match ADAPTER_INFO.try_lock(){
+            Ok(info) => info.clone().unwrap_or_else(|| String::from("<no adapter yet>")),
+            Err(_) => String::from("<adapter info was busy>")
+        };
+        report.add_section("Adapter", &adapter_info);
+
+        //the panicking thread may be holding the buffers, in which case the supervisor saves them once it has joined it
+        let recovered = match open_buffers.try_lock(){
+            Ok(buffers) => Some(recovery::save_dirty_buffers(&buffers, &recovery_dir)),
+            Err(TryLockError::Poisoned(poisoned)) => Some(recovery::save_dirty_buffers(&poisoned.into_inner(), &recovery_dir)),
+            Err(TryLockError::WouldBlock) => None
+        };
+        let recovered = match recovered{
+            Some(Ok(paths)) => paths.iter().map(|path| path.display().to_string()).collect::<Vec<String>>().join("\n"),
+            Some(Err(error)) => format!("Failed to save buffers: {}", error),
+            None => String::from("Buffers were locked by the panicking thread, they will be saved once it has stopped")
+        };
+        report.add_section("Recovered buffers", &recovered);
+
+        //the logger takes locks the panicking thread might hold, so this goes straight to stderr
+        match report.write(&crash_dir){
+            Ok(path) => eprintln!("Wrote crash report to {}", path.display()),
+            Err(error) => eprintln!("Failed to write crash report: {}", error)
+        }
+    }
+}
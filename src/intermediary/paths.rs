@@ -0,0 +1,69 @@
+use std::env;
+use std::path::PathBuf;
+
+///Gets the directory digit keeps its own files in (swap files, crash reports, ...).
+///This is $XDG_DATA_HOME/digit or ~/.local/share/digit, %LOCALAPPDATA%\digit on windows,
+///and falls back to the system temp directory if none of those are set.
+pub fn data_dir() -> PathBuf{
+    if let Some(dir) = env::var_os("XDG_DATA_HOME"){
+        return PathBuf::from(dir).join("digit");
+    }
+    if let Some(dir) = env::var_os("LOCALAPPDATA"){
+        return PathBuf::from(dir).join("digit");
+    }
+    if let Some(home) = env::var_os("HOME"){
+        return PathBuf::from(home).join(".local").join("share").join("digit");
+    }
+    env::temp_dir().join("digit")
+}
+
+///Gets the directory swap files are written to.
+pub fn swap_dir() -> PathBuf{
+    data_dir().join("swap")
+}
+
+///Gets the directory crash reports are written to.
+pub fn crash_dir() -> PathBuf{
+    data_dir().join("crashes")
+}
+
+///Gets the directory unsaved buffers are written to when the editor crashes.
+pub fn recovery_dir() -> PathBuf{
+    data_dir().join("recovery")
+}
+
+///Gets the directory the log file is written to.
+pub fn log_dir() -> PathBuf{
+    data_dir().join("logs")
+}
+
+///Gets the directory performance traces are written to.
+pub fn trace_dir() -> PathBuf{
+    data_dir().join("traces")
+}
+
+///Gets the directory the user's settings are read from.
+///This is $XDG_CONFIG_HOME/digit or ~/.config/digit, %APPDATA%\digit on windows,
+///and falls back to the data directory if none of those are set.
+pub fn config_dir() -> PathBuf{
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME"){
+        return PathBuf::from(dir).join("digit");
+    }
+    if let Some(dir) = env::var_os("APPDATA"){
+        return PathBuf::from(dir).join("digit");
+    }
+    if let Some(home) = env::var_os("HOME"){
+        return PathBuf::from(home).join(".config").join("digit");
+    }
+    data_dir()
+}
+
+///Gets the path of the config file.
+pub fn config_file() -> PathBuf{
+    config_dir().join("digit.conf")
+}
+
+///Gets the directory screenshots are written to.
+pub fn screenshot_dir() -> PathBuf{
+    data_dir().join("screenshots")
+}
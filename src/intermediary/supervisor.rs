@@ -0,0 +1,179 @@
+use std::any::Any;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+///How a supervised thread stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThreadStatus{
+    ///The thread returned normally.
+    Finished,
+    ///The thread panicked, with the panic message if one could be recovered.
+    Panicked(String),
+    ///The thread was still running when the join timeout ran out and has been abandoned.
+    TimedOut
+}
+
+///Describes a supervised thread that has stopped (or been given up on).
+#[derive(Debug, Clone)]
+pub struct ThreadExit{
+    pub name: String,
+    pub status: ThreadStatus
+}
+
+impl ThreadExit{
+    ///Whether this thread stopped in a way that should be treated as a crash.
+    pub fn is_crash(&self) -> bool{
+        self.status != ThreadStatus::Finished
+    }
+}
+
+///Owns the editor's long running threads so that a panic on either side can be noticed,
+///and so that every thread can be joined on exit instead of being killed mid-frame.
+pub struct Supervisor{
+    threads: Vec<(String, JoinHandle<()>)>
+}
+
+impl Supervisor{
+    ///Creates a supervisor with no threads.
+    pub fn new() -> Supervisor{
+        Supervisor{
+            threads: Vec::new()
+        }
+    }
+
+    ///Spawns a named thread running `f` and starts supervising it.
+    pub fn spawn<F>(&mut self, name: &str, f: F) where F: FnOnce() + Send + 'static{
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(f)
+            .expect("Failed to spawn thread!");
+
+        self.threads.push((name.to_string(), handle));
+    }
+
+    ///Get the number of threads that are still supervised (not yet joined).
+    pub fn len(&self) -> usize{
+        self.threads.len()
+    }
+
+    ///Gets whether every supervised thread has been joined.
+    pub fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
+
+    ///Joins every thread that has already stopped, without blocking on the ones that are still running.
+    pub fn try_join_finished(&mut self) -> Vec<ThreadExit>{
+        let mut exits = Vec::new();
+        let mut still_running = Vec::new();
+
+        for (name, handle) in self.threads.drain(..){
+            if handle.is_finished(){
+                exits.push(join_thread(name, handle));
+            } else {
+                still_running.push((name, handle));
+            }
+        }
+
+        self.threads = still_running;
+        exits
+    }
+
+    ///Joins every supervised thread, giving them `timeout` in total to stop.
+    ///Threads that are still running after that are detached and reported as [ThreadStatus::TimedOut].
+    pub fn join_all(&mut self, timeout: Duration) -> Vec<ThreadExit>{
+        let deadline = Instant::now() + timeout;
+        let mut exits = Vec::new();
+
+        while !self.is_empty() && Instant::now() < deadline{
+            exits.append(&mut self.try_join_finished());
+            thread::sleep(Duration::from_millis(5));
+        }
+        exits.append(&mut self.try_join_finished());
+
+        //anything left is stuck, dropping the handle detaches it
+        for (name, _handle) in self.threads.drain(..){
+            exits.push(ThreadExit{
+                name,
+                status: ThreadStatus::TimedOut
+            });
+        }
+
+        exits
+    }
+}
+
+///Joins a thread which has already finished, turning a panic into a [ThreadStatus::Panicked].
+fn join_thread(name: String, handle: JoinHandle<()>) -> ThreadExit{
+    let status = match handle.join(){
+        Ok(()) => ThreadStatus::Finished,
+        Err(payload) => ThreadStatus::Panicked(panic_message(payload.as_ref()))
+    };
+
+    ThreadExit{
+        name,
+        status
+    }
+}
+
+///Gets the message out of a panic payload.  panic!() payloads are either a &str or a String.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String{
+    if let Some(message) = payload.downcast_ref::<&str>(){
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>(){
+        message.clone()
+    } else {
+        String::from("<non-string panic payload>")
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn finished_thread_is_reported_as_finished(){
+        let mut supervisor = Supervisor::new();
+
+        supervisor.spawn("test", || {});
+
+        let exits = supervisor.join_all(Duration::from_secs(5));
+
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0].name, "test");
+        assert_eq!(exits[0].status, ThreadStatus::Finished);
+    }
+
+    #[test]
+    fn panicked_thread_is_reported_with_message(){
+        let mut supervisor = Supervisor::new();
+
+        supervisor.spawn("test", || panic!("gpu on fire"));
+
+        let exits = supervisor.join_all(Duration::from_secs(5));
+
+        assert_eq!(exits[0].status, ThreadStatus::Panicked(String::from("gpu on fire")));
+        assert!(exits[0].is_crash());
+    }
+
+    #[test]
+    fn stuck_thread_times_out(){
+        let mut supervisor = Supervisor::new();
+
+        supervisor.spawn("test", || thread::sleep(Duration::from_secs(2)));
+
+        let exits = supervisor.join_all(Duration::from_millis(20));
+
+        assert_eq!(exits[0].status, ThreadStatus::TimedOut);
+        assert!(supervisor.is_empty());
+    }
+
+    #[test]
+    fn try_join_finished_leaves_running_threads(){
+        let mut supervisor = Supervisor::new();
+
+        supervisor.spawn("test", || thread::sleep(Duration::from_millis(500)));
+
+        assert_eq!(supervisor.try_join_finished().len(), 0);
+        assert_eq!(supervisor.len(), 1);
+    }
+}
@@ -1,56 +1,142 @@
 use std::{thread};
 use std::time::Duration;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, TryLockError};
 
+use backend::file_buffer::FileBuffer;
+use intermediary::crash_report::CrashReport;
 use intermediary::message_queue::{MessageQueue, FrontendMessage, BackendMessage};
+use intermediary::supervisor::{Supervisor, ThreadExit, ThreadStatus};
 
 mod frontend;
 mod backend;
 mod intermediary;
 
+///How long the threads get to finish up once shutdown has started before they are abandoned.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn main() {
     
-    println!("Main Thread Started");
+    intermediary::logging::init();
+
+    log::info!("Main Thread Started");
+
+    //flags override the config file, e.g. --backend gl --vsync off
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let graphics_config = frontend::graphics_config::GraphicsConfig::load(&intermediary::paths::config_file(), &args);
+    log::info!("Graphics config: {:?}", graphics_config);
 
 
     let frontend_message_queue: Arc<Mutex<MessageQueue<FrontendMessage>>> = Arc::new(Mutex::new(MessageQueue::new()));
     let backend_message_queue: Arc<Mutex<MessageQueue<BackendMessage>>> = Arc::new(Mutex::new(MessageQueue::new()));
 
-    //create copies of message queues for the render thread.  these need to be copied because they're moved 
-    let render_frontend_message_queue = frontend_message_queue.clone();
-    let render_backend_message_queue = backend_message_queue.clone();
+    //buffers are shared with the main thread so they can still be written to swap files if the backend thread dies
+    let open_buffers: Arc<Mutex<Vec<FileBuffer>>> = Arc::new(Mutex::new(Vec::new()));
+
+    intermediary::panic_hook::install(open_buffers.clone());
+
+    //let the user know if the last session crashed with unsaved buffers
+    match backend::recovery::take_recovery_notice(&intermediary::paths::recovery_dir()) {
+        Ok(Some(notice)) => backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::Notification(notice)),
+        Ok(None) => {},
+        Err(error) => log::error!("Failed to check for recovered buffers: {}", error)
+    }
+
+    let mut supervisor = Supervisor::new();
+
+    //start backend thread
+    {
+        let frontend_message_queue = frontend_message_queue.clone();
+        let backend_message_queue = backend_message_queue.clone();
+        let open_buffers = open_buffers.clone();
+        supervisor.spawn("backend", move || {
+            backend::main::main(frontend_message_queue, backend_message_queue, open_buffers);
+        });
+    }
 
     //start rendering thread
-    thread::spawn(move || {
-        frontend::main::main(render_frontend_message_queue, render_backend_message_queue);
-    });
-
-    backend_message_queue.lock().unwrap().add_message(BackendMessage::TestMessage);
-
-    'running: loop{
-
-        //process messages from frontend
-        let mut incoming_queue = frontend_message_queue.lock().unwrap();
-   
-        while !incoming_queue.is_empty() {
-            let message = incoming_queue.get_message();
-
-            match message{
-                FrontendMessage::UserQuit => {
-                    break 'running;
-                },
-                FrontendMessage::DebugMessage(message) => {
-                    println!("[DEBUG] [FRONTEND]: {:?}", message)
-                }
-                _ => {}
-            }
-
-        }
-
-        drop(incoming_queue);    
-        
+    {
+        let frontend_message_queue = frontend_message_queue.clone();
+        let backend_message_queue = backend_message_queue.clone();
+        supervisor.spawn("render", move || {
+            frontend::main::main(frontend_message_queue, backend_message_queue, graphics_config);
+        });
+    }
+
+    //wait for either side to stop, whether that is the user quitting or a panic
+    let mut exits: Vec<ThreadExit> = Vec::new();
+    while exits.is_empty() {
+        exits = supervisor.try_join_finished();
         thread::sleep(Duration::from_millis(10));
     }
+
+    //tell whatever is still running to stop, and wait for it
+    request_shutdown(&frontend_message_queue, &backend_message_queue);
+    exits.append(&mut supervisor.join_all(SHUTDOWN_TIMEOUT));
+
+    for exit in &exits {
+        log::info!("Thread '{}' stopped: {:?}", exit.name, exit.status);
+    }
+
+    if exits.iter().any(|exit| exit.is_crash()) {
+        handle_crash(&exits, &open_buffers);
+    }
+
+    log::logger().flush();
+}
+
+///Asks both threads to finish what they're doing and exit.
+///Locks are taken even if poisoned, since the thread that poisoned them is already gone.
+fn request_shutdown(frontend_message_queue: &Arc<Mutex<MessageQueue<FrontendMessage>>>, backend_message_queue: &Arc<Mutex<MessageQueue<BackendMessage>>>){
+    frontend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(FrontendMessage::Shutdown);
+    backend_message_queue.lock().unwrap_or_else(|e| e.into_inner()).add_message(BackendMessage::Shutdown);
+}
+
+///Flushes swap files and saves dirty buffers after a thread has panicked or failed to stop.
+///Panics already got a crash report from the panic hook, so a report is only written here for threads that hung.
+fn handle_crash(exits: &[ThreadExit], open_buffers: &Arc<Mutex<Vec<FileBuffer>>>){
+    //a thread that timed out could still be holding the lock, so don't wait on it
+    let buffers_summary = match open_buffers.try_lock() {
+        Ok(buffers) => save_buffers(&buffers),
+        Err(TryLockError::Poisoned(poisoned)) => save_buffers(&poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => String::from("Buffers were still locked by a running thread and could not be saved")
+    };
+    log::warn!("{}", buffers_summary);
+
+    if exits.iter().any(|exit| matches!(exit.status, ThreadStatus::Panicked(..))) {
+        return;
+    }
+
+    let crashed = exits.iter().find(|exit| exit.is_crash()).unwrap();
+    let mut report = CrashReport::new(&crashed.name, "thread did not stop in time");
+
+    let threads: Vec<String> = exits.iter().map(|exit| format!("{}: {:?}", exit.name, exit.status)).collect();
+    report.add_section("Threads", &threads.join("\n"));
+    report.add_section("Saved buffers", &buffers_summary);
+
+    match report.write(&intermediary::paths::crash_dir()) {
+        Ok(path) => log::error!("Wrote crash report to {}", path.display()),
+        Err(error) => log::error!("Failed to write crash report: {}", error)
+    }
+}
+
+///Writes swap files and recovery copies of every dirty buffer, returning a summary of what was written.
+fn save_buffers(buffers: &[FileBuffer]) -> String{
+    let mut summary = Vec::new();
+
+    match backend::swap::flush_swap_files(buffers, &intermediary::paths::swap_dir()) {
+        Ok(paths) => summary.extend(paths.iter().map(|path| format!("swap: {}", path.display()))),
+        Err(error) => summary.push(format!("Failed to write swap files: {}", error))
+    }
+    match backend::recovery::save_dirty_buffers(buffers, &intermediary::paths::recovery_dir()) {
+        Ok(paths) => summary.extend(paths.iter().map(|path| format!("recovered: {}", path.display()))),
+        Err(error) => summary.push(format!("Failed to save recovery copies: {}", error))
+    }
+
+    if summary.is_empty() {
+        String::from("No unsaved buffers")
+    } else {
+        summary.join("\n")
+    }
 }
 
 #[cfg(test)]